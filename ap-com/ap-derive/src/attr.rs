@@ -0,0 +1,107 @@
+use syn::{
+    Attribute, Data, DeriveInput, Fields, Ident, Lit, Meta, NestedMeta, Result, Error,
+    spanned::Spanned,
+};
+
+/// Options given on the struct, ex. #[model(table = "categories", soft_delete)]
+#[derive(Default)]
+pub struct ModelAttrs {
+    pub table: Option<String>,
+    pub id_str: Option<String>,
+    pub sort: Option<Vec<String>>,
+    pub soft_delete: bool,
+    pub path: Option<String>,
+    pub routes: Option<syn::Path>,
+}
+
+/// A struct field and its column options, ex. #[model(rename = "kind", mutable, filter)]
+pub struct Column {
+    pub ident: Ident,
+    pub name: String,
+    pub skip: bool,
+    pub mutable: bool,
+    pub filter: bool,
+}
+
+impl ModelAttrs {
+
+    pub fn parse(input: &DeriveInput) -> Result<Self> {
+        let mut out = Self::default();
+        for meta in model_metas(&input.attrs)? {
+            match &meta {
+                Meta::NameValue(nv) if nv.path.is_ident("table") => out.table = Some(lit_str(&nv.lit)?),
+                Meta::NameValue(nv) if nv.path.is_ident("id_str") => out.id_str = Some(lit_str(&nv.lit)?),
+                Meta::NameValue(nv) if nv.path.is_ident("path") => out.path = Some(lit_str(&nv.lit)?),
+                Meta::NameValue(nv) if nv.path.is_ident("sort") => {
+                    out.sort = Some(lit_str(&nv.lit)?
+                        .split(',')
+                        .map(|col| col.trim().to_string())
+                        .filter(|col| !col.is_empty())
+                        .collect());
+                },
+                Meta::NameValue(nv) if nv.path.is_ident("routes") => {
+                    out.routes = Some(syn::parse_str(&lit_str(&nv.lit)?)
+                        .map_err(|e| Error::new(nv.lit.span(), e))?);
+                },
+                Meta::Path(path) if path.is_ident("soft_delete") => out.soft_delete = true,
+                _ => return Err(Error::new(meta.span(), "unknown model attribute")),
+            }
+        }
+        Ok(out)
+    }
+}
+
+impl Column {
+
+    pub fn parse_all(input: &DeriveInput) -> Result<Vec<Self>> {
+        let fields = match &input.data {
+            Data::Struct(data) => match &data.fields {
+                Fields::Named(fields) => &fields.named,
+                _ => return Err(Error::new(input.span(), "Model can only be derived for structs with named fields")),
+            },
+            _ => return Err(Error::new(input.span(), "Model can only be derived for structs")),
+        };
+        let mut cols = Vec::new();
+        for field in fields.iter() {
+            let ident = field.ident.clone().expect("named field");
+            let mut col = Column {
+                name: ident.to_string().trim_start_matches("r#").to_string(),
+                ident, skip: false, mutable: false, filter: false,
+            };
+            for meta in model_metas(&field.attrs)? {
+                match &meta {
+                    Meta::NameValue(nv) if nv.path.is_ident("rename") => col.name = lit_str(&nv.lit)?,
+                    Meta::Path(path) if path.is_ident("skip") => col.skip = true,
+                    Meta::Path(path) if path.is_ident("mutable") => col.mutable = true,
+                    Meta::Path(path) if path.is_ident("filter") => col.filter = true,
+                    _ => return Err(Error::new(meta.span(), "unknown model field attribute")),
+                }
+            }
+            cols.push(col);
+        }
+        Ok(cols)
+    }
+}
+
+fn model_metas(attrs: &[Attribute]) -> Result<Vec<Meta>> {
+    let mut out = Vec::new();
+    for attr in attrs.iter().filter(|a| a.path.is_ident("model")) {
+        match attr.parse_meta()? {
+            Meta::List(list) => for nested in list.nested.into_iter() {
+                match nested {
+                    NestedMeta::Meta(meta) => out.push(meta),
+                    NestedMeta::Lit(lit) => return Err(Error::new(lit.span(), "expected a model attribute")),
+                }
+            },
+            meta => return Err(Error::new(meta.span(), "expected #[model(...)]")),
+        }
+    }
+    Ok(out)
+}
+
+fn lit_str(lit: &Lit) -> Result<String> {
+    match lit {
+        Lit::Str(s) => Ok(s.value()),
+        _ => Err(Error::new(lit.span(), "expected a string literal")),
+    }
+}
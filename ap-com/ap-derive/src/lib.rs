@@ -0,0 +1,178 @@
+//! Derive macros for ap_com's Model and ModelRoutes traits. Both read #[model(...)] attributes:
+//!
+//! ```ignore
+//! #[derive(Model, ModelRoutes)]
+//! #[model(table = "categories", id_str = "category_id", path = "/category")]
+//! pub struct Category {
+//!     pub id: Id,
+//!     #[model(mutable, filter)]
+//!     pub name: String,
+//!     #[model(skip)]
+//!     pub score: Option<f64>,
+//!     ...
+//! }
+//! ```
+//!
+//! On the struct: table, id_str, sort = "col, col" (sortable_columns), soft_delete, and for
+//!     ModelRoutes, path and routes = "path::to::fn" (used as model_routes()).
+//! On fields: skip (never written -- for columns the database fills in), rename = "col"
+//!     (pair with #[sqlx(rename = "col")] so FromRow agrees), mutable and filter.
+mod attr;
+mod sql;
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput};
+use attr::{ModelAttrs, Column};
+
+/// Implements Model, generating table(), id(), insert() and the column lists from the
+///     struct's fields, plus an inherent save() writing back the mutable columns
+#[proc_macro_derive(Model, attributes(model))]
+pub fn derive_model(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_model(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+/// Implements ModelRoutes, with the path and model_routes() given by #[model(path, routes)]
+#[proc_macro_derive(ModelRoutes, attributes(model))]
+pub fn derive_model_routes(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand_model_routes(&input)
+        .unwrap_or_else(|e| e.to_compile_error())
+        .into()
+}
+
+fn expand_model(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let attrs = ModelAttrs::parse(input)?;
+    let cols = Column::parse_all(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+
+    let table = attrs.table.clone().unwrap_or_else(|| sql::table_name(&ident.to_string()));
+    let written = cols.iter().filter(|c| !c.skip).collect::<Vec<&Column>>();
+    let insert_sql = sql::insert(&table, &written.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>());
+    let insert_fields = written.iter().map(|c| &c.ident);
+
+    let id = cols.iter().find(|c| c.name == "id").map(|c| {
+        let id_ident = &c.ident;
+        quote! {
+            #[inline]
+            fn id(self) -> ::ap_com::Id { self.#id_ident }
+        }
+    });
+    let id_str = attrs.id_str.as_ref().map(|id_str| quote! {
+        #[inline]
+        fn id_str() -> String { String::from(#id_str) }
+    });
+    let sortable = attrs.sort.as_ref().map(|sort| quote! {
+        #[inline]
+        fn sortable_columns() -> Vec<&'static str> { vec![#(#sort),*] }
+    });
+    let soft_delete = if attrs.soft_delete {
+        Some(quote! {
+            #[inline]
+            fn soft_delete() -> bool { true }
+        })
+    } else {
+        None
+    };
+    let mutable = written.iter().filter(|c| c.mutable).collect::<Vec<&&Column>>();
+    let mutable_names = mutable.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>();
+    let mutable_columns = if mutable.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            #[inline]
+            fn mutable_columns() -> Vec<&'static str> { vec![#(#mutable_names),*] }
+        })
+    };
+    let col_names = cols.iter().map(|c| c.name.as_str()).collect::<Vec<&str>>();
+    let filter_names = cols.iter().filter(|c| c.filter).map(|c| c.name.as_str()).collect::<Vec<&str>>();
+    let filterable_columns = if filter_names.is_empty() {
+        None
+    } else {
+        Some(quote! {
+            #[inline]
+            fn filterable_columns() -> Vec<&'static str> { vec![#(#filter_names),*] }
+        })
+    };
+
+    let save = if mutable.is_empty() {
+        None
+    } else {
+        let touch = written.iter().any(|c| c.name == "updated_at") && !mutable_names.contains(&"updated_at");
+        let update_sql = sql::update(&table, &mutable_names, touch, attrs.soft_delete);
+        let update_fields = mutable.iter().map(|c| &c.ident);
+        let touch_bind = if touch { Some(quote! { .bind(::ap_com::now()) }) } else { None };
+        Some(quote! {
+            impl #impl_generics #ident #ty_generics #where_clause {
+                /// Write this row's mutable columns back to the database by id, deferring
+                ///     an Updated event. Returns None if no such row exists
+                pub async fn save<'a, A>(&self, db: A) -> ::sqlx::Result<Option<Self>>
+                where
+                    A: ::sqlx::Acquire<'a, Database = ::sqlx::Postgres> + Send
+                {
+                    let mut conn = db.acquire().await?;
+                    let old = <Self as ::ap_com::models::Model>::get_for_update(&mut *conn, self.id.clone()).await?;
+                    let res = ::sqlx::query_as::<::sqlx::Postgres, Self>(#update_sql)
+                        .bind(&self.id)
+                        #(.bind(&self.#update_fields))*
+                        #touch_bind
+                        .fetch_optional(&mut *conn).await?;
+                    if let (Some(old), Some(new)) = (old, &res) {
+                        ::ap_com::models::event::defer_update(old, new.clone());
+                    }
+                    Ok(res)
+                }
+            }
+        })
+    };
+
+    Ok(quote! {
+        #[::async_trait::async_trait]
+        impl #impl_generics ::ap_com::models::Model for #ident #ty_generics #where_clause {
+            #[inline]
+            fn table() -> String { String::from(#table) }
+            #[inline]
+            fn columns() -> Vec<&'static str> { vec![#(#col_names),*] }
+            #id
+            #id_str
+            #mutable_columns
+            #filterable_columns
+            #sortable
+            #soft_delete
+
+            async fn insert<'e, E: ::sqlx::PgExecutor<'e>>(self, db: E) -> ::sqlx::Result<Self> {
+                let res = ::sqlx::query_as::<::sqlx::Postgres, Self>(#insert_sql)
+                    #(.bind(&self.#insert_fields))*
+                    .fetch_one(db).await?;
+                Ok(::ap_com::models::event::created(res))
+            }
+        }
+        #save
+    })
+}
+
+fn expand_model_routes(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let attrs = ModelAttrs::parse(input)?;
+    let ident = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let path = attrs.path.as_ref().map(|path| quote! {
+        #[inline]
+        fn path() -> String { String::from(#path) }
+    });
+    let routes = attrs.routes.as_ref().map(|routes| quote! { #routes(cfg); });
+    Ok(quote! {
+        #[::async_trait::async_trait]
+        impl #impl_generics ::ap_com::models::ModelRoutes for #ident #ty_generics #where_clause {
+            #path
+
+            fn model_routes(cfg: &mut ::actix_web::web::ServiceConfig) {
+                #routes
+                let _ = cfg;
+            }
+        }
+    })
+}
@@ -0,0 +1,63 @@
+//! SQL generated for derived models, kept as plain string building so it can be tested
+//!     without expanding the macros
+
+/// Default table name for a struct -- snake case and pluralized with an 's',
+///     ex. FieldValue -> field_values. Use #[model(table = "...")] for anything irregular
+pub fn table_name(ident: &str) -> String {
+    let mut out = String::new();
+    for (i, c) in ident.chars().enumerate() {
+        if c.is_uppercase() {
+            if i > 0 {
+                out.push('_');
+            }
+            out.extend(c.to_lowercase());
+        } else {
+            out.push(c);
+        }
+    }
+    out.push('s');
+    out
+}
+
+/// A quoted identifier, as ap_com::query::sql::Ident writes them
+pub fn quote(name: &str) -> String {
+    format!("\"{}\"", name.replace('"', "\"\""))
+}
+
+/// INSERT binding every column in order as $1..$n
+pub fn insert(table: &str, cols: &[&str]) -> String {
+    let params = (1..=cols.len())
+        .map(|n| format!("${}", n))
+        .collect::<Vec<String>>();
+    let cols = cols.iter().map(|col| quote(col)).collect::<Vec<String>>();
+    format!("INSERT INTO {} ({}) VALUES ({}) RETURNING *",
+        quote(table), cols.join(", "), params.join(", "))
+}
+
+/// UPDATE of the given columns by id, binding the id as $1 and the columns from $2.
+///     With touch, updated_at is bound last
+pub fn update(table: &str, cols: &[&str], touch: bool, soft_delete: bool) -> String {
+    let mut set = cols.iter().enumerate()
+        .map(|(i, col)| format!("{} = ${}", quote(col), i + 2))
+        .collect::<Vec<String>>();
+    if touch {
+        set.push(format!("\"updated_at\" = ${}", cols.len() + 2));
+    }
+    let live = if soft_delete { " AND \"status\" <> 'deleted'" } else { "" };
+    format!("UPDATE {} SET {} WHERE \"id\" = $1{} RETURNING *", quote(table), set.join(", "), live)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn generates_numbered_placeholders() {
+        assert_eq!(table_name("FieldValue"), "field_values");
+        assert_eq!(insert("categories", &["id", "name", "description"]),
+            r#"INSERT INTO "categories" ("id", "name", "description") VALUES ($1, $2, $3) RETURNING *"#);
+        assert_eq!(update("records", &["name", "private"], true, true),
+            r#"UPDATE "records" SET "name" = $2, "private" = $3, "updated_at" = $4 WHERE "id" = $1 AND "status" <> 'deleted' RETURNING *"#);
+        assert_eq!(quote("odd\"name"), r#""odd""name""#);
+    }
+}
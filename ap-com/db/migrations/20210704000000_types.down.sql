@@ -0,0 +1,15 @@
+DROP TYPE IF EXISTS field_kind;
+DROP TYPE IF EXISTS provider_id;
+DROP TYPE IF EXISTS provider_type;
+DROP TYPE IF EXISTS group_role;
+DROP TYPE IF EXISTS gender;
+DROP TYPE IF EXISTS role;
+DROP TYPE IF EXISTS feeling;
+DROP TYPE IF EXISTS status;
+
+DROP DOMAIN IF EXISTS expiration;
+DROP DOMAIN IF EXISTS refresh_token;
+DROP DOMAIN IF EXISTS access_token;
+DROP DOMAIN IF EXISTS session_token;
+DROP DOMAIN IF EXISTS jwt;
+DROP DOMAIN IF EXISTS id;
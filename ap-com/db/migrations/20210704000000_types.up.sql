@@ -0,0 +1,19 @@
+-- Enums and domains the models bind by name (see ap_com::types). Enum labels are the
+-- lowercased Rust variants, per #[sqlx(rename_all = "lowercase")].
+
+CREATE DOMAIN id AS TEXT;
+CREATE DOMAIN jwt AS TEXT;
+CREATE DOMAIN session_token AS TEXT;
+CREATE DOMAIN access_token AS TEXT;
+CREATE DOMAIN refresh_token AS TEXT;
+CREATE DOMAIN expiration AS TIMESTAMP;
+
+CREATE TYPE status AS ENUM ('active', 'archived', 'deleted', 'deferred');
+CREATE TYPE feeling AS ENUM ('happy', 'sad', 'angry', 'tired');
+CREATE TYPE role AS ENUM ('admin', 'superuser', 'user');
+CREATE TYPE gender AS ENUM ('male', 'female', 'other', 'prefernottosay');
+CREATE TYPE group_role AS ENUM ('admin', 'moderator', 'member');
+CREATE TYPE provider_type AS ENUM ('credentials', 'oauth');
+CREATE TYPE provider_id AS ENUM ('devisa', 'google', 'github', 'gitlab', 'facebook', 'linkedin', 'twitter');
+CREATE TYPE field_kind AS ENUM ('integer', 'realnum', 'double', 'range', 'date', 'datetime',
+    'enumeration', 'selection', 'text', 'boolean');
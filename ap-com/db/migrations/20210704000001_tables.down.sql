@@ -0,0 +1,34 @@
+DROP TABLE IF EXISTS direct_topic_messages;
+DROP TABLE IF EXISTS direct_group_messages;
+DROP TABLE IF EXISTS direct_user_messages;
+DROP TABLE IF EXISTS post_book_entries;
+DROP TABLE IF EXISTS post_books;
+DROP TABLE IF EXISTS topic_posts;
+DROP TABLE IF EXISTS group_posts;
+DROP TABLE IF EXISTS post_feeling_responses;
+DROP TABLE IF EXISTS posts;
+DROP TABLE IF EXISTS topic_votes;
+DROP TABLE IF EXISTS topic_categories;
+DROP TABLE IF EXISTS categories;
+DROP TABLE IF EXISTS topics;
+DROP TABLE IF EXISTS group_users;
+DROP TABLE IF EXISTS groups;
+DROP TABLE IF EXISTS channels;
+DROP TABLE IF EXISTS tasks;
+DROP TABLE IF EXISTS actions;
+DROP TABLE IF EXISTS item_fields;
+DROP TABLE IF EXISTS record_items;
+DROP TABLE IF EXISTS field_targets;
+DROP TABLE IF EXISTS field_values;
+DROP TABLE IF EXISTS fields;
+DROP TABLE IF EXISTS items;
+DROP TABLE IF EXISTS records;
+DROP TABLE IF EXISTS links;
+DROP TABLE IF EXISTS user_badges;
+DROP TABLE IF EXISTS user_levels;
+DROP TABLE IF EXISTS profiles;
+DROP TABLE IF EXISTS verification_requests;
+DROP TABLE IF EXISTS sessions;
+DROP TABLE IF EXISTS accounts;
+DROP TABLE IF EXISTS credentials;
+DROP TABLE IF EXISTS users;
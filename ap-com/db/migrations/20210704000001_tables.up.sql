@@ -0,0 +1,392 @@
+-- Model tables. Each table's columns follow its model struct, and Model::table() names
+-- the table. Rows belonging to a user are removed with the user. Ids default to a random
+-- UUID like Id::gen(), for inserts which leave them to the database (Postgres 13+).
+
+CREATE TABLE users (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    name            TEXT,
+    email           TEXT UNIQUE,
+    email_verified  TIMESTAMP,
+    image           TEXT,
+    created_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE credentials (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    username    TEXT NOT NULL UNIQUE,
+    password    TEXT NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE accounts (
+    id                      id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id                 id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider_type           provider_type NOT NULL,
+    provider_id             provider_id NOT NULL,
+    provider_account_id     id NOT NULL,
+    refresh_token           refresh_token,
+    access_token            access_token,
+    access_token_expires    expiration,
+    created_at              TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at              TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (provider_id, provider_account_id)
+);
+
+CREATE TABLE sessions (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id         id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires         expiration NOT NULL,
+    session_token   session_token NOT NULL UNIQUE,
+    access_token    access_token NOT NULL UNIQUE,
+    created_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE verification_requests (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    identifier  TEXT NOT NULL,
+    token       TEXT NOT NULL UNIQUE,
+    expires     TIMESTAMP NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE profiles (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id         id NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    bio             TEXT,
+    role            role NOT NULL DEFAULT 'user',
+    cover_image     TEXT,
+    referral        TEXT,
+    phone_number    TEXT,
+    company         TEXT,
+    postal_code     TEXT,
+    state           TEXT,
+    country         TEXT,
+    city            TEXT,
+    website         TEXT,
+    occupation      TEXT,
+    facebook_url    TEXT,
+    linkedin_url    TEXT,
+    twitter_url     TEXT,
+    education       TEXT,
+    gender          gender,
+    birthday        TIMESTAMP,
+    created_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE user_levels (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    level       INTEGER NOT NULL DEFAULT 1 CHECK (level >= 0),
+    exp         REAL NOT NULL DEFAULT 0,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE user_badges (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_level_id   id NOT NULL REFERENCES user_levels (id) ON DELETE CASCADE,
+    name            TEXT NOT NULL,
+    description     TEXT NOT NULL,
+    condition       TEXT NOT NULL,
+    achieved_at     TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE links (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    name        TEXT NOT NULL,
+    value       TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE records (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    status      status NOT NULL DEFAULT 'active',
+    description TEXT,
+    image       TEXT,
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE items (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    status      status NOT NULL DEFAULT 'active',
+    image       TEXT,
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE fields (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    kind        field_kind NOT NULL DEFAULT 'text',
+    status      status NOT NULL DEFAULT 'active',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE field_values (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    field_id    id NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    value       BYTEA NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+-- FieldTarget still uses integer keys
+CREATE TABLE field_targets (
+    id          SERIAL PRIMARY KEY,
+    field_id    INTEGER NOT NULL,
+    name        TEXT NOT NULL,
+    description TEXT,
+    value       BYTEA NOT NULL DEFAULT '',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE record_items (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    link_id     id REFERENCES links (id) ON DELETE SET NULL,
+    record_id   id NOT NULL REFERENCES records (id) ON DELETE CASCADE,
+    item_id     id NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    name        TEXT,
+    description TEXT,
+    status      status NOT NULL DEFAULT 'active',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE item_fields (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    link_id     id REFERENCES links (id) ON DELETE SET NULL,
+    item_id     id NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    field_id    id NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    name        TEXT,
+    description TEXT,
+    status      status NOT NULL DEFAULT 'active',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE actions (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    status      status NOT NULL DEFAULT 'active',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE tasks (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    status      status NOT NULL DEFAULT 'active',
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE channels (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE groups (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    image       TEXT,
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    status      status NOT NULL DEFAULT 'active',
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE group_users (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    group_id    id NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    link_id     id REFERENCES links (id) ON DELETE SET NULL,
+    name        TEXT,
+    description TEXT,
+    role        group_role DEFAULT 'member',
+    status      status NOT NULL DEFAULT 'active',
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (user_id, group_id)
+);
+
+CREATE TABLE topics (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    name        TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE categories (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    name        TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE topic_categories (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category_id id NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+    topic_id    id NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    score       DOUBLE PRECISION NOT NULL DEFAULT 0,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE topic_votes (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    topic_id    id NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    is_for      BOOLEAN NOT NULL,
+    feeling     feeling,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE posts (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id         id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    responds_to_id  id REFERENCES posts (id) ON DELETE SET NULL,
+    image           TEXT,
+    content         TEXT NOT NULL,
+    feeling         feeling,
+    private         BOOLEAN NOT NULL DEFAULT TRUE,
+    status          status NOT NULL DEFAULT 'active',
+    created_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE post_feeling_responses (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    post_id     id NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    feeling     feeling NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE group_posts (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    group_id    id NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    post_id     id NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE topic_posts (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    post_id     id NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    topic_id    id NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    link_id     id REFERENCES links (id) ON DELETE SET NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE post_books (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    user_id     id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    status      status NOT NULL DEFAULT 'active',
+    description TEXT,
+    image       TEXT,
+    private     BOOLEAN NOT NULL DEFAULT TRUE,
+    wiki        BOOLEAN NOT NULL DEFAULT FALSE,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE post_book_entries (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    post_id     id NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    book_id     id NOT NULL REFERENCES post_books (id) ON DELETE CASCADE,
+    link_id     id REFERENCES links (id) ON DELETE SET NULL
+);
+
+CREATE TABLE direct_user_messages (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    sender_id       id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    recipient_id    id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    replies_to_id   id REFERENCES direct_user_messages (id) ON DELETE SET NULL,
+    content         TEXT NOT NULL,
+    attachments     TEXT[] NOT NULL DEFAULT '{}',
+    sent_at         TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    read_at         TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE direct_group_messages (
+    id                  id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    sender_id           id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    group_id            id NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    replies_to_id       id REFERENCES direct_group_messages (id) ON DELETE SET NULL,
+    content             TEXT NOT NULL,
+    attachments         TEXT[] NOT NULL DEFAULT '{}',
+    restrict_to_role    group_role,
+    sent_at             TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at          TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE TABLE direct_topic_messages (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    sender_id       id NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    topic_id        id NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    replies_to_id   id REFERENCES direct_topic_messages (id) ON DELETE SET NULL,
+    content         TEXT NOT NULL,
+    attachments     TEXT[] NOT NULL DEFAULT '{}',
+    sent_at         TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE INDEX accounts_user_id_idx ON accounts (user_id);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+CREATE INDEX records_user_id_idx ON records (user_id);
+CREATE INDEX items_user_id_idx ON items (user_id);
+CREATE INDEX fields_user_id_idx ON fields (user_id);
+CREATE INDEX field_values_field_id_idx ON field_values (field_id, created_at);
+CREATE INDEX record_items_record_id_idx ON record_items (record_id);
+CREATE INDEX record_items_item_id_idx ON record_items (item_id);
+CREATE INDEX item_fields_item_id_idx ON item_fields (item_id);
+CREATE INDEX item_fields_field_id_idx ON item_fields (field_id);
+CREATE INDEX posts_user_id_idx ON posts (user_id);
+CREATE INDEX group_users_group_id_idx ON group_users (group_id);
+CREATE INDEX topic_posts_topic_id_idx ON topic_posts (topic_id);
+CREATE INDEX direct_user_messages_recipient_id_idx ON direct_user_messages (recipient_id, sent_at);
+CREATE INDEX direct_group_messages_group_id_idx ON direct_group_messages (group_id, sent_at);
+CREATE INDEX direct_topic_messages_topic_id_idx ON direct_topic_messages (topic_id, sent_at);
@@ -0,0 +1,18 @@
+DO $$
+DECLARE
+    t regclass;
+BEGIN
+    FOR t IN
+        SELECT tgrelid::regclass FROM pg_trigger WHERE tgname = 'audit_row'
+    LOOP
+        EXECUTE format('DROP TRIGGER IF EXISTS audit_row ON %s', t);
+    END LOOP;
+END
+$$;
+
+DROP FUNCTION IF EXISTS audit_table(regclass);
+DROP FUNCTION IF EXISTS audit_row();
+DROP FUNCTION IF EXISTS audit_masked();
+DROP TABLE IF EXISTS audit_log;
+DROP FUNCTION IF EXISTS audit_log_append_only();
+DROP TYPE IF EXISTS audit_op;
@@ -0,0 +1,79 @@
+-- Append-only log of every insert, update and delete on the model tables.
+-- Rows are written by the audit_row() trigger; the acting user is read from the
+-- transaction-local ap.actor_id setting (see ap_com::models::audit).
+
+CREATE TYPE audit_op AS ENUM ('insert', 'update', 'delete');
+
+CREATE TABLE IF NOT EXISTS audit_log (
+    id          BIGSERIAL PRIMARY KEY,
+    op          audit_op NOT NULL,
+    actor_id    TEXT,
+    table_name  TEXT NOT NULL,
+    row_id      TEXT NOT NULL,
+    before      JSONB,
+    after       JSONB,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc')
+);
+
+CREATE INDEX IF NOT EXISTS audit_log_row_idx ON audit_log (table_name, row_id, id);
+CREATE INDEX IF NOT EXISTS audit_log_actor_idx ON audit_log (actor_id, id);
+
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    RAISE EXCEPTION 'audit_log is append-only';
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER audit_log_append_only
+    BEFORE UPDATE OR DELETE OR TRUNCATE ON audit_log
+    FOR EACH STATEMENT EXECUTE FUNCTION audit_log_append_only();
+
+-- Columns holding secrets, which are left out of every snapshot
+CREATE OR REPLACE FUNCTION audit_masked() RETURNS text[] AS $$
+    SELECT ARRAY['password', 'access_token', 'refresh_token', 'session_token', 'token']
+$$ LANGUAGE sql IMMUTABLE;
+
+CREATE OR REPLACE FUNCTION audit_row() RETURNS trigger AS $$
+BEGIN
+    INSERT INTO audit_log (op, actor_id, table_name, row_id, before, after)
+    VALUES (
+        lower(TG_OP)::audit_op,
+        NULLIF(current_setting('ap.actor_id', true), ''),
+        TG_TABLE_NAME,
+        CASE WHEN TG_OP = 'DELETE' THEN OLD.id::text ELSE NEW.id::text END,
+        CASE WHEN TG_OP = 'INSERT' THEN NULL ELSE to_jsonb(OLD) - audit_masked() END,
+        CASE WHEN TG_OP = 'DELETE' THEN NULL ELSE to_jsonb(NEW) - audit_masked() END
+    );
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- Attach audit_row() to a table. Call from migrations adding tables after this one
+CREATE OR REPLACE FUNCTION audit_table(target regclass) RETURNS void AS $$
+BEGIN
+    EXECUTE format('DROP TRIGGER IF EXISTS audit_row ON %s', target);
+    EXECUTE format('CREATE TRIGGER audit_row AFTER INSERT OR UPDATE OR DELETE ON %s
+        FOR EACH ROW EXECUTE FUNCTION audit_row()', target);
+END
+$$ LANGUAGE plpgsql;
+
+-- Every existing public table with an id column, except sessions and verification
+-- requests, which hold nothing but short-lived tokens
+DO $$
+DECLARE
+    t regclass;
+BEGIN
+    FOR t IN
+        SELECT format('%I.%I', c.table_schema, c.table_name)::regclass
+        FROM information_schema.columns c
+        JOIN information_schema.tables tb
+            ON tb.table_schema = c.table_schema AND tb.table_name = c.table_name
+        WHERE c.table_schema = 'public'
+          AND c.column_name = 'id'
+          AND tb.table_type = 'BASE TABLE'
+          AND c.table_name NOT IN ('audit_log', 'sessions', 'verification_requests')
+    LOOP
+        PERFORM audit_table(t);
+    END LOOP;
+END
+$$;
@@ -0,0 +1,14 @@
+DO $$
+DECLARE
+    t regclass;
+BEGIN
+    FOR t IN
+        SELECT tgrelid::regclass FROM pg_trigger WHERE tgname = 'notify_change'
+    LOOP
+        EXECUTE format('DROP TRIGGER IF EXISTS notify_change ON %s', t);
+    END LOOP;
+END
+$$;
+
+DROP FUNCTION IF EXISTS notify_table(regclass);
+DROP FUNCTION IF EXISTS notify_change();
@@ -0,0 +1,44 @@
+-- Change feed for the model tables. Every committed insert, update and delete sends a
+-- compact notification on the model_changes channel, ex.
+--   {"table": "records", "op": "update", "id": "..."}
+-- which ap_com::db::listen turns into typed events.
+
+CREATE OR REPLACE FUNCTION notify_change() RETURNS trigger AS $$
+BEGIN
+    PERFORM pg_notify('model_changes', json_build_object(
+        'table', TG_TABLE_NAME,
+        'op', lower(TG_OP),
+        'id', CASE WHEN TG_OP = 'DELETE' THEN OLD.id::text ELSE NEW.id::text END
+    )::text);
+    RETURN NULL;
+END
+$$ LANGUAGE plpgsql;
+
+-- Attach notify_change() to a table. Call from migrations adding tables after this one
+CREATE OR REPLACE FUNCTION notify_table(target regclass) RETURNS void AS $$
+BEGIN
+    EXECUTE format('DROP TRIGGER IF EXISTS notify_change ON %s', target);
+    EXECUTE format('CREATE TRIGGER notify_change AFTER INSERT OR UPDATE OR DELETE ON %s
+        FOR EACH ROW EXECUTE FUNCTION notify_change()', target);
+END
+$$ LANGUAGE plpgsql;
+
+-- Every existing public table with an id column
+DO $$
+DECLARE
+    t regclass;
+BEGIN
+    FOR t IN
+        SELECT format('%I.%I', c.table_schema, c.table_name)::regclass
+        FROM information_schema.columns c
+        JOIN information_schema.tables tb
+            ON tb.table_schema = c.table_schema AND tb.table_name = c.table_name
+        WHERE c.table_schema = 'public'
+          AND c.column_name = 'id'
+          AND tb.table_type = 'BASE TABLE'
+          AND c.table_name <> 'audit_log'
+    LOOP
+        PERFORM notify_table(t);
+    END LOOP;
+END
+$$;
@@ -0,0 +1,23 @@
+-- Options of enumeration and selection fields. Stored field values hold option ids, so
+-- options can be renamed without rewriting them. A merged option is deprecated and points
+-- at the option it was merged into (see ap_com::models::field::options).
+
+CREATE TABLE IF NOT EXISTS field_options (
+    id          id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    field_id    id NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    label       TEXT NOT NULL CHECK (btrim(label) <> ''),
+    color       TEXT CHECK (color ~ '^#[0-9a-fA-F]{6}$'),
+    position    INTEGER NOT NULL DEFAULT 0,
+    deprecated  BOOLEAN NOT NULL DEFAULT FALSE,
+    merged_into id REFERENCES field_options (id) ON DELETE SET NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at  TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    CHECK (merged_into IS NULL OR deprecated)
+);
+
+CREATE INDEX IF NOT EXISTS field_options_field_id_idx ON field_options (field_id, position);
+CREATE UNIQUE INDEX IF NOT EXISTS field_options_label_idx ON field_options (field_id, lower(label))
+    WHERE merged_into IS NULL;
+
+SELECT audit_table('field_options');
+SELECT notify_table('field_options');
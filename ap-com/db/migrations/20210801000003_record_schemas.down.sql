@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS record_schema_fields;
+DROP INDEX IF EXISTS field_values_item_id_idx;
+ALTER TABLE field_values DROP COLUMN IF EXISTS item_id;
@@ -0,0 +1,24 @@
+-- Record schemas: the fields every item in a record must have (see
+-- ap_com::models::record::schema). Defaults are stored in the same encoding as
+-- field_values.value, which may now belong to an item.
+
+ALTER TABLE field_values ADD COLUMN IF NOT EXISTS item_id id REFERENCES items (id) ON DELETE CASCADE;
+
+CREATE INDEX IF NOT EXISTS field_values_item_id_idx ON field_values (item_id, field_id, created_at);
+
+CREATE TABLE IF NOT EXISTS record_schema_fields (
+    id              id PRIMARY KEY DEFAULT gen_random_uuid()::text,
+    record_id       id NOT NULL REFERENCES records (id) ON DELETE CASCADE,
+    field_id        id NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    required        BOOLEAN NOT NULL DEFAULT FALSE,
+    default_value   BYTEA,
+    position        INTEGER NOT NULL DEFAULT 0,
+    created_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    updated_at      TIMESTAMP NOT NULL DEFAULT (NOW() AT TIME ZONE 'utc'),
+    UNIQUE (record_id, field_id)
+);
+
+CREATE INDEX IF NOT EXISTS record_schema_fields_field_id_idx ON record_schema_fields (field_id);
+
+SELECT audit_table('record_schema_fields');
+SELECT notify_table('record_schema_fields');
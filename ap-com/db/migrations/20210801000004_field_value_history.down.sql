@@ -0,0 +1,6 @@
+DROP TRIGGER IF EXISTS field_values_append_only ON field_values;
+DROP FUNCTION IF EXISTS field_values_append_only();
+DROP INDEX IF EXISTS field_values_series_idx;
+CREATE INDEX IF NOT EXISTS field_values_item_id_idx ON field_values (item_id, field_id, created_at);
+ALTER TABLE field_values DROP COLUMN IF EXISTS num;
+ALTER TABLE field_values DROP COLUMN IF EXISTS recorded_at;
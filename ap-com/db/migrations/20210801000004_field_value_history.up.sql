@@ -0,0 +1,46 @@
+-- Field values are kept as their history: a new value is a new row, recorded at a time which
+-- may be in the past, and rows are never updated. num holds the values of integer, realnum
+-- and double fields as a double for aggregation (see ap_com::models::field::series).
+
+ALTER TABLE field_values ADD COLUMN IF NOT EXISTS recorded_at TIMESTAMP;
+UPDATE field_values SET recorded_at = created_at WHERE recorded_at IS NULL;
+ALTER TABLE field_values
+    ALTER COLUMN recorded_at SET NOT NULL,
+    ALTER COLUMN recorded_at SET DEFAULT (NOW() AT TIME ZONE 'utc');
+
+-- Numbers are stored as 8 big-endian bytes: integers as a bigint, realnums and doubles as
+-- an IEEE 754 double, which is split into its sign, exponent and mantissa to backfill num.
+ALTER TABLE field_values ADD COLUMN IF NOT EXISTS num DOUBLE PRECISION;
+UPDATE field_values v SET num = CASE
+        WHEN n.kind = 'integer' THEN n.bits
+        WHEN n.exponent = 0 THEN n.sign * n.mantissa * power(2::float8, -1074)
+        WHEN n.exponent < 2047 THEN n.sign * (n.mantissa + 4503599627370496) * power(2::float8, n.exponent - 1075)
+    END
+    FROM (
+        SELECT v.id, f.kind, b.bits,
+            CASE WHEN b.bits < 0 THEN -1 ELSE 1 END AS sign,
+            (b.bits >> 52) & 2047 AS exponent,
+            b.bits & 4503599627370495 AS mantissa
+        FROM field_values v
+        INNER JOIN fields f ON f.id = v.field_id
+        CROSS JOIN LATERAL (SELECT ('x' || encode(v.value, 'hex'))::bit(64)::bigint AS bits) b
+        WHERE f.kind IN ('integer', 'realnum', 'double') AND length(v.value) = 8
+    ) n
+    WHERE n.id = v.id;
+
+DROP INDEX IF EXISTS field_values_item_id_idx;
+CREATE INDEX IF NOT EXISTS field_values_series_idx ON field_values (field_id, item_id, recorded_at);
+
+CREATE OR REPLACE FUNCTION field_values_append_only() RETURNS trigger AS $$
+BEGIN
+    IF (NEW.field_id, NEW.item_id, NEW.value, NEW.num, NEW.recorded_at)
+        IS DISTINCT FROM (OLD.field_id, OLD.item_id, OLD.value, OLD.num, OLD.recorded_at) THEN
+        RAISE EXCEPTION 'field_values are append-only, record a new value instead';
+    END IF;
+    RETURN NEW;
+END
+$$ LANGUAGE plpgsql;
+
+CREATE TRIGGER field_values_append_only
+    BEFORE UPDATE ON field_values
+    FOR EACH ROW EXECUTE FUNCTION field_values_append_only();
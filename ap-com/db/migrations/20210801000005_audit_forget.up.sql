@@ -0,0 +1,12 @@
+-- Let account erasure remove the erased user's rows from the audit log. The append-only
+-- trigger stands aside only while the transaction-local ap.audit_forget setting is on,
+-- which ap_com::models::audit::forget sets around its own statements.
+
+CREATE OR REPLACE FUNCTION audit_log_append_only() RETURNS trigger AS $$
+BEGIN
+    IF TG_OP IN ('UPDATE', 'DELETE') AND current_setting('ap.audit_forget', true) = 'on' THEN
+        RETURN NULL;
+    END IF;
+    RAISE EXCEPTION 'audit_log is append-only';
+END
+$$ LANGUAGE plpgsql;
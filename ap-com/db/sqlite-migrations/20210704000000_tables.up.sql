@@ -0,0 +1,392 @@
+-- The model tables for the SQLite backend (see ap_com::db::sqlite). Mirrors the Postgres
+-- schema in db/migrations: enums are TEXT checked against their labels, ids and tokens
+-- are TEXT, and timestamps are stored as text in UTC. Array columns hold JSON text.
+
+CREATE TABLE users (
+    id              TEXT PRIMARY KEY,
+    name            TEXT,
+    email           TEXT UNIQUE,
+    email_verified  TIMESTAMP,
+    image           TEXT,
+    created_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE credentials (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    username    TEXT NOT NULL UNIQUE,
+    password    TEXT NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE accounts (
+    id                      TEXT PRIMARY KEY,
+    user_id                 TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    provider_type           TEXT NOT NULL CHECK (provider_type IN ('credentials', 'oauth')),
+    provider_id             TEXT NOT NULL CHECK (provider_id IN ('devisa', 'google', 'github', 'gitlab', 'facebook', 'linkedin', 'twitter')),
+    provider_account_id     TEXT NOT NULL,
+    refresh_token           TEXT,
+    access_token            TEXT,
+    access_token_expires    TIMESTAMP,
+    created_at              TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at              TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (provider_id, provider_account_id)
+);
+
+CREATE TABLE sessions (
+    id              TEXT PRIMARY KEY,
+    user_id         TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    expires         TIMESTAMP NOT NULL,
+    session_token   TEXT NOT NULL UNIQUE,
+    access_token    TEXT NOT NULL UNIQUE,
+    created_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE verification_requests (
+    id          TEXT PRIMARY KEY,
+    identifier  TEXT NOT NULL,
+    token       TEXT NOT NULL UNIQUE,
+    expires     TIMESTAMP NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE profiles (
+    id              TEXT PRIMARY KEY,
+    user_id         TEXT NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    bio             TEXT,
+    role            TEXT NOT NULL DEFAULT 'user' CHECK (role IN ('admin', 'superuser', 'user')),
+    cover_image     TEXT,
+    referral        TEXT,
+    phone_number    TEXT,
+    company         TEXT,
+    postal_code     TEXT,
+    state           TEXT,
+    country         TEXT,
+    city            TEXT,
+    website         TEXT,
+    occupation      TEXT,
+    facebook_url    TEXT,
+    linkedin_url    TEXT,
+    twitter_url     TEXT,
+    education       TEXT,
+    gender          TEXT CHECK (gender IN ('male', 'female', 'other', 'prefernottosay')),
+    birthday        TIMESTAMP,
+    created_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE user_levels (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL UNIQUE REFERENCES users (id) ON DELETE CASCADE,
+    level       INTEGER NOT NULL DEFAULT 1 CHECK (level >= 0),
+    exp         REAL NOT NULL DEFAULT 0,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE user_badges (
+    id              TEXT PRIMARY KEY,
+    user_level_id   TEXT NOT NULL REFERENCES user_levels (id) ON DELETE CASCADE,
+    name            TEXT NOT NULL,
+    description     TEXT NOT NULL,
+    condition       TEXT NOT NULL,
+    achieved_at     TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE links (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL,
+    value       TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE records (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    description TEXT,
+    image       TEXT,
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE items (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    image       TEXT,
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE fields (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    kind        TEXT NOT NULL DEFAULT 'text' CHECK (kind IN ('integer', 'realnum', 'double', 'range', 'date', 'datetime', 'enumeration', 'selection', 'text', 'boolean')),
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE field_values (
+    id          TEXT PRIMARY KEY,
+    field_id    TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    value       BLOB NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+-- FieldTarget still uses integer keys
+CREATE TABLE field_targets (
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    field_id    INTEGER NOT NULL,
+    name        TEXT NOT NULL,
+    description TEXT,
+    value       BLOB NOT NULL DEFAULT X'',
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE record_items (
+    id          TEXT PRIMARY KEY,
+    link_id     TEXT REFERENCES links (id) ON DELETE SET NULL,
+    record_id   TEXT NOT NULL REFERENCES records (id) ON DELETE CASCADE,
+    item_id     TEXT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    name        TEXT,
+    description TEXT,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE item_fields (
+    id          TEXT PRIMARY KEY,
+    link_id     TEXT REFERENCES links (id) ON DELETE SET NULL,
+    item_id     TEXT NOT NULL REFERENCES items (id) ON DELETE CASCADE,
+    field_id    TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    name        TEXT,
+    description TEXT,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE actions (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE tasks (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE channels (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE groups (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    description TEXT,
+    image       TEXT,
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    cover_image TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE group_users (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    group_id    TEXT NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    link_id     TEXT REFERENCES links (id) ON DELETE SET NULL,
+    name        TEXT,
+    description TEXT,
+    role        TEXT DEFAULT 'member' CHECK (role IN ('admin', 'moderator', 'member')),
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (user_id, group_id)
+);
+
+CREATE TABLE topics (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE categories (
+    id          TEXT PRIMARY KEY,
+    name        TEXT NOT NULL UNIQUE,
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE topic_categories (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    category_id TEXT NOT NULL REFERENCES categories (id) ON DELETE CASCADE,
+    topic_id    TEXT NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    score       REAL NOT NULL DEFAULT 0,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE topic_votes (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    topic_id    TEXT NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    is_for      BOOLEAN NOT NULL,
+    feeling     TEXT CHECK (feeling IN ('happy', 'sad', 'angry', 'tired')),
+    description TEXT,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE posts (
+    id              TEXT PRIMARY KEY,
+    user_id         TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    responds_to_id  TEXT REFERENCES posts (id) ON DELETE SET NULL,
+    image           TEXT,
+    content         TEXT NOT NULL,
+    feeling         TEXT CHECK (feeling IN ('happy', 'sad', 'angry', 'tired')),
+    private         BOOLEAN NOT NULL DEFAULT 1,
+    status          TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    created_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE post_feeling_responses (
+    id          TEXT PRIMARY KEY,
+    post_id     TEXT NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    feeling     TEXT NOT NULL CHECK (feeling IN ('happy', 'sad', 'angry', 'tired')),
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE group_posts (
+    id          TEXT PRIMARY KEY,
+    group_id    TEXT NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    post_id     TEXT NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE topic_posts (
+    id          TEXT PRIMARY KEY,
+    post_id     TEXT NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    topic_id    TEXT NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    link_id     TEXT REFERENCES links (id) ON DELETE SET NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE post_books (
+    id          TEXT PRIMARY KEY,
+    user_id     TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    name        TEXT NOT NULL,
+    status      TEXT NOT NULL DEFAULT 'active' CHECK (status IN ('active', 'archived', 'deleted', 'deferred')),
+    description TEXT,
+    image       TEXT,
+    private     BOOLEAN NOT NULL DEFAULT 1,
+    wiki        BOOLEAN NOT NULL DEFAULT 0,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE post_book_entries (
+    id          TEXT PRIMARY KEY,
+    post_id     TEXT NOT NULL REFERENCES posts (id) ON DELETE CASCADE,
+    book_id     TEXT NOT NULL REFERENCES post_books (id) ON DELETE CASCADE,
+    link_id     TEXT REFERENCES links (id) ON DELETE SET NULL
+);
+
+CREATE TABLE direct_user_messages (
+    id              TEXT PRIMARY KEY,
+    sender_id       TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    recipient_id    TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    replies_to_id   TEXT REFERENCES direct_user_messages (id) ON DELETE SET NULL,
+    content         TEXT NOT NULL,
+    attachments     TEXT NOT NULL DEFAULT '[]',
+    sent_at         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    read_at         TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE direct_group_messages (
+    id                  TEXT PRIMARY KEY,
+    sender_id           TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    group_id            TEXT NOT NULL REFERENCES groups (id) ON DELETE CASCADE,
+    replies_to_id       TEXT REFERENCES direct_group_messages (id) ON DELETE SET NULL,
+    content             TEXT NOT NULL,
+    attachments         TEXT NOT NULL DEFAULT '[]',
+    restrict_to_role    TEXT CHECK (restrict_to_role IN ('admin', 'moderator', 'member')),
+    sent_at             TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at          TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE TABLE direct_topic_messages (
+    id              TEXT PRIMARY KEY,
+    sender_id       TEXT NOT NULL REFERENCES users (id) ON DELETE CASCADE,
+    topic_id        TEXT NOT NULL REFERENCES topics (id) ON DELETE CASCADE,
+    replies_to_id   TEXT REFERENCES direct_topic_messages (id) ON DELETE SET NULL,
+    content         TEXT NOT NULL,
+    attachments     TEXT NOT NULL DEFAULT '[]',
+    sent_at         TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+CREATE INDEX accounts_user_id_idx ON accounts (user_id);
+CREATE INDEX sessions_user_id_idx ON sessions (user_id);
+CREATE INDEX records_user_id_idx ON records (user_id);
+CREATE INDEX items_user_id_idx ON items (user_id);
+CREATE INDEX fields_user_id_idx ON fields (user_id);
+CREATE INDEX field_values_field_id_idx ON field_values (field_id, created_at);
+CREATE INDEX record_items_record_id_idx ON record_items (record_id);
+CREATE INDEX record_items_item_id_idx ON record_items (item_id);
+CREATE INDEX item_fields_item_id_idx ON item_fields (item_id);
+CREATE INDEX item_fields_field_id_idx ON item_fields (field_id);
+CREATE INDEX posts_user_id_idx ON posts (user_id);
+CREATE INDEX group_users_group_id_idx ON group_users (group_id);
+CREATE INDEX topic_posts_topic_id_idx ON topic_posts (topic_id);
+CREATE INDEX direct_user_messages_recipient_id_idx ON direct_user_messages (recipient_id, sent_at);
+CREATE INDEX direct_group_messages_group_id_idx ON direct_group_messages (group_id, sent_at);
+CREATE INDEX direct_topic_messages_topic_id_idx ON direct_topic_messages (topic_id, sent_at);
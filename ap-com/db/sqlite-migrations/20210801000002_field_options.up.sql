@@ -0,0 +1,19 @@
+-- Options of enumeration and selection fields -- mirrors db/migrations/20210801000002.
+-- Colors are checked by shape only, as SQLite has no regular expressions.
+
+CREATE TABLE field_options (
+    id          TEXT PRIMARY KEY,
+    field_id    TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    label       TEXT NOT NULL CHECK (trim(label) <> ''),
+    color       TEXT CHECK (color GLOB '#[0-9a-fA-F][0-9a-fA-F][0-9a-fA-F][0-9a-fA-F][0-9a-fA-F][0-9a-fA-F]'),
+    position    INTEGER NOT NULL DEFAULT 0,
+    deprecated  BOOLEAN NOT NULL DEFAULT 0,
+    merged_into TEXT REFERENCES field_options (id) ON DELETE SET NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    CHECK (merged_into IS NULL OR deprecated)
+);
+
+CREATE INDEX field_options_field_id_idx ON field_options (field_id, position);
+CREATE UNIQUE INDEX field_options_label_idx ON field_options (field_id, lower(label))
+    WHERE merged_into IS NULL;
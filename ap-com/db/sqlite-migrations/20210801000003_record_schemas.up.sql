@@ -0,0 +1,20 @@
+-- Record schemas, and field values which belong to an item -- mirrors
+-- db/migrations/20210801000003.
+
+ALTER TABLE field_values ADD COLUMN item_id TEXT REFERENCES items (id) ON DELETE CASCADE;
+
+CREATE INDEX field_values_item_id_idx ON field_values (item_id, field_id, created_at);
+
+CREATE TABLE record_schema_fields (
+    id              TEXT PRIMARY KEY,
+    record_id       TEXT NOT NULL REFERENCES records (id) ON DELETE CASCADE,
+    field_id        TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    required        BOOLEAN NOT NULL DEFAULT 0,
+    default_value   BLOB,
+    position        INTEGER NOT NULL DEFAULT 0,
+    created_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at      TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    UNIQUE (record_id, field_id)
+);
+
+CREATE INDEX record_schema_fields_field_id_idx ON record_schema_fields (field_id);
@@ -0,0 +1,19 @@
+DROP TRIGGER IF EXISTS field_values_append_only;
+DROP INDEX IF EXISTS field_values_series_idx;
+DROP INDEX IF EXISTS field_values_field_id_idx;
+
+CREATE TABLE field_values_old (
+    id          TEXT PRIMARY KEY,
+    field_id    TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    item_id     TEXT REFERENCES items (id) ON DELETE CASCADE,
+    value       BLOB NOT NULL,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+INSERT INTO field_values_old (id, field_id, item_id, value, created_at, updated_at)
+    SELECT id, field_id, item_id, value, created_at, updated_at FROM field_values;
+DROP TABLE field_values;
+ALTER TABLE field_values_old RENAME TO field_values;
+
+CREATE INDEX field_values_field_id_idx ON field_values (field_id, created_at);
+CREATE INDEX field_values_item_id_idx ON field_values (item_id, field_id, created_at);
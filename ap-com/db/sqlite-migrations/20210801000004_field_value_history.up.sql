@@ -0,0 +1,73 @@
+-- Field values are kept as their history -- mirrors db/migrations/20210801000004. SQLite
+-- cannot add a NOT NULL column with a non-constant default, so field_values is rebuilt.
+-- SQLite has no functions to decode bytes, so num is backfilled by reading the 8 stored
+-- bytes as a big-endian integer (each byte looked up in a blob of every byte), which for
+-- realnum and double fields are then split into the sign, exponent and mantissa of a
+-- double and scaled by a table of powers of two.
+
+DROP INDEX IF EXISTS field_values_field_id_idx;
+DROP INDEX IF EXISTS field_values_item_id_idx;
+
+CREATE TABLE field_values_new (
+    id          TEXT PRIMARY KEY,
+    field_id    TEXT NOT NULL REFERENCES fields (id) ON DELETE CASCADE,
+    item_id     TEXT REFERENCES items (id) ON DELETE CASCADE,
+    value       BLOB NOT NULL,
+    num         DOUBLE,
+    recorded_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    created_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    updated_at  TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
+
+WITH RECURSIVE
+    up (e, v) AS (SELECT 0, 1.0 UNION ALL SELECT e + 1, v * 2 FROM up WHERE e < 972),
+    down (e, v) AS (SELECT -1, 0.5 UNION ALL SELECT e - 1, v / 2 FROM down WHERE e > -1074),
+    pow2 (e, v) AS (SELECT e, v FROM up UNION ALL SELECT e, v FROM down),
+    nums (id, kind, bits) AS (
+        SELECT v.id, f.kind,
+            (instr(b.bytes, substr(v.value, 1, 1)) - CASE WHEN substr(v.value, 1, 1) >= X'80' THEN 257 ELSE 1 END) * 72057594037927936 +
+            (instr(b.bytes, substr(v.value, 2, 1)) - 1) * 281474976710656 +
+            (instr(b.bytes, substr(v.value, 3, 1)) - 1) * 1099511627776 +
+            (instr(b.bytes, substr(v.value, 4, 1)) - 1) * 4294967296 +
+            (instr(b.bytes, substr(v.value, 5, 1)) - 1) * 16777216 +
+            (instr(b.bytes, substr(v.value, 6, 1)) - 1) * 65536 +
+            (instr(b.bytes, substr(v.value, 7, 1)) - 1) * 256 +
+            (instr(b.bytes, substr(v.value, 8, 1)) - 1) * 1
+        FROM field_values v
+        JOIN fields f ON f.id = v.field_id
+        CROSS JOIN (SELECT X'000102030405060708090A0B0C0D0E0F101112131415161718191A1B1C1D1E1F202122232425262728292A2B2C2D2E2F303132333435363738393A3B3C3D3E3F404142434445464748494A4B4C4D4E4F505152535455565758595A5B5C5D5E5F606162636465666768696A6B6C6D6E6F707172737475767778797A7B7C7D7E7F808182838485868788898A8B8C8D8E8F909192939495969798999A9B9C9D9E9FA0A1A2A3A4A5A6A7A8A9AAABACADAEAFB0B1B2B3B4B5B6B7B8B9BABBBCBDBEBFC0C1C2C3C4C5C6C7C8C9CACBCCCDCECFD0D1D2D3D4D5D6D7D8D9DADBDCDDDEDFE0E1E2E3E4E5E6E7E8E9EAEBECEDEEEFF0F1F2F3F4F5F6F7F8F9FAFBFCFDFEFF' AS bytes) b
+        WHERE f.kind IN ('integer', 'realnum', 'double') AND length(v.value) = 8
+    ),
+    doubles (id, sign, exponent, mantissa) AS (
+        SELECT id,
+            CASE WHEN bits < 0 THEN -1 ELSE 1 END,
+            (bits >> 52) & 2047,
+            bits & 4503599627370495
+        FROM nums WHERE kind <> 'integer'
+    )
+INSERT INTO field_values_new (id, field_id, item_id, value, num, recorded_at, created_at, updated_at)
+    SELECT v.id, v.field_id, v.item_id, v.value,
+        CASE
+            WHEN n.kind = 'integer' THEN n.bits
+            WHEN d.exponent = 0 THEN d.sign * d.mantissa * p.v
+            WHEN d.exponent < 2047 THEN d.sign * (d.mantissa + 4503599627370496) * p.v
+        END,
+        v.created_at, v.created_at, v.updated_at
+    FROM field_values v
+    LEFT JOIN nums n ON n.id = v.id
+    LEFT JOIN doubles d ON d.id = v.id
+    LEFT JOIN pow2 p ON p.e = CASE WHEN d.exponent = 0 THEN -1074 ELSE d.exponent - 1075 END;
+DROP TABLE field_values;
+ALTER TABLE field_values_new RENAME TO field_values;
+
+CREATE INDEX field_values_field_id_idx ON field_values (field_id, created_at);
+CREATE INDEX field_values_series_idx ON field_values (field_id, item_id, recorded_at);
+
+CREATE TRIGGER field_values_append_only
+    BEFORE UPDATE ON field_values
+    FOR EACH ROW
+    WHEN (NEW.field_id, NEW.item_id, NEW.value, NEW.num, NEW.recorded_at)
+        IS NOT (OLD.field_id, OLD.item_id, OLD.value, OLD.num, OLD.recorded_at)
+BEGIN
+    SELECT RAISE(ABORT, 'field_values are append-only, record a new value instead');
+END;
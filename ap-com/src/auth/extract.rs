@@ -0,0 +1,55 @@
+//! Extractors for the user making a request, decoded from the bearer access token
+use std::convert::TryFrom;
+use actix_web::{
+    dev::Payload, error::{ErrorForbidden, ErrorUnauthorized},
+    http::header, FromRequest, HttpRequest,
+};
+use futures::future::{ready, Ready};
+use super::jwt::{decode_token, EncodedUser, Role};
+
+impl EncodedUser {
+
+    #[inline]
+    pub fn is_admin(&self) -> bool {
+        Role::from_str(&self.role) == Role::Admin
+    }
+
+    /// Decode the user from the request's `Authorization: Bearer {jwt}` header, if any
+    pub fn from_bearer(req: &HttpRequest) -> Option<Self> {
+        let auth = req.headers().get(header::AUTHORIZATION)?.to_str().ok()?;
+        let token = auth.strip_prefix("Bearer ")
+            .or_else(|| auth.strip_prefix("bearer "))?
+            .trim();
+        let claims = decode_token(token).ok()?;
+        EncodedUser::try_from(claims).ok()
+    }
+}
+
+impl FromRequest for EncodedUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(Self::from_bearer(req)
+            .ok_or_else(|| ErrorUnauthorized("Missing or invalid access token")))
+    }
+}
+
+/// A request made by a user with the site-wide Admin role. Rejects everyone else
+#[derive(Debug, Clone, PartialEq)]
+pub struct AdminUser(pub EncodedUser);
+
+impl FromRequest for AdminUser {
+    type Error = actix_web::Error;
+    type Future = Ready<Result<Self, Self::Error>>;
+    type Config = ();
+
+    fn from_request(req: &HttpRequest, _payload: &mut Payload) -> Self::Future {
+        ready(match EncodedUser::from_bearer(req) {
+            Some(user) if user.is_admin() => Ok(AdminUser(user)),
+            Some(_) => Err(ErrorForbidden("Admin role required")),
+            None => Err(ErrorUnauthorized("Missing or invalid access token")),
+        })
+    }
+}
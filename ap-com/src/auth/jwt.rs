@@ -102,7 +102,8 @@ impl JWT {
 }
 
 
-pub fn get_encoding_key() -> anyhow::Result<EncodingKey> {
+/// JWT_SECRET, from the environment in PROD and from .env otherwise
+pub fn jwt_secret() -> anyhow::Result<String> {
     let key = if let Ok(env) = std::env::var("ENV") {
         match env.as_str() {
             "PROD" => std::env::var("JWT_SECRET")?,
@@ -110,7 +111,11 @@ pub fn get_encoding_key() -> anyhow::Result<EncodingKey> {
             _ => panic!("ENV var not set correctly")
         }
     } else { dotenv::var("JWT_SECRET")? };
-    return Ok(EncodingKey::from_secret(key.as_bytes()));
+    Ok(key)
+}
+
+pub fn get_encoding_key() -> anyhow::Result<EncodingKey> {
+    return Ok(EncodingKey::from_secret(jwt_secret()?.as_bytes()));
 }
 
 pub fn get_decoding_key() -> anyhow::Result<DecodingKey<'static>> {
@@ -0,0 +1,138 @@
+//! Change feed over Postgres LISTEN/NOTIFY. The notify_change() trigger
+//!     (db/migrations/*_listen.up.sql) sends every committed write to the model tables on
+//!     CHANNEL, whichever API instance or SQL session made it. changes() turns those
+//!     notifications into a stream of typed Notices, reconnecting when the connection
+//!     drops, ex. from an actor
+//! ```ignore
+//! ctx.add_stream(listen::changes(db.pool.clone()));
+//! ```
+use std::time::Duration;
+use futures::stream::{self, Stream};
+use serde::{Serialize, Deserialize};
+use sqlx::postgres::{PgExecutor, PgListener, PgPool};
+use crate::{Id, models::{Model, audit::AuditOp}};
+
+/// Channel the notify_change() trigger notifies on
+pub const CHANNEL: &str = "model_changes";
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// A committed write to a row, as sent by notify_change()
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Change {
+    pub table: String,
+    pub op: AuditOp,
+    pub id: Id,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Notice {
+    Change(Change),
+    /// The feed lost its connection and has re-established it. Changes made in the
+    ///     meantime were not seen, so subscribers should resync, ex. with Model::updated_since
+    Reconnected,
+}
+
+impl Change {
+
+    /// Whether this change is to a row of M
+    pub fn is<M: Model>(&self) -> bool {
+        self.table == M::table()
+    }
+
+    /// The changed row of M as it is now. None if this change is to another table, or
+    ///     the row has since been deleted
+    pub async fn fetch<'e, M: Model, E: PgExecutor<'e>>(&self, db: E) -> sqlx::Result<Option<M>> {
+        if !self.is::<M>() {
+            return Ok(None);
+        }
+        M::get(db, self.id.clone()).await
+    }
+}
+
+/// Stream every change made to the model tables from now on. The stream never ends --
+///     when the connection is lost, it retries with backoff and yields Notice::Reconnected
+///     once it is listening again
+pub fn changes(pool: PgPool) -> impl Stream<Item = Notice> + Send + 'static {
+    stream::unfold(Feed::new(pool), |mut feed| async move {
+        let notice = feed.next().await;
+        Some((notice, feed))
+    })
+}
+
+struct Feed {
+    pool: PgPool,
+    listener: Option<PgListener>,
+    backoff: Duration,
+    connected: bool,
+}
+
+impl Feed {
+
+    fn new(pool: PgPool) -> Self {
+        Self { pool, listener: None, backoff: MIN_BACKOFF, connected: false }
+    }
+
+    async fn connect(&self) -> sqlx::Result<PgListener> {
+        let mut listener = PgListener::connect_with(&self.pool).await?;
+        listener.listen(CHANNEL).await?;
+        Ok(listener)
+    }
+
+    async fn next(&mut self) -> Notice {
+        loop {
+            let listener = match self.listener.as_mut() {
+                Some(listener) => listener,
+                None => {
+                    match self.connect().await {
+                        Ok(listener) => {
+                            self.listener = Some(listener);
+                            self.backoff = MIN_BACKOFF;
+                            if std::mem::replace(&mut self.connected, true) {
+                                return Notice::Reconnected;
+                            }
+                        },
+                        Err(e) => {
+                            tracing::warn!("Change feed could not connect, retrying in {:?}: {}", self.backoff, e);
+                            actix::clock::sleep(self.backoff).await;
+                            self.backoff = (self.backoff * 2).min(MAX_BACKOFF);
+                        },
+                    }
+                    continue;
+                }
+            };
+            // try_recv() returns None when the connection is lost. The listener is dropped
+            //     rather than left to reconnect itself, so the reconnect can be reported
+            match listener.try_recv().await {
+                Ok(Some(notification)) => match serde_json::from_str::<Change>(notification.payload()) {
+                    Ok(change) => return Notice::Change(change),
+                    Err(e) => tracing::warn!("Ignoring malformed change {:?}: {}", notification.payload(), e),
+                },
+                Ok(None) => {
+                    tracing::warn!("Change feed lost its connection");
+                    self.listener = None;
+                },
+                Err(e) => {
+                    tracing::warn!("Change feed error: {}", e);
+                    self.listener = None;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Record;
+
+    #[test]
+    fn parses_trigger_payload() {
+        let id = Id::gen();
+        let payload = format!(r#"{{"table" : "records", "op" : "update", "id" : "{}"}}"#, id);
+        let change: Change = serde_json::from_str(&payload).unwrap();
+        assert_eq!(change, Change { table: "records".into(), op: AuditOp::Update, id });
+        assert!(change.is::<Record>());
+    }
+}
@@ -1 +1,84 @@
-pub struct Mi;
+//! Versioned schema migrations, embedded from db/migrations at compile time. Every
+//!     migration is reversible -- {version}_{name}.up.sql applies it and .down.sql undoes
+//!     it. Applied versions are tracked by sqlx in the _sqlx_migrations table
+use sqlx::{
+    postgres::PgPool,
+    migrate::{Migrate, MigrateError, Migrator},
+};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./db/migrations");
+
+/// A migration known to this build, and whether the database has applied it
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+}
+
+/// Apply every pending migration, in order
+pub async fn up(db: &PgPool) -> Result<(), MigrateError> {
+    MIGRATOR.run(db).await
+}
+
+/// Undo the latest applied migration, returning its version. None if no migration
+///     has been applied
+pub async fn down(db: &PgPool) -> Result<Option<i64>, MigrateError> {
+    let applied = applied(db).await?;
+    let latest = match applied.iter().max() {
+        Some(latest) => *latest,
+        None => return Ok(None),
+    };
+    let target = applied.iter()
+        .filter(|version| **version < latest)
+        .max()
+        .copied()
+        .unwrap_or(0);
+    MIGRATOR.undo(db, target).await?;
+    Ok(Some(latest))
+}
+
+/// Every migration in this build, oldest first
+pub async fn status(db: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let applied = applied(db).await?;
+    let res = MIGRATOR.iter()
+        .filter(|m| !m.migration_type.is_down_migration())
+        .map(|m| MigrationStatus {
+            version: m.version,
+            description: m.description.to_string(),
+            applied: applied.contains(&m.version),
+        })
+        .collect();
+    Ok(res)
+}
+
+/// Migrations in this build which the database has not applied yet
+pub async fn pending(db: &PgPool) -> Result<Vec<MigrationStatus>, MigrateError> {
+    let res = status(db).await?.into_iter()
+        .filter(|m| !m.applied)
+        .collect();
+    Ok(res)
+}
+
+async fn applied(db: &PgPool) -> Result<Vec<i64>, MigrateError> {
+    let mut conn = db.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let res = conn.list_applied_migrations().await?
+        .into_iter()
+        .map(|m| m.version)
+        .collect();
+    Ok(res)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrations_are_reversible() {
+        let ups = MIGRATOR.iter().filter(|m| m.migration_type.is_up_migration()).count();
+        let downs = MIGRATOR.iter().filter(|m| m.migration_type.is_down_migration()).count();
+        assert!(ups > 0);
+        assert_eq!(ups, downs);
+    }
+}
@@ -1,13 +1,19 @@
 #[cfg(feature = "pg")]
 pub mod pg;
 
+#[cfg(feature = "pg")]
+pub mod listen;
+
+#[cfg(feature = "pg")]
+pub mod migrate;
+
 #[cfg(feature = "sqlite")]
 pub mod sqlite;
 
 #[cfg(feature = "pg")]
 pub use pg::Db;
 
-#[cfg(feature = "sqlite")]
+// With both backends enabled, Db stays the Postgres one -- the SQLite one is
+//     sqlite::Db
+#[cfg(all(feature = "sqlite", not(feature = "pg")))]
 pub use sqlite::Db;
-
-
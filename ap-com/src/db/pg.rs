@@ -1,8 +1,8 @@
 use tracing::info;
-use crate::models::Model;
-use sqlx::postgres::{PgPool, PgPoolOptions, PgRow};
-use sqlx::prelude::*;
-use sqlx::Executor;
+use crate::{Id, models::{Model, audit, event}, query::Sql};
+use futures::future::BoxFuture;
+use sqlx::postgres::{PgPool, PgPoolOptions, Postgres};
+use sqlx::Transaction;
 
 #[derive(Clone, Debug)]
 pub struct Db {
@@ -21,79 +21,98 @@ impl Db {
         Ok( Self { pool } )
     }
 
-    pub async fn clear(self, table: &str) -> sqlx::Result<()> {
-        sqlx::query("DELETE FROM $1 RETURNING id")
-            .bind(&table)
-            .execute(&self.pool).await?;
-        Ok(())
+    /// Begin a transaction on the pool. Dropping it without commit() rolls it back
+    pub async fn begin(&self) -> sqlx::Result<Transaction<'static, Postgres>> {
+        self.pool.begin().await
     }
 
-    pub async fn get<T>(self, id: i32) -> sqlx::Result<Option<T>>
+    /// Run f inside a transaction, committing if it returns Ok and rolling back if it
+    ///     returns Err. Model methods accept the transaction as their executor, ex.
+    ///
+    /// ```ignore
+    /// db.transaction(|tx| Box::pin(async move {
+    ///     let group = group.insert(&mut *tx).await?;
+    ///     GroupUser::new(group.id.clone(), user_id).insert(&mut *tx).await?;
+    ///     Ok(group)
+    /// })).await
+    /// ```
+    pub async fn transaction<F, T, E>(&self, f: F) -> Result<T, E>
     where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send
+        F: for<'c> FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, E>> + Send,
+        T: Send,
+        E: From<sqlx::Error> + Send,
     {
-        let res = sqlx::query_as::<sqlx::Postgres, T>("SELECT * FROM $1 WHERE id = $2")
-            .bind(T::table())
-            .bind(id)
-            .fetch_optional(&self.pool).await?;
-        Ok(res)
+        self.transaction_as(None, f).await
     }
 
-    pub async fn get_all<T>(self) -> sqlx::Result<Vec<T>>
+    /// Like transaction(), attributing the writes made by f to actor in the audit log
+    ///     (see models::audit). Events f defers (see models::event::defer) are published
+    ///     once the transaction has committed
+    pub async fn transaction_as<F, T, E>(&self, actor: Option<&Id>, f: F) -> Result<T, E>
     where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send
+        F: for<'c> FnOnce(&'c mut Transaction<'static, Postgres>) -> BoxFuture<'c, Result<T, E>> + Send,
+        T: Send,
+        E: From<sqlx::Error> + Send,
     {
-
-        let res = sqlx::query_as::<sqlx::Postgres, T>("SELECT * FROM $1")
-            .bind(T::table())
-            .fetch_all(&self.pool).await?;
-        Ok(res)
+        let mut tx = self.pool.begin().await?;
+        if actor.is_some() {
+            audit::set_actor(&mut tx, actor).await?;
+        }
+        let (res, pending) = event::collect(f(&mut tx)).await;
+        match res {
+            Ok(out) => {
+                tx.commit().await?;
+                pending.publish();
+                Ok(out)
+            },
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
     }
 
-    pub async fn get_all_by_user<T>(self, user_id: i32) -> sqlx::Result<Vec<T>>
-    where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send
-    {
+    /// Remove every row of T's table for good, soft_delete() or not
+    pub async fn clear<T: Model>(&self) -> sqlx::Result<u64> {
+        let res = Sql::<T>::purge().execute(&self.pool).await?;
+        Ok(res.rows_affected())
+    }
 
-        let res = sqlx::query_as::<sqlx::Postgres, T>("SELECT * FROM $1 WHERE user_id = $2")
-            .bind(T::table())
-            .bind(user_id)
-            .fetch_all(&self.pool).await?;
-        Ok(res)
+    pub async fn get<T: Model>(&self, id: Id) -> sqlx::Result<Option<T>> {
+        T::get(&self.pool, id).await
     }
 
-    pub async fn get_all_with_field_val<T, S>(self, field: &str, val: S) -> sqlx::Result<Vec<T>>
-    where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send,
-        S: std::string::ToString,
-    {
+    pub async fn get_all<T: Model>(&self) -> sqlx::Result<Vec<T>> {
+        T::get_all(&self.pool).await
+    }
 
-        let res = sqlx::query_as::<sqlx::Postgres, T>("SELECT * FROM $1 WHERE $2 = $3")
-            .bind(T::table())
-            .bind(field)
-            .bind(val.to_string())
-            .fetch_all(&self.pool).await?;
-        Ok(res)
+    /// Live rows of T belonging to the user. Fails with ColumnNotFound if T has no user_id
+    pub async fn get_all_by_user<T: Model>(&self, user_id: Id) -> sqlx::Result<Vec<T>> {
+        Sql::<T>::select()
+            .eq("user_id", user_id)
+            .live()
+            .fetch_all(&self.pool).await
     }
 
-    pub async fn delete<T>(self, id: i32) -> sqlx::Result<Option<T>>
+    /// Live rows of T whose field, compared as text, equals val. The field must be one
+    ///     of T::columns()
+    pub async fn get_all_with_field_val<T, S>(&self, field: &str, val: S) -> sqlx::Result<Vec<T>>
     where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send
+        T: Model,
+        S: ToString,
     {
-        let res = sqlx::query_as::<sqlx::Postgres, T>("DELETE FROM $1 WHERE id = $2 RETURNING *")
-            .bind(T::table())
-            .bind(id)
-            .fetch_optional(&self.pool).await?;
-        Ok(res)
+        Sql::<T>::select()
+            .eq_text(field, val)
+            .live()
+            .fetch_all(&self.pool).await
     }
 
-    pub async fn delete_all<T>(self) -> sqlx::Result<Vec<T>>
-    where
-        T: for<'r> sqlx::FromRow<'r, PgRow> + Model + Unpin + Send
-    {
-        let res = sqlx::query_as::<sqlx::Postgres, T>("DELETE FROM $1 RETURNING *")
-            .bind(T::table())
-            .fetch_all(&self.pool).await?;
-        Ok(res)
+    /// Delete the row of T with this id -- soft deletes for soft_delete() models
+    pub async fn delete<T: Model>(&self, id: Id) -> sqlx::Result<Option<T>> {
+        T::delete(&self.pool, id).await
+    }
+
+    pub async fn delete_all<T: Model>(&self) -> sqlx::Result<Vec<T>> {
+        T::delete_all(&self.pool).await
     }
 }
@@ -0,0 +1,348 @@
+//! SQLite backend, for local development, embedded deployments and tests which should not
+//!     need a Postgres server. The schema (db/sqlite-migrations) mirrors the Postgres one,
+//!     less the audit log and notify triggers, and the functions here give any Model
+//!     generic reads and writes against a SQLite file, ex.
+//! ```ignore
+//! let db = sqlite::Db::new("sqlite://dev.db").await?;
+//! db.migrate().await?;
+//! let record = sqlite::insert(&db.pool, Record::new("Sleep".into(), user.id.clone())).await?;
+//! let records = sqlite::get_all::<Record, _>(&db.pool).await?;
+//! ```
+//! Rows are written by their serialized fields, each bound as the type its column is
+//!     declared with (see ColumnType). Identifiers are checked and quoted as in query::Sql
+use std::{collections::HashMap, convert::TryFrom, str::FromStr};
+use chrono::NaiveDateTime;
+use futures::future::BoxFuture;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use sqlx::{
+    Acquire, Transaction, FromRow,
+    migrate::{MigrateError, Migrator},
+    query::QueryAs,
+    sqlite::{
+        Sqlite, SqliteArguments, SqliteConnectOptions, SqliteConnection, SqliteExecutor,
+        SqliteJournalMode, SqlitePool, SqlitePoolOptions, SqliteRow,
+    },
+};
+use tracing::info;
+use crate::{Id, models::{Model, check_patch, not_deleted}, query::sql::Ident};
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./db/sqlite-migrations");
+
+/// How a column stores its values, read from its declared type. Enums are TEXT columns
+///     checked against their labels, ex. status TEXT CHECK (status IN ('active', ..))
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnType {
+    Integer,
+    Real,
+    Boolean,
+    Timestamp,
+    Blob,
+    Text,
+    Enum(Vec<String>),
+}
+
+#[derive(Clone, Debug)]
+pub struct Db {
+    pub pool: SqlitePool,
+}
+
+impl Db {
+
+    /// Open (or create) the database file at db_url, ex. sqlite://dev.db
+    pub async fn new(db_url: &str) -> anyhow::Result<Self> {
+        let opts = SqliteConnectOptions::from_str(db_url)?
+            .create_if_missing(true)
+            .foreign_keys(true)
+            .journal_mode(SqliteJournalMode::Wal);
+        let pool = SqlitePoolOptions::new()
+            .connect_with(opts).await?;
+        info!(db_url, "SQLite pool successfully initialized");
+        Ok(Self { pool })
+    }
+
+    /// A fresh, migrated in-memory database. Every connection to :memory: opens its own
+    ///     database, so the pool holds a single connection
+    pub async fn memory() -> anyhow::Result<Self> {
+        let opts = SqliteConnectOptions::from_str("sqlite::memory:")?
+            .foreign_keys(true);
+        let pool = SqlitePoolOptions::new()
+            .max_connections(1)
+            .connect_with(opts).await?;
+        let db = Self { pool };
+        db.migrate().await?;
+        Ok(db)
+    }
+
+    /// Apply every pending migration in db/sqlite-migrations
+    pub async fn migrate(&self) -> Result<(), MigrateError> {
+        MIGRATOR.run(&self.pool).await
+    }
+
+    pub async fn begin(&self) -> sqlx::Result<Transaction<'static, Sqlite>> {
+        self.pool.begin().await
+    }
+
+    /// Run f inside a transaction, committing if it returns Ok and rolling back if it
+    ///     returns Err -- see pg::Db::transaction()
+    pub async fn transaction<F, T, E>(&self, f: F) -> Result<T, E>
+    where
+        F: for<'c> FnOnce(&'c mut Transaction<'static, Sqlite>) -> BoxFuture<'c, Result<T, E>> + Send,
+        T: Send,
+        E: From<sqlx::Error> + Send,
+    {
+        let mut tx = self.pool.begin().await?;
+        match f(&mut tx).await {
+            Ok(out) => {
+                tx.commit().await?;
+                Ok(out)
+            },
+            Err(e) => {
+                tx.rollback().await?;
+                Err(e)
+            }
+        }
+    }
+}
+
+impl ColumnType {
+
+    fn parse(declared: &str, labels: Option<Vec<String>>) -> Self {
+        let declared = declared.to_uppercase();
+        match labels {
+            Some(labels) => Self::Enum(labels),
+            None if declared.contains("BOOL") => Self::Boolean,
+            None if declared.contains("TIMESTAMP") || declared.contains("DATE") => Self::Timestamp,
+            None if declared.contains("INT") => Self::Integer,
+            None if declared.contains("REAL") || declared.contains("DOUB") || declared.contains("FLOA") => Self::Real,
+            None if declared.contains("BLOB") => Self::Blob,
+            None => Self::Text,
+        }
+    }
+}
+
+/// The type of each of the table's columns, by name
+pub async fn column_types(conn: &mut SqliteConnection, table: &str) -> sqlx::Result<HashMap<String, ColumnType>> {
+    let cols: Vec<(String, String, String)> = sqlx::query_as("
+        SELECT col.name, col.type, tbl.sql
+        FROM sqlite_master AS tbl, pragma_table_info(tbl.name) AS col
+        WHERE tbl.type = 'table' AND tbl.name = ?
+        ")
+        .bind(table)
+        .fetch_all(conn).await?;
+    if cols.is_empty() {
+        return Err(sqlx::Error::Protocol(format!("No table {}", table)));
+    }
+    Ok(cols.into_iter()
+        .map(|(name, declared, sql)| {
+            let labels = labels(&sql, &name);
+            (name, ColumnType::parse(&declared, labels))
+        })
+        .collect())
+}
+
+/// The labels listed by a CHECK (col IN ('a', 'b', ..)) constraint in a table's SQL
+fn labels(table_sql: &str, col: &str) -> Option<Vec<String>> {
+    let check = format!("CHECK ({} IN (", col);
+    let start = table_sql.find(&check)? + check.len();
+    let end = start + table_sql[start..].find(')')?;
+    Some(table_sql[start..end]
+        .split(',')
+        .map(|label| label.trim().trim_matches('\'').to_string())
+        .collect())
+}
+
+/// Insert a row, mapping its serialized fields onto the table's columns. Fields which
+///     are not columns of the table are skipped
+pub async fn insert<'a, M, A>(db: A, model: M) -> sqlx::Result<M>
+where
+    M: Model + Serialize + for<'r> FromRow<'r, SqliteRow>,
+    A: Acquire<'a, Database = Sqlite> + Send,
+{
+    let row = match serde_json::to_value(&model) {
+        Ok(Value::Object(row)) => row,
+        Ok(_) => return Err(sqlx::Error::Protocol("Model did not serialize to an object".into())),
+        Err(e) => return Err(sqlx::Error::Decode(Box::new(e))),
+    };
+    let mut conn = db.acquire().await?;
+    let types = column_types(&mut *conn, &M::table()).await?;
+    let row = row.into_iter()
+        .filter_map(|(col, val)| types.get(&col).map(|ty| (col, ty, val)))
+        .collect::<Vec<(String, &ColumnType, Value)>>();
+    let cols = row.iter()
+        .map(|(col, _, _)| Ident::column::<M>(col).map(|col| col.to_string()))
+        .collect::<sqlx::Result<Vec<String>>>()?;
+    let sql = format!("INSERT INTO {} ({}) VALUES ({}) RETURNING *",
+        Ident::table::<M>()?,
+        cols.join(", "),
+        vec!["?"; cols.len()].join(", "));
+    let mut query = sqlx::query_as::<Sqlite, M>(&sql);
+    for (col, ty, val) in row.into_iter() {
+        query = bind(query, &col, ty, val)?;
+    }
+    query.fetch_one(&mut *conn).await
+}
+
+/// The row with this id -- None if it does not exist or is soft deleted
+pub async fn get<'e, M, E>(db: E, id: Id) -> sqlx::Result<Option<M>>
+where
+    M: Model + for<'r> FromRow<'r, SqliteRow>,
+    E: SqliteExecutor<'e>,
+{
+    sqlx::query_as::<Sqlite, M>(&format!("SELECT * FROM {} WHERE \"id\" = ?{}", Ident::table::<M>()?, not_deleted::<M>()))
+        .bind(id)
+        .fetch_optional(db).await
+}
+
+/// Every live row of the table, oldest first
+pub async fn get_all<'e, M, E>(db: E) -> sqlx::Result<Vec<M>>
+where
+    M: Model + for<'r> FromRow<'r, SqliteRow>,
+    E: SqliteExecutor<'e>,
+{
+    sqlx::query_as::<Sqlite, M>(&format!("SELECT * FROM {} WHERE TRUE{} ORDER BY {}, \"id\"",
+            Ident::table::<M>()?, not_deleted::<M>(), Ident::column::<M>(M::sortable_columns()[0])?))
+        .fetch_all(db).await
+}
+
+/// Every live row belonging to the user with this id
+pub async fn get_all_by_user<'e, M, E>(db: E, user_id: Id) -> sqlx::Result<Vec<M>>
+where
+    M: Model + for<'r> FromRow<'r, SqliteRow>,
+    E: SqliteExecutor<'e>,
+{
+    sqlx::query_as::<Sqlite, M>(&format!("SELECT * FROM {} WHERE {} = ?{} ORDER BY {}, \"id\"",
+            Ident::table::<M>()?, Ident::column::<M>("user_id")?, not_deleted::<M>(),
+            Ident::column::<M>(M::sortable_columns()[0])?))
+        .bind(user_id)
+        .fetch_all(db).await
+}
+
+/// Apply a JSON merge-patch to the row with this id and bump its updated_at -- see
+///     Model::update()
+pub async fn update<'a, M, A>(db: A, id: Id, patch: Map<String, Value>) -> sqlx::Result<Option<M>>
+where
+    M: Model + for<'r> FromRow<'r, SqliteRow>,
+    A: Acquire<'a, Database = Sqlite> + Send,
+{
+    check_patch::<M>(&patch)?;
+    let mut conn = db.acquire().await?;
+    if patch.is_empty() {
+        return get::<M, _>(&mut *conn, id).await;
+    }
+    let types = column_types(&mut *conn, &M::table()).await?;
+    let set = patch.keys()
+        .map(|col| Ident::column::<M>(col).map(|col| format!("{} = ?", col)))
+        .collect::<sqlx::Result<Vec<String>>>()?
+        .join(", ");
+    let sql = format!("UPDATE {} SET {}, \"updated_at\" = ? WHERE \"id\" = ?{} RETURNING *",
+        Ident::table::<M>()?, set, not_deleted::<M>());
+    let mut query = sqlx::query_as::<Sqlite, M>(&sql);
+    for (col, val) in patch.into_iter() {
+        let ty = types.get(&col).ok_or_else(|| sqlx::Error::ColumnNotFound(col.clone()))?;
+        query = bind(query, &col, ty, val)?;
+    }
+    query
+        .bind(crate::now())
+        .bind(id)
+        .fetch_optional(&mut *conn).await
+}
+
+/// Delete the row with this id -- soft deletes for soft_delete() models
+pub async fn delete<'e, M, E>(db: E, id: Id) -> sqlx::Result<Option<M>>
+where
+    M: Model + for<'r> FromRow<'r, SqliteRow>,
+    E: SqliteExecutor<'e>,
+{
+    let table = Ident::table::<M>()?;
+    if M::soft_delete() {
+        sqlx::query_as::<Sqlite, M>(&format!("
+            UPDATE {} SET \"status\" = 'deleted', \"updated_at\" = ?
+            WHERE \"id\" = ? AND \"status\" <> 'deleted'
+            RETURNING *
+            ", table))
+            .bind(crate::now())
+            .bind(id)
+            .fetch_optional(db).await
+    } else {
+        sqlx::query_as::<Sqlite, M>(&format!("DELETE FROM {} WHERE \"id\" = ? RETURNING *", table))
+            .bind(id)
+            .fetch_optional(db).await
+    }
+}
+
+/// Bind a serialized field as the type of its column. Timestamps are bound as
+///     NaiveDateTime so they are stored in the same format as ones bound directly, and
+///     enums, which serialize by variant name, as their lowercase labels. TEXT columns
+///     take arrays and objects as JSON text. Anything else which does not fit the column
+///     fails with a Decode error
+fn bind<'q, M>(query: QueryAs<'q, Sqlite, M, SqliteArguments<'q>>, col: &str, ty: &ColumnType, val: Value) -> sqlx::Result<QueryAs<'q, Sqlite, M, SqliteArguments<'q>>> {
+    let query = match (ty, val) {
+        (_, Value::Null) => query.bind(None::<String>),
+        (ColumnType::Integer, Value::Number(n)) if n.is_i64() => query.bind(n.as_i64()),
+        (ColumnType::Real, Value::Number(n)) => query.bind(n.as_f64()),
+        (ColumnType::Boolean, Value::Bool(b)) => query.bind(b),
+        (ColumnType::Timestamp, Value::String(s)) => match NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S%.f") {
+            Ok(datetime) => query.bind(datetime),
+            Err(_) => return Err(mismatch(col, &Value::String(s))),
+        },
+        (ColumnType::Blob, Value::Array(arr)) => {
+            let bytes = arr.iter()
+                .map(|v| v.as_u64().and_then(|b| u8::try_from(b).ok()))
+                .collect::<Option<Vec<u8>>>();
+            match bytes {
+                Some(bytes) => query.bind(bytes),
+                None => return Err(mismatch(col, &Value::Array(arr))),
+            }
+        },
+        (ColumnType::Enum(labels), Value::String(s)) => {
+            let label = s.to_lowercase();
+            query.bind(if labels.contains(&label) { label } else { s })
+        },
+        (ColumnType::Text, Value::String(s)) => query.bind(s),
+        (ColumnType::Text, val) => query.bind(val.to_string()),
+        (_, val) => return Err(mismatch(col, &val)),
+    };
+    Ok(query)
+}
+
+fn mismatch(col: &str, val: &Value) -> sqlx::Error {
+    sqlx::Error::Decode(format!("Cannot store {} in column {}", val, col).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use crate::models::{User, Record};
+
+    #[actix_rt::test]
+    async fn round_trips_models() {
+        let db = Db::memory().await.unwrap();
+        let user = insert(&db.pool, User::default()).await.unwrap();
+        // Text which looks like a timestamp is still stored as text
+        let record = Record { description: Some("2021-08-01T00:00:00".into()), ..Record::new("Sleep".into(), user.id.clone()) };
+        let record = insert(&db.pool, record).await.unwrap();
+        assert_eq!(get::<Record, _>(&db.pool, record.id.clone()).await.unwrap(), Some(record.clone()));
+
+        let patch = json!({ "name": "Naps", "private": false });
+        let updated = update::<Record, _>(&db.pool, record.id.clone(), patch.as_object().unwrap().clone())
+            .await.unwrap().unwrap();
+        assert_eq!((updated.name.as_str(), updated.private), ("Naps", false));
+
+        delete::<Record, _>(&db.pool, record.id.clone()).await.unwrap();
+        assert!(get_all_by_user::<Record, _>(&db.pool, user.id).await.unwrap().is_empty());
+    }
+
+    #[actix_rt::test]
+    async fn reads_column_types() {
+        let db = Db::memory().await.unwrap();
+        let mut conn = db.pool.acquire().await.unwrap();
+        let types = column_types(&mut conn, "records").await.unwrap();
+        assert_eq!(types["name"], ColumnType::Text);
+        assert_eq!(types["private"], ColumnType::Boolean);
+        assert_eq!(types["created_at"], ColumnType::Timestamp);
+        let labels = ["active", "archived", "deleted", "deferred"].iter().map(|l| l.to_string()).collect();
+        assert_eq!(types["status"], ColumnType::Enum(labels));
+    }
+}
@@ -1,3 +1,5 @@
+extern crate self as ap_com;
+
 pub mod types;
 pub mod db;
 pub mod rel;
@@ -1,9 +1,10 @@
 use crate::{models::{
-    Model,
+    Model, ModelRoutes,
         record::Record,
         item::Item,
     }, types::{Gender, Id, Status, now, private}};
-use actix_web::{HttpRequest, HttpResponse, Responder};
+use crate::models::event;
+use actix_web::{HttpRequest, HttpResponse, Responder, web::ServiceConfig};
 use chrono::NaiveDate;
 use serde::{Serialize, Deserialize};
 use sqlx::{
@@ -31,8 +32,12 @@ pub struct Action {
 #[async_trait::async_trait]
 impl Model for Action {
     fn table() -> String { String::from("actions") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "description", "status", "created_at", "updated_at"] }
+    fn soft_delete() -> bool { true }
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "description", "status"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "name", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO actions
             (id, user_id, name, description, status, created_at, updated_at)
@@ -47,7 +52,16 @@ impl Model for Action {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
+    }
+}
+#[async_trait::async_trait]
+impl ModelRoutes for Action {
+    #[inline]
+    fn path() -> String { String::from("/action") }
+
+    fn model_routes(cfg: &mut ServiceConfig) {
+        cfg;
     }
 }
 
@@ -0,0 +1,130 @@
+//! Append-only history of writes to the model tables. Entries are written by the audit_row()
+//!     trigger (db/migrations/*_audit.up.sql), so every insert, update and delete is captured
+//!     whatever code path makes it. Secret columns -- passwords and tokens -- are left out of
+//!     the snapshots, and sessions and verification requests are not audited at all. The
+//!     acting user is read from the transaction-local ap.actor_id setting, which routes set
+//!     through Db::transaction_as() and other callers through begin_as() or set_actor().
+//!     The only way out of the log is forget(), which erasure uses to remove an erased
+//!     user's rows
+use actix_web::{HttpResponse, web::{self, Data, Path, ServiceConfig}};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sqlx::{
+    FromRow, Postgres, Transaction,
+    postgres::{PgPool, PgExecutor, PgConnection},
+    types::chrono::NaiveDateTime,
+};
+use crate::{Db, Id, auth::extract::AdminUser, util::respond};
+
+#[derive(sqlx::Type, Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[sqlx(type_name = "audit_op", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum AuditOp {
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A single write to a row, with the row as it was before and after. before is None
+///     for inserts and after is None for deletes
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    pub id: i64,
+    pub op: AuditOp,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub actor_id: Option<Id>,
+    pub table_name: String,
+    pub row_id: Id,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub created_at: NaiveDateTime,
+}
+
+impl AuditEntry {
+
+    /// Every write to the row with this id in table, oldest first
+    pub async fn history<'e, E: PgExecutor<'e>>(db: E, table: &str, row_id: Id) -> sqlx::Result<Vec<Self>> {
+        let res = sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM audit_log
+            WHERE table_name = $1 AND row_id = $2
+            ORDER BY id
+            ")
+            .bind(table)
+            .bind(row_id)
+            .fetch_all(db).await?;
+        Ok(res)
+    }
+
+    /// Every write made by the user with this id, oldest first
+    pub async fn by_actor<'e, E: PgExecutor<'e>>(db: E, actor_id: Id) -> sqlx::Result<Vec<Self>> {
+        let res = sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM audit_log WHERE actor_id = $1 ORDER BY id")
+            .bind(actor_id)
+            .fetch_all(db).await?;
+        Ok(res)
+    }
+}
+
+/// Attribute the rest of the current transaction's writes to actor. Has no effect
+///     outside of a transaction
+pub async fn set_actor<'e, E: PgExecutor<'e>>(db: E, actor: Option<&Id>) -> sqlx::Result<()> {
+    sqlx::query("SELECT set_config('ap.actor_id', COALESCE($1::text, ''), true)")
+        .bind(actor)
+        .execute(db).await?;
+    Ok(())
+}
+
+/// Begin a transaction whose writes are attributed to actor in the audit log
+pub async fn begin_as(pool: &PgPool, actor: Option<&Id>) -> sqlx::Result<Transaction<'static, Postgres>> {
+    let mut tx = pool.begin().await?;
+    set_actor(&mut tx, actor).await?;
+    Ok(tx)
+}
+
+/// Delete the history of each (table, row ids) and detach user_id from the writes it made,
+///     returning the number of entries removed. The append-only trigger is lifted for
+///     these statements only, and only inside the caller's transaction (see
+///     models::user::erase)
+pub async fn forget<'a, I>(conn: &mut PgConnection, user_id: &Id, rows: I) -> sqlx::Result<u64>
+where
+    I: IntoIterator<Item = (&'a str, &'a [Id])>,
+{
+    sqlx::query("SELECT set_config('ap.audit_forget', 'on', true)")
+        .execute(&mut *conn).await?;
+    let mut removed = 0;
+    for (table, ids) in rows {
+        let ids = ids.iter().map(|id| id.to_string()).collect::<Vec<String>>();
+        removed += sqlx::query("DELETE FROM audit_log WHERE table_name = $1 AND row_id = ANY($2)")
+            .bind(table)
+            .bind(ids)
+            .execute(&mut *conn).await?
+            .rows_affected();
+    }
+    sqlx::query("UPDATE audit_log SET actor_id = NULL WHERE actor_id = $1")
+        .bind(user_id.to_string())
+        .execute(&mut *conn).await?;
+    sqlx::query("SELECT set_config('ap.audit_forget', '', true)")
+        .execute(&mut *conn).await?;
+    Ok(removed)
+}
+
+/// Mounted at /audit -- admin only, since snapshots hold every other column of the row
+pub fn routes(cfg: &mut ServiceConfig) {
+    cfg
+        .service(web::resource("/{table}/{id}")
+            .route(web::get().to(service_get_history))
+        );
+}
+
+async fn service_get_history(
+    db: Data<Db>,
+    _admin: AdminUser,
+    path: Path<(String, Id)>
+) -> actix_web::Result<HttpResponse>
+{
+    let (table, id) = path.into_inner();
+    match AuditEntry::history(&db.pool, &table, id).await {
+        Ok(history) => Ok(respond::ok(history)),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
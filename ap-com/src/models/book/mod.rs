@@ -7,6 +7,7 @@ pub mod record;
 
 use uuid::Uuid;
 use crate::Model;
+use crate::models::event;
 use crate::{
     models::post::Post,
     types::{Id, Status, now,Feeling, private}
@@ -42,8 +43,9 @@ pub struct Book {
 
 #[async_trait::async_trait]
 impl Model for Book {
-    fn table() -> String { String::from("tables") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn table() -> String { String::from("post_books") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "status", "description", "image", "private", "wiki", "created_at", "updated_at"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO post_books (id, name, user_id, status,
                 image, description, private, wiki, created_at,
@@ -61,7 +63,7 @@ impl Model for Book {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -97,7 +99,7 @@ impl Book {
         user_id: Id) -> sqlx::Result<Self>
     {
         let post = Post::new(user_id, content, None, image, feeling)
-            .insert(&db)
+            .insert(db)
             .await?;
         let id = sqlx::query_as::<Postgres, Self>("
             INSERT INTO post_books (id, name, user_id, status,
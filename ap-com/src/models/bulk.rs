@@ -0,0 +1,181 @@
+//! Bulk insert / upsert of many rows at once, ex. for import jobs. Rows are written in a
+//!     single transaction with a savepoint per row, so each row gets its own result
+use serde::{Serialize, Deserialize, de::DeserializeOwned};
+use sqlx::{Acquire, Postgres};
+use crate::query::sql::Ident;
+use super::Model;
+
+/// Largest request body accepted by the bulk routes
+pub const BULK_PAYLOAD_LIMIT: usize = 64 * 1024 * 1024;
+
+/// Query string accepted by bulk routes, ex. ?upsert=true&atomic=true
+#[derive(Default, Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct BulkOptions {
+    /// Update rows whose id already exists instead of failing them
+    #[serde(default)]
+    pub upsert: bool,
+    /// Roll back every row if any row fails. Otherwise the rows which succeeded are committed
+    #[serde(default)]
+    pub atomic: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "result", rename_all = "lowercase")]
+pub enum RowResult<T> {
+    Ok {
+        row: usize,
+        data: T,
+        /// The row as it was before an upsert overwrote it. None if the row was inserted
+        #[serde(skip)]
+        previous: Option<T>,
+    },
+    Error { row: usize, error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct BulkReport<T> {
+    /// Whether the transaction was committed. False when an atomic import had failures
+    pub committed: bool,
+    pub succeeded: usize,
+    pub failed: usize,
+    pub results: Vec<RowResult<T>>,
+}
+
+impl<T> RowResult<T> {
+
+    pub fn row(&self) -> usize {
+        match self {
+            RowResult::Ok { row, .. } | RowResult::Error { row, .. } => *row,
+        }
+    }
+
+    pub fn is_ok(&self) -> bool {
+        matches!(self, RowResult::Ok { .. })
+    }
+}
+
+impl<T> BulkReport<T> {
+
+    pub fn new(committed: bool, results: Vec<RowResult<T>>) -> Self {
+        let succeeded = results.iter().filter(|r| r.is_ok()).count();
+        Self { committed, succeeded, failed: results.len() - succeeded, results }
+    }
+}
+
+/// Parse a request body holding either a JSON array of rows or NDJSON (one row per line).
+///     Rows which fail to deserialize are returned as errors in place, so they can be
+///     reported alongside database errors. Fails only if the body is not valid at all
+pub fn parse_rows<T: DeserializeOwned>(body: &[u8]) -> Result<Vec<Result<T, String>>, String> {
+    let body = std::str::from_utf8(body).map_err(|e| e.to_string())?;
+    if body.trim_start().starts_with('[') {
+        let values: Vec<serde_json::Value> = serde_json::from_str(body)
+            .map_err(|e| format!("Invalid JSON array: {}", e))?;
+        Ok(values.into_iter()
+            .map(|v| serde_json::from_value(v).map_err(|e| e.to_string()))
+            .collect())
+    } else {
+        Ok(body.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(|e| e.to_string()))
+            .collect())
+    }
+}
+
+/// Insert every successfully parsed row with Model::insert_many, reporting rows which
+///     failed to parse at their original position. An atomic import with unparseable
+///     rows is rejected without touching the database
+pub async fn import<'a, M, A>(db: A, parsed: Vec<Result<M, String>>, opts: BulkOptions) -> sqlx::Result<BulkReport<M>>
+where
+    M: Model + Serialize,
+    A: Acquire<'a, Database = Postgres> + Send,
+{
+    let (mut rows, mut indices, mut results) = (Vec::new(), Vec::new(), Vec::new());
+    for (row, res) in parsed.into_iter().enumerate() {
+        match res {
+            Ok(model) => {
+                rows.push(model);
+                indices.push(row);
+            },
+            Err(error) => results.push(RowResult::Error { row, error }),
+        }
+    }
+    if opts.atomic && !results.is_empty() {
+        return Ok(BulkReport::new(false, results));
+    }
+    let report = M::insert_many(db, rows, opts).await?;
+    results.extend(report.results.into_iter().map(|res| match res {
+        RowResult::Ok { row, data, previous } => RowResult::Ok { row: indices[row], data, previous },
+        RowResult::Error { row, error } => RowResult::Error { row: indices[row], error },
+    }));
+    results.sort_by_key(|r| r.row());
+    Ok(BulkReport::new(report.committed, results))
+}
+
+/// INSERT for a single row bound as JSON ($1), mapping the row's fields onto the table's
+///     columns. With upsert, rows whose id exists have their mutable_columns() replaced
+///     and updated_at set to now. Identifiers are checked and quoted as in query::Sql
+pub(crate) fn insert_sql<M: Model>(upsert: bool) -> sqlx::Result<String> {
+    let table = Ident::table::<M>()?;
+    let conflict = if upsert {
+        let mut set = Vec::new();
+        for col in M::mutable_columns() {
+            set.push(format!("{col} = EXCLUDED.{col}", col = Ident::column::<M>(col)?));
+        }
+        if M::sortable_columns().contains(&"updated_at") {
+            set.push("\"updated_at\" = (NOW() AT TIME ZONE 'utc')".to_string());
+        }
+        if set.is_empty() {
+            "ON CONFLICT (\"id\") DO NOTHING".to_string()
+        } else {
+            format!("ON CONFLICT (\"id\") DO UPDATE SET {}", set.join(", "))
+        }
+    } else {
+        String::new()
+    };
+    Ok(format!("
+        INSERT INTO {table}
+        SELECT * FROM jsonb_populate_record(NULL::{table}, {row})
+        {conflict}
+        RETURNING *
+        ", table = table, row = db_labels(&table, "$1"), conflict = conflict))
+}
+
+/// The JSON object bound at param, with the values of enum columns mapped to their labels.
+///     Enums serialize by variant name, ex. "PreferNotToSay", while the database labels
+///     are the lowercase sqlx names, so those strings are lowercased before
+///     jsonb_populate_record casts them
+pub(crate) fn db_labels(table: &Ident, param: &str) -> String {
+    format!("(
+        SELECT coalesce(jsonb_object_agg(kv.key, CASE
+            WHEN ty.typtype = 'e' AND jsonb_typeof(kv.value) = 'string' THEN to_jsonb(lower(kv.value #>> '{{}}'))
+            ELSE kv.value END), '{{}}')
+        FROM jsonb_each({param}) AS kv
+        LEFT JOIN pg_attribute col ON col.attrelid = '{table}'::regclass AND col.attname = kv.key AND NOT col.attisdropped
+        LEFT JOIN pg_type ty ON ty.oid = col.atttypid
+        )", table = table, param = param)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::Record;
+
+    #[test]
+    fn parses_arrays_and_ndjson() {
+        let array: Vec<Result<serde_json::Value, String>> = parse_rows(b"[{\"a\": 1}, {\"a\": 2}]").unwrap();
+        let ndjson: Vec<Result<serde_json::Value, String>> = parse_rows(b"{\"a\": 1}\n\n{\"a\": 2}\n").unwrap();
+        assert_eq!(array, ndjson);
+        let bad: Vec<Result<Record, String>> = parse_rows(b"{\"name\": \"ok\"}\nnot json").unwrap();
+        assert!(bad[0].is_ok() && bad[1].is_err());
+    }
+
+    #[test]
+    fn upsert_replaces_mutable_columns() {
+        let sql = insert_sql::<Record>(true).unwrap();
+        assert!(sql.contains("INSERT INTO \"records\""));
+        assert!(sql.contains("ON CONFLICT (\"id\") DO UPDATE SET \"name\" = EXCLUDED.\"name\""));
+        assert!(sql.contains("\"updated_at\" = (NOW() AT TIME ZONE 'utc')"));
+        assert!(sql.contains("jsonb_each($1)"));
+        assert!(!insert_sql::<Record>(false).unwrap().contains("ON CONFLICT"));
+    }
+}
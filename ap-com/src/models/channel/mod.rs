@@ -1,5 +1,6 @@
 use actix::prelude::*;
 use crate::{Id, Model};
+use crate::models::event;
 use crate::now;
 use serde::{Serialize, Deserialize};
 use sqlx::{
@@ -26,17 +27,20 @@ pub struct Channel {
 impl Model for Channel {
 
     fn table() -> String { String::from("channels") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "description", "updated_at", "created_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "description"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "name"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>(
             "INSERT INTO channels (id, user_id, name, description)
-             vALUES ($1, $2, $3, $4) RETURNING *")
+             VALUES ($1, $2, $3, $4) RETURNING *")
             .bind(&self.id)
             .bind(self.user_id)
             .bind(&self.name)
             .bind(&self.description)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
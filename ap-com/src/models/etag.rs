@@ -0,0 +1,88 @@
+//! ETags for optimistic concurrency -- a row's tag is its id and updated_at, encoded
+//!     the same way as a page Cursor, so any write to the row changes its tag
+use std::convert::TryFrom;
+use actix_web::{HttpRequest, http::header};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use crate::{Id, query::{Cursor, parse_date}};
+
+/// Outcome of a conditional write
+#[derive(Debug, Clone, PartialEq)]
+pub enum Precondition<T> {
+    /// The row was unchanged and the write went through
+    Met(T),
+    /// The row has changed since it was read. Holds the row as it is now
+    Failed(T),
+    NotFound,
+}
+
+/// A parsed If-Match header
+#[derive(Debug, Clone, PartialEq)]
+pub enum IfMatch {
+    Any,
+    Tags(Vec<Cursor>),
+}
+
+impl<T> From<Option<T>> for Precondition<T> {
+    /// A conditional write which matched no row -- either the row changed, or it is gone
+    fn from(current: Option<T>) -> Self {
+        match current {
+            Some(current) => Precondition::Failed(current),
+            None => Precondition::NotFound,
+        }
+    }
+}
+
+/// Quoted (strong) ETag for a row
+pub fn etag(id: Id, updated_at: NaiveDateTime) -> String {
+    format!("\"{}\"", Cursor::new(updated_at, id))
+}
+
+/// ETag for a serialized model, read from its id and updated_at fields. None for
+///     models without an updated_at
+pub fn etag_of<T: Serialize>(model: &T) -> Option<String> {
+    let value = serde_json::to_value(model).ok()?;
+    let id = Id::try_from(value.get("id")?.as_str()?.to_string()).ok()?;
+    let updated_at = parse_date(value.get("updated_at")?.as_str()?)?;
+    Some(etag(id, updated_at))
+}
+
+impl IfMatch {
+
+    /// Read the If-Match header, if there is one. Tags which we could not have
+    ///     issued are dropped, so they never match
+    pub fn from_request(req: &HttpRequest) -> Option<Self> {
+        let val = req.headers().get(header::IF_MATCH)?.to_str().ok()?.trim();
+        if val == "*" {
+            return Some(IfMatch::Any);
+        }
+        Some(IfMatch::Tags(val.split(',')
+            .filter_map(|tag| tag.trim().trim_matches('"').parse().ok())
+            .collect()))
+    }
+
+    /// The updated_at the client last saw for the row with this id, if it sent one
+    pub fn updated_at(&self, id: &Id) -> Option<NaiveDateTime> {
+        match self {
+            IfMatch::Any => None,
+            IfMatch::Tags(tags) => tags.iter()
+                .find(|tag| &tag.id == id)
+                .map(|tag| tag.at),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn etag_round_trips_through_if_match() {
+        let (id, at) = (Id::gen(), NaiveDateTime::from_timestamp(1_767_225_600, 42_000));
+        let tag = etag(id.clone(), at);
+        let req = actix_web::test::TestRequest::default()
+            .insert_header((header::IF_MATCH, format!("\"other\", {}", tag)))
+            .to_http_request();
+        assert_eq!(IfMatch::from_request(&req).unwrap().updated_at(&id), Some(at));
+    }
+}
@@ -0,0 +1,249 @@
+//! In-process bus for model writes. Each model type gets its own ModelEvents<T> system
+//!     service, which forwards every ModelEvent<T> published for that type to the
+//!     Recipients subscribed to it, ex.
+//! ```ignore
+//! event::subscribe::<Record>(indexer.start().recipient());
+//! ```
+//! The Model write methods (insert, update, delete, archive..) defer() their events, as
+//!     should any other write. Inside Db::transaction_as() they are published only once
+//!     the transaction commits, and outside of one as soon as the write is made
+use std::{cell::RefCell, collections::HashSet, future::Future};
+use actix::prelude::*;
+
+/// Rows which can be published -- in practice any Model
+pub trait EventModel: Clone + Send + Unpin + 'static {}
+
+impl<T: Clone + Send + Unpin + 'static> EventModel for T {}
+
+/// A committed write to a row of T
+#[derive(Debug, Clone, PartialEq)]
+pub enum ModelEvent<T> {
+    Created(T),
+    Updated { old: T, new: T },
+    /// Holds the row as it was deleted -- for soft delete models, with status 'deleted'
+    Deleted(T),
+}
+
+impl<T: 'static> Message for ModelEvent<T> {
+    type Result = ();
+}
+
+pub enum ModelEventControl<T: EventModel> {
+    Subscribe(Recipient<ModelEvent<T>>),
+    Unsubscribe(Recipient<ModelEvent<T>>),
+}
+
+impl<T: EventModel> Message for ModelEventControl<T> {
+    type Result = ();
+}
+
+/// Fans ModelEvent<T> out to its subscribers. Subscribers whose actor has stopped are
+///     dropped the next time an event is sent
+pub struct ModelEvents<T: EventModel> {
+    subscribers: HashSet<Recipient<ModelEvent<T>>>,
+}
+
+impl<T> ModelEvent<T> {
+
+    /// The row as it is after the write -- None for deletes
+    pub fn after(&self) -> Option<&T> {
+        match self {
+            ModelEvent::Created(new) | ModelEvent::Updated { new, .. } => Some(new),
+            ModelEvent::Deleted(_) => None,
+        }
+    }
+
+    /// The row as it was before the write -- None for inserts
+    pub fn before(&self) -> Option<&T> {
+        match self {
+            ModelEvent::Updated { old, .. } | ModelEvent::Deleted(old) => Some(old),
+            ModelEvent::Created(_) => None,
+        }
+    }
+}
+
+impl<T: EventModel> Default for ModelEvents<T> {
+    fn default() -> Self {
+        Self { subscribers: HashSet::new() }
+    }
+}
+
+impl<T: EventModel> Actor for ModelEvents<T> {
+    type Context = Context<Self>;
+}
+
+impl<T: EventModel> Supervised for ModelEvents<T> {}
+
+impl<T: EventModel> SystemService for ModelEvents<T> {}
+
+impl<T: EventModel> Handler<ModelEventControl<T>> for ModelEvents<T> {
+    type Result = ();
+
+    fn handle(&mut self, msg: ModelEventControl<T>, _: &mut Self::Context) -> Self::Result {
+        match msg {
+            ModelEventControl::Subscribe(recipient) => {
+                self.subscribers.insert(recipient);
+            },
+            ModelEventControl::Unsubscribe(recipient) => {
+                self.subscribers.remove(&recipient);
+            },
+        }
+    }
+}
+
+impl<T: EventModel> Handler<ModelEvent<T>> for ModelEvents<T> {
+    type Result = ();
+
+    fn handle(&mut self, msg: ModelEvent<T>, _: &mut Self::Context) -> Self::Result {
+        self.subscribers.retain(|recipient| recipient.do_send(msg.clone()).is_ok());
+    }
+}
+
+/// Receive every ModelEvent<T> published from now on
+pub fn subscribe<T: EventModel>(recipient: Recipient<ModelEvent<T>>) {
+    ModelEvents::<T>::from_registry().do_send(ModelEventControl::Subscribe(recipient));
+}
+
+pub fn unsubscribe<T: EventModel>(recipient: Recipient<ModelEvent<T>>) {
+    ModelEvents::<T>::from_registry().do_send(ModelEventControl::Unsubscribe(recipient));
+}
+
+/// Send an event to T's subscribers. Does nothing outside of an actix System, ex. in
+///     CLI tools, since there is nobody to receive it
+pub fn publish<T: EventModel>(event: ModelEvent<T>) {
+    if System::try_current().is_some() {
+        ModelEvents::<T>::from_registry().do_send(event);
+    }
+}
+
+type Deferred = Box<dyn FnOnce() + Send>;
+
+tokio::task_local! {
+    static DEFERRED: RefCell<Vec<Deferred>>;
+}
+
+/// Events deferred while a transaction ran, to be published once it has committed
+#[must_use]
+pub(crate) struct Pending(Vec<Deferred>);
+
+impl Pending {
+
+    pub(crate) fn publish(self) {
+        self.0.into_iter().for_each(|publish| publish());
+    }
+}
+
+/// Publish event once the transaction run by Db::transaction_as() (or collect()) commits,
+///     or drop it if the transaction rolls back. Outside of one, publishes right away
+pub fn defer<T: EventModel>(event: ModelEvent<T>) {
+    let mut event = Some(event);
+    let _ = DEFERRED.try_with(|deferred| if let Some(event) = event.take() {
+        deferred.borrow_mut().push(Box::new(move || publish(event)));
+    });
+    if let Some(event) = event {
+        publish(event);
+    }
+}
+
+/// defer() an Updated event for a row, unless the write left it unchanged
+pub fn defer_update<T: EventModel + PartialEq>(old: T, new: T) {
+    if old != new {
+        defer(ModelEvent::Updated { old, new });
+    }
+}
+
+/// defer() a Created event for a row just inserted, handing the row back
+pub fn created<T: EventModel>(row: T) -> T {
+    defer(ModelEvent::Created(row.clone()));
+    row
+}
+
+/// defer() each of the events
+pub fn defer_all<T: EventModel, I: IntoIterator<Item = ModelEvent<T>>>(events: I) {
+    events.into_iter().for_each(defer);
+}
+
+/// Run fut, collecting the events it defers instead of publishing them
+pub(crate) async fn collect<F: Future>(fut: F) -> (F::Output, Pending) {
+    DEFERRED.scope(RefCell::new(Vec::new()), async move {
+        let out = fut.await;
+        (out, Pending(DEFERRED.with(|deferred| deferred.take())))
+    }).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Default)]
+    struct Collector(Vec<ModelEvent<i32>>);
+
+    struct Count;
+
+    impl Message for Count {
+        type Result = usize;
+    }
+
+    impl Actor for Collector {
+        type Context = Context<Self>;
+    }
+
+    impl Handler<ModelEvent<i32>> for Collector {
+        type Result = ();
+
+        fn handle(&mut self, msg: ModelEvent<i32>, _: &mut Self::Context) {
+            self.0.push(msg);
+        }
+    }
+
+    impl Handler<Count> for Collector {
+        type Result = usize;
+
+        fn handle(&mut self, _: Count, _: &mut Self::Context) -> usize {
+            self.0.len()
+        }
+    }
+
+    #[actix_rt::test]
+    async fn forwards_events_to_subscribers() {
+        let collector = Collector::default().start();
+        subscribe::<i32>(collector.clone().recipient());
+        publish(ModelEvent::Created(1));
+        // The bus handles messages in order, so once this is answered both events
+        //     have been forwarded
+        ModelEvents::<i32>::from_registry()
+            .send(ModelEvent::Updated { old: 1, new: 2 }).await.unwrap();
+        assert_eq!(collector.send(Count).await.unwrap(), 2);
+    }
+
+    #[actix_rt::test]
+    async fn holds_deferred_events_until_published() {
+        let collector = Collector::default().start();
+        subscribe::<i32>(collector.clone().recipient());
+        let (_, pending) = collect(async { defer(ModelEvent::Created(1)) }).await;
+        ModelEvents::<i32>::from_registry().send(ModelEvent::Deleted(0)).await.unwrap();
+        assert_eq!(collector.send(Count).await.unwrap(), 1);
+        pending.publish();
+        ModelEvents::<i32>::from_registry().send(ModelEvent::Deleted(0)).await.unwrap();
+        assert_eq!(collector.send(Count).await.unwrap(), 3);
+    }
+
+    #[actix_rt::test]
+    async fn model_writes_defer_their_events() -> anyhow::Result<()> {
+        use crate::{Db, models::{Model, Record, User}};
+        let db = Db::new(&dotenv::var("DATABASE_URL").unwrap()).await?;
+        let mut tx = db.pool.begin().await?;
+        let (res, pending) = collect(async {
+            let user = User { name: Some("events".into()), ..Default::default() }.insert(&mut tx).await?;
+            let record = Record::new("events".into(), user.id.clone()).insert(&mut tx).await?;
+            Record::archive(&mut tx, record.id.clone()).await?;
+            Record::delete(&mut tx, record.id).await?;
+            Ok::<_, sqlx::Error>(())
+        }).await;
+        res?;
+        // The user and record Created, the archive's Updated and the record's Deleted
+        assert_eq!(pending.0.len(), 4);
+        tx.rollback().await?;
+        Ok(())
+    }
+}
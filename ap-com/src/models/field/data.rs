@@ -0,0 +1,231 @@
+//! Typed field values. FieldData has a variant per FieldKind, and two encodings: the
+//!     binary one stored in field_values.value and field_targets.value, and JSON, where the
+//!     value sits next to its kind, ex.
+//! ```ignore
+//! { "kind": "Integer", "value": 72 }
+//! { "kind": "Date", "value": "2021-08-01" }
+//! { "kind": "Selection", "value": ["walk", "swim"] }
+//! ```
+//! The binary encoding has no kind tag, so it is read back with the kind of its field.
+//!     Numbers are big-endian, dates are days since 0001-01-01 and datetimes microseconds
+//!     since the unix epoch, and selections are length-prefixed UTF-8 strings
+use std::convert::TryInto;
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use serde_json::{json, Value};
+use super::FieldKind;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "kind", content = "value")]
+pub enum FieldData {
+    Integer(i64),
+    RealNum(f64),
+    Double(f64),
+    /// Lower and upper bound, inclusive
+    Range(f64, f64),
+    Date(NaiveDate),
+    DateTime(NaiveDateTime),
+    /// One option
+    Enumeration(String),
+    /// Any number of distinct options
+    Selection(Vec<String>),
+    Text(String),
+    Boolean(bool),
+}
+
+#[derive(Display, Debug, Clone, PartialEq)]
+pub enum FieldDataError {
+    #[display(fmt = "Expected a {:?} value, got {:?}", expected, found)]
+    KindMismatch { expected: FieldKind, found: FieldKind },
+    #[display(fmt = "Invalid {:?} value: {}", _0, _1)]
+    Invalid(FieldKind, String),
+    #[display(fmt = "{} is not an option of this field", _0)]
+    UnknownOption(String),
+}
+
+impl std::error::Error for FieldDataError {}
+
+impl From<FieldDataError> for sqlx::Error {
+    fn from(e: FieldDataError) -> Self {
+        sqlx::Error::Decode(Box::new(e))
+    }
+}
+
+impl FieldData {
+
+    pub fn kind(&self) -> FieldKind {
+        match self {
+            Self::Integer(_) => FieldKind::Integer,
+            Self::RealNum(_) => FieldKind::RealNum,
+            Self::Double(_) => FieldKind::Double,
+            Self::Range(..) => FieldKind::Range,
+            Self::Date(_) => FieldKind::Date,
+            Self::DateTime(_) => FieldKind::DateTime,
+            Self::Enumeration(_) => FieldKind::Enumeration,
+            Self::Selection(_) => FieldKind::Selection,
+            Self::Text(_) => FieldKind::Text,
+            Self::Boolean(_) => FieldKind::Boolean,
+        }
+    }
+
+    /// The number held by Integer, RealNum and Double values
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Self::Integer(n) => Some(*n as f64),
+            Self::RealNum(n) | Self::Double(n) => Some(*n),
+            _ => None,
+        }
+    }
+
+    /// Read a bare JSON value, ex. 72 or "2021-08-01", as a value of kind
+    pub fn from_json(kind: &FieldKind, value: Value) -> Result<Self, FieldDataError> {
+        serde_json::from_value(json!({ "kind": kind, "value": value }))
+            .map_err(|e| FieldDataError::Invalid(kind.clone(), e.to_string()))
+    }
+
+    /// The bare JSON value, without its kind
+    pub fn to_json(&self) -> Value {
+        match serde_json::to_value(self) {
+            Ok(Value::Object(mut obj)) => obj.remove("value").unwrap_or(Value::Null),
+            _ => Value::Null,
+        }
+    }
+
+    /// Check the value belongs in a field of kind. For Enumeration and Selection fields
+    ///     with an option list, the chosen options must be on it
+    pub fn validate(&self, kind: &FieldKind, options: Option<&[String]>) -> Result<(), FieldDataError> {
+        if &self.kind() != kind {
+            return Err(FieldDataError::KindMismatch { expected: kind.clone(), found: self.kind() });
+        }
+        let invalid = |msg: &str| Err(FieldDataError::Invalid(kind.clone(), msg.to_string()));
+        match self {
+            Self::RealNum(n) | Self::Double(n) if !n.is_finite() => invalid("not a finite number"),
+            Self::Range(lo, hi) if !lo.is_finite() || !hi.is_finite() => invalid("not a finite number"),
+            Self::Range(lo, hi) if lo > hi => invalid("lower bound above upper bound"),
+            Self::Selection(opts) if opts.iter().enumerate().any(|(i, o)| opts[..i].contains(o)) => invalid("option chosen twice"),
+            Self::Enumeration(opt) => check_options(std::slice::from_ref(opt), options),
+            Self::Selection(opts) => check_options(opts, options),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn encode(&self) -> Vec<u8> {
+        match self {
+            Self::Integer(n) => n.to_be_bytes().to_vec(),
+            Self::RealNum(n) | Self::Double(n) => n.to_be_bytes().to_vec(),
+            Self::Range(lo, hi) => {
+                let mut out = lo.to_be_bytes().to_vec();
+                out.extend_from_slice(&hi.to_be_bytes());
+                out
+            },
+            Self::Date(date) => date.num_days_from_ce().to_be_bytes().to_vec(),
+            Self::DateTime(dt) => (dt.timestamp() * 1_000_000 + dt.timestamp_subsec_micros() as i64).to_be_bytes().to_vec(),
+            Self::Enumeration(s) | Self::Text(s) => s.as_bytes().to_vec(),
+            Self::Selection(opts) => {
+                let mut out = Vec::new();
+                for opt in opts {
+                    out.extend_from_slice(&(opt.len() as u32).to_be_bytes());
+                    out.extend_from_slice(opt.as_bytes());
+                }
+                out
+            },
+            Self::Boolean(b) => vec![*b as u8],
+        }
+    }
+
+    /// Read a stored value of kind
+    pub fn decode(kind: &FieldKind, bytes: &[u8]) -> Result<Self, FieldDataError> {
+        let invalid = || FieldDataError::Invalid(kind.clone(), format!("cannot decode {} bytes", bytes.len()));
+        let utf8 = |bytes: &[u8]| String::from_utf8(bytes.to_vec()).map_err(|_| invalid());
+        let data = match kind {
+            FieldKind::Integer => Self::Integer(i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            FieldKind::RealNum => Self::RealNum(f64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            FieldKind::Double => Self::Double(f64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?)),
+            FieldKind::Range if bytes.len() == 16 => Self::Range(
+                f64::from_be_bytes(bytes[..8].try_into().map_err(|_| invalid())?),
+                f64::from_be_bytes(bytes[8..].try_into().map_err(|_| invalid())?)),
+            FieldKind::Range => return Err(invalid()),
+            FieldKind::Date => {
+                let days = i32::from_be_bytes(bytes.try_into().map_err(|_| invalid())?);
+                Self::Date(NaiveDate::from_num_days_from_ce_opt(days).ok_or_else(invalid)?)
+            },
+            FieldKind::DateTime => {
+                let micros = i64::from_be_bytes(bytes.try_into().map_err(|_| invalid())?);
+                let (secs, micros) = (micros.div_euclid(1_000_000), micros.rem_euclid(1_000_000));
+                Self::DateTime(NaiveDateTime::from_timestamp_opt(secs, micros as u32 * 1000).ok_or_else(invalid)?)
+            },
+            FieldKind::Enumeration => Self::Enumeration(utf8(bytes)?),
+            FieldKind::Text => Self::Text(utf8(bytes)?),
+            FieldKind::Selection => {
+                let (mut opts, mut rest) = (Vec::new(), bytes);
+                while !rest.is_empty() {
+                    if rest.len() < 4 {
+                        return Err(invalid());
+                    }
+                    let len = u32::from_be_bytes(rest[..4].try_into().map_err(|_| invalid())?) as usize;
+                    let opt = rest.get(4..4 + len).ok_or_else(invalid)?;
+                    opts.push(utf8(opt)?);
+                    rest = &rest[4 + len..];
+                }
+                Self::Selection(opts)
+            },
+            FieldKind::Boolean => match bytes {
+                [0] => Self::Boolean(false),
+                [1] => Self::Boolean(true),
+                _ => return Err(invalid()),
+            },
+        };
+        Ok(data)
+    }
+}
+
+fn check_options(chosen: &[String], options: Option<&[String]>) -> Result<(), FieldDataError> {
+    match options {
+        Some(options) => match chosen.iter().find(|opt| !options.contains(opt)) {
+            Some(opt) => Err(FieldDataError::UnknownOption(opt.clone())),
+            None => Ok(()),
+        },
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn round_trips_every_kind() {
+        let values = vec![
+            FieldData::Integer(-72),
+            FieldData::RealNum(1.5),
+            FieldData::Double(-0.25),
+            FieldData::Range(1.0, 2.5),
+            FieldData::Date(NaiveDate::from_ymd(2021, 8, 1)),
+            FieldData::DateTime(NaiveDate::from_ymd(1969, 12, 31).and_hms_micro(23, 59, 59, 500_000)),
+            FieldData::Enumeration("walk".into()),
+            FieldData::Selection(vec!["walk".into(), "".into(), "swim".into()]),
+            FieldData::Text("Slept well".into()),
+            FieldData::Boolean(true),
+        ];
+        for value in values {
+            let kind = value.kind();
+            assert_eq!(FieldData::decode(&kind, &value.encode()), Ok(value.clone()));
+            assert_eq!(FieldData::from_json(&kind, value.to_json()), Ok(value));
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_values() {
+        assert!(FieldData::from_json(&FieldKind::Integer, json!("72")).is_err());
+        assert!(FieldData::from_json(&FieldKind::Date, json!("yesterday")).is_err());
+        assert!(FieldData::decode(&FieldKind::Integer, &[0, 1]).is_err());
+        assert_eq!(FieldData::Text("72".into()).validate(&FieldKind::Integer, None),
+            Err(FieldDataError::KindMismatch { expected: FieldKind::Integer, found: FieldKind::Text }));
+        assert!(FieldData::Range(2.0, 1.0).validate(&FieldKind::Range, None).is_err());
+        let options = vec!["walk".to_string(), "swim".to_string()];
+        assert!(FieldData::Enumeration("walk".into()).validate(&FieldKind::Enumeration, Some(&options)).is_ok());
+        assert_eq!(FieldData::Selection(vec!["run".into()]).validate(&FieldKind::Selection, Some(&options)),
+            Err(FieldDataError::UnknownOption("run".into())));
+    }
+}
@@ -1,30 +1,44 @@
 pub mod value;
 pub mod target;
+pub mod data;
+pub mod options;
+pub mod series;
 
 use uuid::Uuid;
 use actix::prelude::*;
-use crate::{Id, Status, now, private};
+use actix_web::{HttpResponse, web::{self, Data, Json, Path, ServiceConfig}};
+use crate::{Db, Id, Status, now, private, util::respond, auth::jwt::EncodedUser};
 use serde::{Serialize, Deserialize};
-use crate::models::Model;
+use crate::models::{Model, ModelRoutes, Item, routes::actor};
+use crate::rel::link::LinkedTo;
+use self::{data::FieldData, value::FieldValue};
 use sqlx::{
     FromRow, Postgres, postgres::PgPool,
     types::chrono::{NaiveDateTime, Utc}
 };
 
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
+#[model(soft_delete)]
 pub struct Field {
     #[serde(default = "Id::gen")]
     pub id: Id,
     #[serde(default = "Id::nil")]
+    #[model(filter)]
     pub user_id: Id,
+    #[model(mutable, filter)]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub description: Option<String>,
     #[serde(default = "private")]
+    #[model(mutable, filter)]
     pub private: bool,
+    /// Fixed once the field is created -- stored values are encoded by it
     #[serde(default = "FieldKind::default")]
+    #[model(filter)]
     pub kind: FieldKind,
     #[serde(default = "Status::default")]
+    #[model(mutable, filter)]
     pub status: Status,
     #[serde(default = "now")]
     pub created_at: NaiveDateTime,
@@ -33,25 +47,84 @@ pub struct Field {
 }
 
 #[async_trait::async_trait]
-impl Model for Field {
-
-    fn table() -> String { String::from("fields") }
-
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>("
-            INSERT INTO fields (id, name, user_id, kind, private, status, description, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id
-            ")
-            .bind(&self.id)
-            .bind(&self.name)
-            .bind(&self.user_id)
-            .bind(&self.kind)
-            .bind(&self.private)
-            .bind(&self.status)
-            .bind(&self.description)
-            .bind(&self.created_at)
-            .bind(&self.updated_at)
-            .fetch_one(db).await?;
-        Ok(res)
+impl ModelRoutes for Field {
+    #[inline]
+    fn path() -> String { String::from("/field") }
+
+    fn model_routes(cfg: &mut ServiceConfig) {
+        cfg
+            .service(web::resource("/id/{id}/values")
+                .route(web::get().to(get_values))
+                .route(web::post().to(new_value))
+            )
+            .route("/id/{id}/history", web::get().to(series::get_history))
+            .route("/id/{id}/aggregate", web::get().to(series::get_aggregate))
+            .route("/id/{id}/as-of", web::get().to(series::get_as_of))
+            .service(web::resource("/{id}/options")
+                .route(web::get().to(options::get_options))
+                .route(web::post().to(options::new_option))
+            )
+            .service(web::resource("/{id}/options/order")
+                .route(web::put().to(options::reorder_options))
+            )
+            .service(web::resource("/{id}/options/{option_id}")
+                .route(web::patch().to(options::update_option))
+            )
+            .service(web::resource("/{id}/options/{option_id}/merge")
+                .route(web::post().to(options::merge_option))
+            );
+    }
+
+    fn includes() -> Vec<&'static str> { vec!["items"] }
+
+    async fn include(db: &PgPool, id: Id, name: &str) -> anyhow::Result<serde_json::Value> {
+        match name {
+            "items" => Ok(serde_json::to_value(<Self as LinkedTo<Item>>::get_links_to_entry(db, id).await?)?),
+            _ => Err(anyhow::anyhow!("Cannot include {}", name)),
+        }
+    }
+}
+
+/// Body of POST /field/id/{id}/values, ex. { "value": 72 } for an integer field, with the
+///     id of the item it was recorded for and the time it was recorded at, if any
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewValue {
+    pub value: serde_json::Value,
+    #[serde(default)]
+    pub item_id: Option<Id>,
+    #[serde(default)]
+    pub recorded_at: Option<NaiveDateTime>,
+}
+
+/// GET /field/id/{id}/values -- the field's values, decoded by its kind
+async fn get_values(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+    match FieldValue::list_typed(&db.pool, id.into_inner()).await {
+        Ok(Some(values)) => Ok(respond::ok(values)),
+        Ok(None) => Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
+
+/// POST /field/id/{id}/values -- the value is read as JSON of the field's kind, and
+///     rejected with 400 if it does not fit
+async fn new_value(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<NewValue>) -> actix_web::Result<HttpResponse> {
+    let field = match Field::get(&db.pool, id.into_inner()).await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => return Ok(respond::err(e)),
+    };
+    let NewValue { value, item_id, recorded_at } = body.into_inner();
+    let data = match FieldData::from_json(&field.kind, value) {
+        Ok(data) => data,
+        Err(e) => return Ok(respond::bad_request().body(e.to_string())),
+    };
+    let res = db.transaction_as(actor(&user), |tx| Box::pin(FieldValue::create(tx, field.id, item_id, recorded_at, data))).await;
+    match res {
+        Ok(value) => Ok(respond::created(value)),
+        Err(sqlx::Error::Decode(e)) => Ok(respond::bad_request().body(e.to_string())),
+        Err(sqlx::Error::RowNotFound) => Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23503") => Ok(respond::not_found("COULD NOT FIND ITEM")),
+        Err(e) => Ok(respond::err(e)),
     }
 }
 
@@ -120,6 +193,19 @@ pub enum FieldKind {
     Boolean
 }
 
+impl FieldKind {
+
+    /// Whether values choose from the field's options (see options::FieldOptions)
+    pub fn has_options(&self) -> bool {
+        matches!(self, Self::Enumeration | Self::Selection)
+    }
+
+    /// Whether values are single numbers, which can be aggregated (see series)
+    pub fn is_numeric(&self) -> bool {
+        matches!(self, Self::Integer | Self::RealNum | Self::Double)
+    }
+}
+
 impl Default for FieldKind {
     fn default() -> Self {
         FieldKind::Text
@@ -0,0 +1,482 @@
+//! Option sets of Enumeration and Selection fields. Every option has a stable id, and stored
+//!     values hold option ids rather than labels, so options can be renamed, recolored and
+//!     reordered without rewriting a single value, ex.
+//! ```ignore
+//! let walk = FieldOption::add(&db.pool, field.id.clone(), NewOption::label("Walk")).await?;
+//! FieldValue::create(&db.pool, field.id, None, None, FieldData::Enumeration(walk.id.to_string())).await?;
+//! ```
+//! An Enumeration field chooses one option and a Selection field any number of them. New
+//!     values may give an option by id or by label. Deprecated options stay readable but
+//!     cannot be chosen again, and a merged option is deprecated and read as the option it
+//!     was merged into
+use actix_web::{HttpResponse, web::{Data, Json, Path}};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use sqlx::{
+    Acquire, FromRow, Postgres,
+    postgres::{PgConnection, PgExecutor},
+    types::chrono::NaiveDateTime,
+};
+use crate::{Db, Id, Model, now, query::Sql, util::respond, auth::jwt::EncodedUser, models::routes::actor};
+use crate::models::event;
+use super::{Field, FieldKind, data::FieldData};
+
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
+pub struct FieldOption {
+    #[serde(default = "Id::gen")]
+    pub id: Id,
+    #[serde(default = "Id::nil")]
+    #[model(filter)]
+    pub field_id: Id,
+    #[model(mutable, filter)]
+    pub label: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
+    pub color: Option<String>,
+    #[serde(default)]
+    #[model(mutable)]
+    pub position: i32,
+    #[serde(default)]
+    #[model(mutable, filter)]
+    pub deprecated: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
+    pub merged_into: Option<Id>,
+    #[serde(default = "now")]
+    pub created_at: NaiveDateTime,
+    #[serde(default = "now")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// The options of one field, in order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct FieldOptions {
+    pub field_id: Id,
+    pub kind: FieldKind,
+    /// Whether values may choose more than one option, ie. the field is a Selection
+    pub multiple: bool,
+    pub options: Vec<FieldOption>,
+}
+
+/// Body of POST /field/{id}/options. Without a position, the option is added last
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewOption {
+    pub label: String,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub position: Option<i32>,
+}
+
+/// Body of PATCH /field/{id}/options/{option_id}
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct OptionPatch {
+    #[serde(default)]
+    pub label: Option<String>,
+    #[serde(default)]
+    pub color: Option<String>,
+    #[serde(default)]
+    pub deprecated: Option<bool>,
+}
+
+/// Body of PUT /field/{id}/options/order -- every option id of the field, in their new order
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct OptionOrder {
+    pub order: Vec<Id>,
+}
+
+/// Body of POST /field/{id}/options/{option_id}/merge
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MergeOption {
+    pub into: Id,
+}
+
+#[derive(Display, Debug)]
+pub enum OptionError {
+    #[display(fmt = "{:?} fields have no options", _0)]
+    NoOptions(FieldKind),
+    #[display(fmt = "Option labels cannot be empty")]
+    EmptyLabel,
+    #[display(fmt = "Invalid color {}, expected #rrggbb", _0)]
+    InvalidColor(String),
+    #[display(fmt = "The order must list every option of the field once")]
+    Order,
+    #[display(fmt = "Options can only be merged into another option which is not merged")]
+    MergeTarget,
+    #[display(fmt = "Merged options cannot be restored")]
+    Merged,
+    #[display(fmt = "No such field or option")]
+    NotFound,
+    #[display(fmt = "{}", _0)]
+    Db(sqlx::Error),
+}
+
+impl std::error::Error for OptionError {}
+
+impl From<sqlx::Error> for OptionError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl NewOption {
+
+    pub fn label(label: &str) -> Self {
+        Self { label: label.to_string(), color: None, position: None }
+    }
+}
+
+impl FieldOption {
+
+    /// Options of the field with field_id, in order, deprecated and merged ones included
+    pub async fn of_field<'e, E: PgExecutor<'e>>(db: E, field_id: Id) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM field_options WHERE field_id = $1 ORDER BY position, created_at")
+            .bind(field_id)
+            .fetch_all(db).await
+    }
+
+    /// Add an option to the field, moving the options at and after its position down one
+    pub async fn add<'a, A>(db: A, field_id: Id, new: NewOption) -> Result<Self, OptionError>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let label = check_label(&new.label)?;
+        if let Some(color) = &new.color {
+            check_color(color)?;
+        }
+        let mut tx = db.begin().await?;
+        option_field(&mut tx, &field_id).await?;
+        let (count,): (i64,) = sqlx::query_as("SELECT COUNT(*) FROM field_options WHERE field_id = $1")
+            .bind(&field_id)
+            .fetch_one(&mut tx).await?;
+        let position = new.position.unwrap_or(count as i32).clamp(0, count as i32);
+        let moved = sqlx::query_as::<Postgres, Self>("
+            UPDATE field_options SET position = position + 1, updated_at = $3
+            WHERE field_id = $1 AND position >= $2
+            RETURNING *")
+            .bind(&field_id)
+            .bind(position)
+            .bind(now())
+            .fetch_all(&mut tx).await?;
+        let option = Self {
+            id: Id::gen(),
+            field_id,
+            label,
+            color: new.color,
+            position,
+            deprecated: false,
+            merged_into: None,
+            created_at: now(),
+            updated_at: now(),
+        }.insert(&mut tx).await?;
+        tx.commit().await?;
+        for new in moved {
+            event::defer_update(Self { position: new.position - 1, ..new.clone() }, new);
+        }
+        Ok(option)
+    }
+
+    /// Rename, recolor, deprecate or restore an option. Values holding it are left as they are
+    pub async fn update<'a, A>(db: A, field_id: Id, option_id: Id, patch: OptionPatch) -> Result<Self, OptionError>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut tx = db.begin().await?;
+        let mut option = Sql::<Self>::select()
+            .eq("id", option_id)
+            .eq("field_id", field_id)
+            .for_update()
+            .fetch_optional(&mut tx).await?
+            .ok_or(OptionError::NotFound)?;
+        let old = option.clone();
+        if let Some(label) = patch.label {
+            option.label = check_label(&label)?;
+        }
+        if let Some(color) = patch.color {
+            check_color(&color)?;
+            option.color = Some(color);
+        }
+        match patch.deprecated {
+            Some(false) if option.merged_into.is_some() => return Err(OptionError::Merged),
+            Some(deprecated) => option.deprecated = deprecated,
+            None => {},
+        }
+        let option = option.save(&mut tx).await?.ok_or(OptionError::NotFound)?;
+        tx.commit().await?;
+        Ok(option)
+    }
+
+    /// Give the field's options the positions of their ids in order
+    pub async fn reorder<'a, A>(db: A, field_id: Id, order: Vec<Id>) -> Result<Vec<Self>, OptionError>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut tx = db.begin().await?;
+        option_field(&mut tx, &field_id).await?;
+        let old = Self::of_field(&mut tx, field_id.clone()).await?;
+        if order.len() != old.len() || !old.iter().all(|opt| order.contains(&opt.id)) {
+            return Err(OptionError::Order);
+        }
+        for (position, id) in order.into_iter().enumerate() {
+            sqlx::query("UPDATE field_options SET position = $3, updated_at = $4 WHERE field_id = $1 AND id = $2")
+                .bind(&field_id)
+                .bind(id)
+                .bind(position as i32)
+                .bind(now())
+                .execute(&mut tx).await?;
+        }
+        let options = Self::of_field(&mut tx, field_id).await?;
+        tx.commit().await?;
+        defer_updates(old, &options);
+        Ok(options)
+    }
+
+    /// Merge an option into another of the same field. The option, and any merged into it
+    ///     before, are deprecated and read as `into` from then on
+    pub async fn merge<'a, A>(db: A, field_id: Id, option_id: Id, into: Id) -> Result<Self, OptionError>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        if option_id == into {
+            return Err(OptionError::MergeTarget);
+        }
+        let mut tx = db.begin().await?;
+        option_field(&mut tx, &field_id).await?;
+        let target = Sql::<Self>::select()
+            .eq("id", into.clone())
+            .eq("field_id", field_id.clone())
+            .fetch_optional(&mut tx).await?
+            .ok_or(OptionError::NotFound)?;
+        if target.merged_into.is_some() {
+            return Err(OptionError::MergeTarget);
+        }
+        let old = sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM field_options WHERE field_id = $1 AND (id = $2 OR merged_into = $2)
+            FOR UPDATE")
+            .bind(&field_id)
+            .bind(&option_id)
+            .fetch_all(&mut tx).await?;
+        let merged = sqlx::query_as::<Postgres, Self>("
+            UPDATE field_options SET merged_into = $3, deprecated = TRUE, updated_at = $4
+            WHERE field_id = $1 AND (id = $2 OR merged_into = $2)
+            RETURNING *")
+            .bind(&field_id)
+            .bind(&option_id)
+            .bind(&into)
+            .bind(now())
+            .fetch_all(&mut tx).await?;
+        let option = merged.iter()
+            .find(|opt| opt.id == option_id)
+            .cloned()
+            .ok_or(OptionError::NotFound)?;
+        tx.commit().await?;
+        defer_updates(old, &merged);
+        Ok(option)
+    }
+}
+
+/// defer() Updated events for the options in new which changed from old
+fn defer_updates(old: Vec<FieldOption>, new: &[FieldOption]) {
+    for old in old {
+        if let Some(new) = new.iter().find(|opt| opt.id == old.id) {
+            event::defer_update(old, new.clone());
+        }
+    }
+}
+
+impl FieldOptions {
+
+    /// The options of field. Empty for kinds without options
+    pub async fn of<'e, E: PgExecutor<'e>>(db: E, field: &Field) -> sqlx::Result<Self> {
+        let options = if field.kind.has_options() {
+            FieldOption::of_field(db, field.id.clone()).await?
+        } else {
+            Vec::new()
+        };
+        Ok(Self { field_id: field.id.clone(), kind: field.kind.clone(), multiple: field.kind == FieldKind::Selection, options })
+    }
+
+    /// Ids of the options new values may choose, for FieldData::validate(). None for kinds
+    ///     without options, which leaves their values unchecked, while a field with no
+    ///     options yet allows none
+    pub fn allowed(&self) -> Option<Vec<String>> {
+        if !self.kind.has_options() {
+            return None;
+        }
+        Some(self.options.iter()
+            .filter(|opt| !opt.deprecated)
+            .map(|opt| opt.id.to_string())
+            .collect())
+    }
+
+    /// A new value, with options given by label replaced by their ids
+    pub fn input(&self, data: FieldData) -> FieldData {
+        self.map(data, |choice| {
+            let id = self.options.iter()
+                .find(|opt| !opt.deprecated && opt.label.eq_ignore_ascii_case(&choice))
+                .map(|opt| opt.id.to_string())
+                .unwrap_or(choice);
+            self.canonical(id)
+        })
+    }
+
+    /// A stored value, with merged options replaced by the option they were merged into
+    pub fn read(&self, data: FieldData) -> FieldData {
+        self.map(data, |choice| self.canonical(choice))
+    }
+
+    fn canonical(&self, id: String) -> String {
+        self.options.iter()
+            .find(|opt| *opt.id == id)
+            .and_then(|opt| opt.merged_into.as_ref())
+            .map(|into| into.to_string())
+            .unwrap_or(id)
+    }
+
+    /// Apply f to each chosen option, dropping repeats
+    fn map<F: Fn(String) -> String>(&self, data: FieldData, f: F) -> FieldData {
+        match data {
+            FieldData::Enumeration(choice) => FieldData::Enumeration(f(choice)),
+            FieldData::Selection(choices) => {
+                let mut out = Vec::with_capacity(choices.len());
+                for choice in choices.into_iter().map(&f) {
+                    if !out.contains(&choice) {
+                        out.push(choice);
+                    }
+                }
+                FieldData::Selection(out)
+            },
+            data => data,
+        }
+    }
+}
+
+/// Lock the live field with field_id, which must be of a kind with options
+async fn option_field(conn: &mut PgConnection, field_id: &Id) -> Result<Field, OptionError> {
+    let field = Sql::<Field>::select()
+        .eq("id", field_id.clone())
+        .live()
+        .for_update()
+        .fetch_optional(&mut *conn).await?
+        .ok_or(OptionError::NotFound)?;
+    if !field.kind.has_options() {
+        return Err(OptionError::NoOptions(field.kind));
+    }
+    Ok(field)
+}
+
+fn check_label(label: &str) -> Result<String, OptionError> {
+    match label.trim() {
+        "" => Err(OptionError::EmptyLabel),
+        label => Ok(label.to_string()),
+    }
+}
+
+fn check_color(color: &str) -> Result<(), OptionError> {
+    let hex = color.strip_prefix('#').unwrap_or_default();
+    if hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        Ok(())
+    } else {
+        Err(OptionError::InvalidColor(color.to_string()))
+    }
+}
+
+/// GET /field/{id}/options
+pub(super) async fn get_options(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+    let field = match Field::get(&db.pool, id.into_inner()).await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => return Ok(respond::err(e)),
+    };
+    match FieldOptions::of(&db.pool, &field).await {
+        Ok(options) => Ok(respond::ok(options)),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
+
+/// POST /field/{id}/options
+pub(super) async fn new_option(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<NewOption>) -> actix_web::Result<HttpResponse> {
+    let (id, new) = (id.into_inner(), body.into_inner());
+    let res = db.transaction_as(actor(&user), |tx| Box::pin(FieldOption::add(tx, id, new))).await;
+    Ok(option_response(res))
+}
+
+/// PATCH /field/{id}/options/{option_id}
+pub(super) async fn update_option(db: Data<Db>, user: Option<EncodedUser>, ids: Path<(Id, Id)>, body: Json<OptionPatch>) -> actix_web::Result<HttpResponse> {
+    let ((id, option_id), patch) = (ids.into_inner(), body.into_inner());
+    let res = db.transaction_as(actor(&user), |tx| Box::pin(FieldOption::update(tx, id, option_id, patch))).await;
+    Ok(option_response(res))
+}
+
+/// PUT /field/{id}/options/order
+pub(super) async fn reorder_options(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<OptionOrder>) -> actix_web::Result<HttpResponse> {
+    let (id, OptionOrder { order }) = (id.into_inner(), body.into_inner());
+    let res = db.transaction_as(actor(&user), |tx| Box::pin(FieldOption::reorder(tx, id, order))).await;
+    Ok(option_response(res))
+}
+
+/// POST /field/{id}/options/{option_id}/merge
+pub(super) async fn merge_option(db: Data<Db>, user: Option<EncodedUser>, ids: Path<(Id, Id)>, body: Json<MergeOption>) -> actix_web::Result<HttpResponse> {
+    let ((id, option_id), MergeOption { into }) = (ids.into_inner(), body.into_inner());
+    let res = db.transaction_as(actor(&user), |tx| Box::pin(FieldOption::merge(tx, id, option_id, into))).await;
+    Ok(option_response(res))
+}
+
+fn option_response<T>(res: Result<T, OptionError>) -> HttpResponse
+where
+    for<'a> T: std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>
+{
+    match res {
+        Ok(out) => respond::ok(out),
+        Err(OptionError::NotFound) => respond::not_found("COULD NOT FIND FIELD OR OPTION"),
+        Err(OptionError::Db(sqlx::Error::Database(e))) if e.code().as_deref() == Some("23505") => {
+            HttpResponse::Conflict().body("An option with that label already exists")
+        },
+        Err(OptionError::Db(e)) => respond::err(e),
+        Err(e) => respond::bad_request().body(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn option(label: &str, merged_into: Option<&FieldOption>) -> FieldOption {
+        FieldOption {
+            id: Id::gen(),
+            field_id: Id::nil(),
+            label: label.to_string(),
+            color: None,
+            position: 0,
+            deprecated: merged_into.is_some(),
+            merged_into: merged_into.map(|opt| opt.id.clone()),
+            created_at: now(),
+            updated_at: now(),
+        }
+    }
+
+    #[test]
+    fn reads_labels_and_merged_options_as_live_ids() {
+        let walk = option("Walk", None);
+        let stroll = option("Stroll", Some(&walk));
+        let swim = option("Swim", None);
+        let (walk_id, stroll_id, swim_id) = (walk.id.to_string(), stroll.id.to_string(), swim.id.to_string());
+        let set = FieldOptions { field_id: Id::nil(), kind: FieldKind::Selection, multiple: true, options: vec![walk, stroll, swim] };
+        assert_eq!(set.allowed(), Some(vec![walk_id.clone(), swim_id.clone()]));
+        assert_eq!(set.input(FieldData::Selection(vec!["swim".into(), walk_id.clone()])),
+            FieldData::Selection(vec![swim_id.clone(), walk_id.clone()]));
+        assert_eq!(set.read(FieldData::Selection(vec![stroll_id.clone(), walk_id.clone(), swim_id.clone()])),
+            FieldData::Selection(vec![walk_id.clone(), swim_id]));
+        assert_eq!(set.read(FieldData::Enumeration(stroll_id)), FieldData::Enumeration(walk_id));
+        assert_eq!(set.input(FieldData::Enumeration("Run".into())), FieldData::Enumeration("Run".into()));
+    }
+
+    #[test]
+    fn allows_nothing_until_options_are_added() {
+        let empty = |kind| FieldOptions { field_id: Id::nil(), kind, multiple: false, options: Vec::new() };
+        assert_eq!(empty(FieldKind::Enumeration).allowed(), Some(Vec::new()));
+        assert_eq!(empty(FieldKind::Integer).allowed(), None);
+    }
+
+}
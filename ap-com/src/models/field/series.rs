@@ -0,0 +1,195 @@
+//! Field values as time series. Values are append-only, so the values of a field, or of one
+//!     (item, field) pair, ordered by recorded_at are its history, ex.
+//! ```ignore
+//! let weight = FieldValue::history(&db.pool, &field, &SeriesQuery::for_item(item_id)).await?;
+//! let weekly = FieldValue::aggregate(&db.pool, &field, Bucket::Week, &query).await?;
+//! let then = FieldValue::as_of(&db.pool, &field, Some(item_id), at).await?;
+//! ```
+//! Without an item, a series covers every value of the field. Only Integer, RealNum and
+//!     Double fields can be aggregated, from the num column written alongside their values
+use actix_web::{HttpResponse, web::{Data, Path, Query}};
+use serde::{Serialize, Deserialize};
+use sqlx::{Postgres, postgres::PgPool, types::chrono::NaiveDateTime};
+use crate::{Db, Id, Model, util::respond};
+use super::{Field, options::FieldOptions, value::{FieldValue, TypedFieldValue}};
+
+/// The span of time aggregates are grouped by, truncated as by Postgres' date_trunc().
+///     Weeks start on Monday
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Bucket {
+    Hour,
+    Day,
+    Week,
+    Month,
+}
+
+/// ?item_id=&from=&to= for series routes. from is inclusive, to exclusive
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct SeriesQuery {
+    #[serde(default)]
+    pub item_id: Option<Id>,
+    #[serde(default)]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub to: Option<NaiveDateTime>,
+}
+
+/// ?bucket=&item_id=&from=&to= for GET /field/id/{id}/aggregate
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AggregateQuery {
+    pub bucket: Bucket,
+    #[serde(default)]
+    pub item_id: Option<Id>,
+    #[serde(default)]
+    pub from: Option<NaiveDateTime>,
+    #[serde(default)]
+    pub to: Option<NaiveDateTime>,
+}
+
+/// ?at=&item_id= for GET /field/id/{id}/as-of
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AsOfQuery {
+    pub at: NaiveDateTime,
+    #[serde(default)]
+    pub item_id: Option<Id>,
+}
+
+/// The values recorded in one bucket
+#[derive(Debug, Clone, sqlx::FromRow, Serialize, Deserialize, PartialEq)]
+pub struct Aggregate {
+    /// Start of the bucket
+    pub bucket: NaiveDateTime,
+    pub count: i64,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub sum: f64,
+}
+
+impl Bucket {
+
+    fn unit(&self) -> &'static str {
+        match self {
+            Self::Hour => "hour",
+            Self::Day => "day",
+            Self::Week => "week",
+            Self::Month => "month",
+        }
+    }
+}
+
+impl SeriesQuery {
+
+    pub fn for_item(item_id: Id) -> Self {
+        Self { item_id: Some(item_id), ..Default::default() }
+    }
+}
+
+impl AggregateQuery {
+
+    pub fn series(&self) -> SeriesQuery {
+        SeriesQuery { item_id: self.item_id.clone(), from: self.from, to: self.to }
+    }
+}
+
+impl FieldValue {
+
+    /// The field's values within the query's range, earliest recorded first
+    pub async fn history(db: &PgPool, field: &Field, query: &SeriesQuery) -> sqlx::Result<Vec<TypedFieldValue>> {
+        let values = sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM field_values
+            WHERE field_id = $1
+                AND ($2::id IS NULL OR item_id = $2)
+                AND ($3::timestamp IS NULL OR recorded_at >= $3)
+                AND ($4::timestamp IS NULL OR recorded_at < $4)
+            ORDER BY recorded_at, created_at")
+            .bind(&field.id)
+            .bind(&query.item_id)
+            .bind(query.from)
+            .bind(query.to)
+            .fetch_all(db).await?;
+        let options = FieldOptions::of(db, field).await?;
+        Ok(Self::read_all(values, &field.kind, &options)?)
+    }
+
+    /// Count, min, max, avg and sum of the field's values within the query's range, per
+    ///     bucket, earliest first. Buckets without values are left out
+    pub async fn aggregate(db: &PgPool, field: &Field, bucket: Bucket, query: &SeriesQuery) -> sqlx::Result<Vec<Aggregate>> {
+        sqlx::query_as::<Postgres, Aggregate>("
+            SELECT date_trunc($5, recorded_at) AS bucket, COUNT(*) AS count,
+                MIN(num) AS min, MAX(num) AS max, AVG(num) AS avg, SUM(num) AS sum
+            FROM field_values
+            WHERE field_id = $1 AND num IS NOT NULL
+                AND ($2::id IS NULL OR item_id = $2)
+                AND ($3::timestamp IS NULL OR recorded_at >= $3)
+                AND ($4::timestamp IS NULL OR recorded_at < $4)
+            GROUP BY 1
+            ORDER BY 1")
+            .bind(&field.id)
+            .bind(&query.item_id)
+            .bind(query.from)
+            .bind(query.to)
+            .bind(bucket.unit())
+            .fetch_all(db).await
+    }
+
+    /// The last value recorded at or before `at`, ie. the field's value at that time
+    pub async fn as_of(db: &PgPool, field: &Field, item_id: Option<Id>, at: NaiveDateTime) -> sqlx::Result<Option<TypedFieldValue>> {
+        let value = sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM field_values
+            WHERE field_id = $1 AND ($2::id IS NULL OR item_id = $2) AND recorded_at <= $3
+            ORDER BY recorded_at DESC, created_at DESC
+            LIMIT 1")
+            .bind(&field.id)
+            .bind(item_id)
+            .bind(at)
+            .fetch_optional(db).await?;
+        let options = FieldOptions::of(db, field).await?;
+        Ok(Self::read_all(value.into_iter().collect(), &field.kind, &options)?.pop())
+    }
+}
+
+/// GET /field/id/{id}/history?item_id=&from=&to=
+pub(super) async fn get_history(db: Data<Db>, id: Path<Id>, query: Query<SeriesQuery>) -> actix_web::Result<HttpResponse> {
+    let field = match Field::get(&db.pool, id.into_inner()).await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => return Ok(respond::err(e)),
+    };
+    match FieldValue::history(&db.pool, &field, &query).await {
+        Ok(values) => Ok(respond::ok(values)),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
+
+/// GET /field/id/{id}/aggregate?bucket=day&item_id=&from=&to=
+pub(super) async fn get_aggregate(db: Data<Db>, id: Path<Id>, query: Query<AggregateQuery>) -> actix_web::Result<HttpResponse> {
+    let field = match Field::get(&db.pool, id.into_inner()).await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => return Ok(respond::err(e)),
+    };
+    if !field.kind.is_numeric() {
+        return Ok(respond::bad_request().body(format!("Cannot aggregate the values of a {:?} field", field.kind)));
+    }
+    match FieldValue::aggregate(&db.pool, &field, query.bucket, &query.series()).await {
+        Ok(buckets) => Ok(respond::ok(buckets)),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
+
+/// GET /field/id/{id}/as-of?at=2021-08-01T00:00:00&item_id=
+pub(super) async fn get_as_of(db: Data<Db>, id: Path<Id>, query: Query<AsOfQuery>) -> actix_web::Result<HttpResponse> {
+    let field = match Field::get(&db.pool, id.into_inner()).await {
+        Ok(Some(field)) => field,
+        Ok(None) => return Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(e) => return Ok(respond::err(e)),
+    };
+    let AsOfQuery { at, item_id } = query.into_inner();
+    match FieldValue::as_of(&db.pool, &field, item_id, at).await {
+        Ok(Some(value)) => Ok(respond::ok(value)),
+        Ok(None) => Ok(respond::not_found("NO VALUE RECORDED BY THEN")),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
@@ -1,20 +1,26 @@
 use crate::{now, private};
 use crate::models::Model;
+use super::{FieldKind, data::{FieldData, FieldDataError}};
 use serde::{Serialize, Deserialize};
 use sqlx::{
     FromRow, Postgres, postgres::PgPool,
     types::chrono::NaiveDateTime,
 };
 
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
 pub struct FieldTarget {
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(skip)]
     pub id: Option<i32>,
+    #[model(filter)]
     pub field_id: i32,
+    #[model(mutable, filter)]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub description: Option<String>,
     #[serde(default = "Vec::new")]
+    #[model(mutable)]
     pub value: Vec<u8>,
     #[serde(default = "now")]
     pub created_at: NaiveDateTime,
@@ -23,25 +29,6 @@ pub struct FieldTarget {
 
 }
 
-#[async_trait::async_trait]
-impl Model for FieldTarget {
-
-    fn table() -> String { String::from("field_targets") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>(
-            "INSERT INTO field_target (field_id, name, description, value)
-             vALUES ($1, $2)
-             RETURNING *")
-            .bind(self.field_id)
-            .bind(&self.name)
-            .bind(&self.description)
-            .bind(self.value)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-
-}
-
 impl FieldTarget {
 
     pub fn new(field_id: i32, name: String, description: Option<String>, value: Vec<u8>) -> Self {
@@ -54,6 +41,17 @@ impl FieldTarget {
     }
 
 
+    /// A target of data, checked against the kind of its field
+    pub fn typed(field_id: i32, kind: &FieldKind, name: String, description: Option<String>, data: &FieldData) -> Result<Self, FieldDataError> {
+        data.validate(kind, None)?;
+        Ok(Self::new(field_id, name, description, data.encode()))
+    }
+
+    /// Decode the stored bytes as a value of kind
+    pub fn data(&self, kind: &FieldKind) -> Result<FieldData, FieldDataError> {
+        FieldData::decode(kind, &self.value)
+    }
+
     pub async fn field(db: &PgPool, field_id: i32) -> sqlx::Result<Option<super::Field>> {
         let res = sqlx::query_as::<Postgres, super::Field>("
             SELECT * FROM fields WHERE id = $1")
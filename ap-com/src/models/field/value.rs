@@ -1,38 +1,69 @@
 use crate::{Status, now, private};
-use crate::{Model, Id};
+use crate::{Model, Id, query::Sql, models::event};
 use serde::{Serialize, Deserialize};
+use super::{Field, FieldKind, data::{FieldData, FieldDataError}, options::FieldOptions, series::SeriesQuery};
 use sqlx::{
-    FromRow, Postgres, postgres::PgPool,
+    Acquire, FromRow, Postgres, postgres::{PgPool, PgExecutor},
     types::chrono::{NaiveDateTime, Utc}
 };
 
+/// One value of a field, optionally for an item. Values are never updated: a new value is a
+///     new row, so the rows of an (item, field) pair are its history (see series)
 #[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
 pub struct FieldValue {
     #[serde(default = "Id::gen")]
     pub id: Id,
     #[serde(default = "Id::nil")]
     pub field_id: Id,
+    /// The item the value was recorded for, if any
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<Id>,
     #[serde(default = "Vec::new")]
     pub value: Vec<u8>,
+    /// The value of Integer, RealNum and Double fields as a double, for aggregation
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub num: Option<f64>,
+    /// When the value was measured or took effect, which may be before it was created
+    #[serde(default = "now")]
+    pub recorded_at: NaiveDateTime,
     #[serde(default = "now")]
     pub created_at: NaiveDateTime,
     #[serde(default = "now")]
     pub updated_at: NaiveDateTime,
 }
 
+/// A FieldValue as the API reads and writes it, with its value decoded by the field's kind
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TypedFieldValue {
+    pub id: Id,
+    pub field_id: Id,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub item_id: Option<Id>,
+    #[serde(flatten)]
+    pub data: FieldData,
+    pub recorded_at: NaiveDateTime,
+    pub created_at: NaiveDateTime,
+    pub updated_at: NaiveDateTime,
+}
+
 #[async_trait::async_trait]
 impl Model for FieldValue {
 
     fn table() -> String { String::from("field_values") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "field_id", "item_id", "value", "num", "recorded_at", "created_at", "updated_at"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["field_id", "item_id"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>(
-            "INSERT INTO field_values (field_id, value)
-             vALUES ($1, $2)
+            "INSERT INTO field_values (field_id, item_id, value, num, recorded_at)
+             VALUES ($1, $2, $3, $4, $5)
              RETURNING *")
             .bind(self.field_id)
+            .bind(self.item_id)
             .bind(self.value)
+            .bind(self.num)
+            .bind(self.recorded_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 
 }
@@ -43,12 +74,90 @@ impl FieldValue {
         Self {
             updated_at: now(),
             created_at: now(),
+            recorded_at: now(),
             id: Id::gen(),
+            item_id: None,
+            num: None,
             field_id,
             value
         }
     }
 
+    pub fn new_for_item(item_id: Id, field_id: Id, value: Vec<u8>) -> Self {
+        Self { item_id: Some(item_id), ..Self::new(field_id, value) }
+    }
+
+
+    /// Store data as a value of the field with field_id, after checking it against the
+    ///     field's kind and options, optionally for an item and recorded at a past time.
+    ///     Fails with RowNotFound if there is no such field, and with a Decode error holding
+    ///     the FieldDataError if data does not fit it. The Created event is deferred (see
+    ///     event::defer)
+    pub async fn create<'a, A>(db: A, field_id: Id, item_id: Option<Id>, recorded_at: Option<NaiveDateTime>, data: FieldData) -> sqlx::Result<TypedFieldValue>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut conn = db.acquire().await?;
+        let field = Sql::<Field>::select()
+            .eq("id", field_id.clone())
+            .live()
+            .fetch_one(&mut *conn).await?;
+        let options = FieldOptions::of(&mut *conn, &field).await?;
+        let data = options.input(data);
+        data.validate(&field.kind, options.allowed().as_deref())?;
+        let value = Self {
+            item_id,
+            num: data.as_f64(),
+            recorded_at: recorded_at.unwrap_or_else(now),
+            ..Self::new(field_id, data.encode())
+        }.insert(&mut *conn).await?;
+        Ok(value.typed(&field.kind)?)
+    }
+
+    /// The values of a field, earliest recorded first, decoded by its kind with merged options resolved.
+    ///     None if there is no such field
+    pub async fn list_typed(db: &PgPool, field_id: Id) -> sqlx::Result<Option<Vec<TypedFieldValue>>> {
+        let field = match Field::get(db, field_id.clone()).await? {
+            Some(field) => field,
+            None => return Ok(None),
+        };
+        Ok(Some(Self::history(db, &field, &SeriesQuery::default()).await?))
+    }
+
+    pub async fn for_field<'e, E: PgExecutor<'e>>(db: E, field_id: Id) -> sqlx::Result<Vec<Self>> {
+        sqlx::query_as::<Postgres, Self>("
+            SELECT * FROM field_values WHERE field_id = $1 ORDER BY recorded_at, created_at")
+            .bind(field_id)
+            .fetch_all(db).await
+    }
+
+    /// Decode the stored bytes as a value of kind
+    pub fn data(&self, kind: &FieldKind) -> Result<FieldData, FieldDataError> {
+        FieldData::decode(kind, &self.value)
+    }
+
+    pub fn typed(self, kind: &FieldKind) -> Result<TypedFieldValue, FieldDataError> {
+        Ok(TypedFieldValue {
+            data: self.data(kind)?,
+            id: self.id,
+            field_id: self.field_id,
+            item_id: self.item_id,
+            recorded_at: self.recorded_at,
+            created_at: self.created_at,
+            updated_at: self.updated_at,
+        })
+    }
+
+    /// Decode values of a field of kind, with merged options resolved
+    pub(super) fn read_all(values: Vec<Self>, kind: &FieldKind, options: &FieldOptions) -> Result<Vec<TypedFieldValue>, FieldDataError> {
+        values.into_iter()
+            .map(|value| {
+                let mut typed = value.typed(kind)?;
+                typed.data = options.read(typed.data);
+                Ok(typed)
+            })
+            .collect()
+    }
 
     pub async fn field(db: &PgPool, field_id: Id) -> sqlx::Result<Option<super::Field>> {
         let res = sqlx::query_as::<Postgres, super::Field>("
@@ -1,4 +1,5 @@
 use crate::models::user::User;
+use crate::models::event;
 use uuid::Uuid;
 use super::Model;
 use crate::{GroupRole, Id, Status, now, private};
@@ -72,8 +73,11 @@ impl Actor for Group {
 impl crate::Model for Group {
 
     fn table() -> String { String::from("groups") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "description", "image", "private", "status", "cover_image", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "description", "image", "private", "status", "cover_image"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "name", "private", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Group> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Group> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO groups (id, user_id, name, description, image, cover_image, private, status) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
             RETURNING *
@@ -87,7 +91,7 @@ impl crate::Model for Group {
             .bind(&self.private)
             .bind(&self.status)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -95,12 +99,16 @@ impl crate::Model for Group {
 impl crate::Model for GroupUser {
 
     fn table() -> String { String::from("group_users") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "group_id", "link_id", "name", "description", "role", "status", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["link_id", "name", "description", "role", "status"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "group_id", "role", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>(
             "INSERT INTO group_users (id, user_id, group_id, link_id, name, description,
                     role, status)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)"
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+            RETURNING *"
         )
             .bind(&self.id)
             .bind(&self.user_id)
@@ -111,7 +119,7 @@ impl crate::Model for GroupUser {
             .bind(&self.role)
             .bind(&self.status)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 
 }
@@ -123,7 +131,13 @@ impl Default for GroupUser {
             id: Id::gen(),
             user_id: Id::nil(),
             group_id: Id::nil(),
-            ..Default::default()
+            link_id: None,
+            name: None,
+            description: None,
+            role: None,
+            status: Status::Active,
+            created_at: now(),
+            updated_at: now(),
         }
     }
 }
@@ -149,6 +163,23 @@ impl GroupUser {
 }
 
 impl Group {
+
+    /// Create the group along with its creator's membership, as the group's admin,
+    ///     in one transaction
+    pub async fn create(self, db: &PgPool) -> sqlx::Result<(Group, GroupUser)> {
+        let mut tx = db.begin().await?;
+        let owner = GroupUser {
+            user_id: self.user_id.clone(),
+            group_id: self.id.clone(),
+            role: Some(GroupRole::Admin),
+            ..Default::default()
+        };
+        let group = Model::insert(self, &mut tx).await?;
+        let owner = owner.insert(&mut tx).await?;
+        tx.commit().await?;
+        Ok((group, owner))
+    }
+
     pub async fn add_member(db: &PgPool, group_id: Id, user_id: Id) -> anyhow::Result<GroupUser> {
         let gu = GroupUser { user_id, group_id, ..Default::default() }
             .insert(db).await?;
@@ -232,21 +263,5 @@ impl Group {
         Ok(res)
     }
 
-    pub async fn insert(self, db: &PgPool) -> anyhow::Result<Group> {
-        let res = sqlx::query_as::<Postgres, Self>("
-            INSERT INTO groups (user_id, name, description, image, cover_image, private, status) VALUES ($1, $2, $3, $4, $5, $6, $7)
-            RETURNING *
-            ")
-            .bind(&self.user_id)
-            .bind(&self.name)
-            .bind(&self.description)
-            .bind(&self.image)
-            .bind(&self.cover_image)
-            .bind(&self.private)
-            .bind(&self.status)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-
 
 }
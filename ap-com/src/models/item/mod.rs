@@ -1,33 +1,45 @@
 use uuid::Uuid;
 use actix::prelude::*;
+use actix_web::{HttpResponse, web::{self, Data, Path, Query, ServiceConfig}};
 use crate::{
+    Db,
+    util::respond,
     types::{Id, Status, now, private},
-    rel::link::{Linked, LinkedTo},
-    models::{Link, Model, field::{Field, FieldKind}}
+    rel::{link::{Linked, LinkedTo}, graph::{Graph, Node, Reached, DepthQuery}},
+    models::{Link, Model, ModelRoutes, Record, record::RecordItem, field::{Field, FieldKind}}
 };
+use crate::models::event;
 use serde::{Serialize, Deserialize};
 use sqlx::{
     FromRow, Postgres, postgres::PgPool,
     types::chrono::{NaiveDateTime, Utc}
 };
 
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
+#[model(soft_delete)]
 #[serde(deny_unknown_fields)]
 pub struct Item {
     #[serde(default = "Id::gen")]
     pub id: Id,
     #[serde(default = "Id::nil")]
+    #[model(filter)]
     pub user_id: Id,
+    #[model(mutable, filter)]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub description: Option<String>,
     #[serde(default = "private")]
+    #[model(mutable, filter)]
     pub private: bool,
     #[serde(default = "Status::default")]
+    #[model(mutable, filter)]
     pub status: Status,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub image: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub cover_image: Option<String>,
     #[serde(default = "now")]
     pub created_at: NaiveDateTime,
@@ -36,35 +48,41 @@ pub struct Item {
 }
 
 #[async_trait::async_trait]
-impl Model for Item {
-    fn table() -> String { String::from("items") }
+impl ModelRoutes for Item {
+    #[inline]
+    fn path() -> String { String::from("/item") }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>("
-            INSERT INTO items
-            (id, name, user_id, private, status, description,
-            image, cover_image, created_at, updated_at)
-            VALUES ($1,$2,$3,$4,$5,$6,$7,$8,$9, $10) RETURNING id
-            ")
-            .bind(&self.id)
-            .bind(&self.name)
-            .bind(&self.user_id)
-            .bind(&self.private)
-            .bind(&self.status)
-            .bind(&self.description)
-            .bind(&self.image)
-            .bind(&self.cover_image)
-            .bind(&self.created_at)
-            .bind(&self.updated_at)
-            .fetch_one(db).await?;
-        Ok(res)
+    fn model_routes(cfg: &mut ServiceConfig) {
+        cfg
+            .route("/id/{id}/records/sharing-field", web::get().to(get_records_sharing_field));
+    }
+
+    fn includes() -> Vec<&'static str> { vec!["records", "fields"] }
+
+    async fn include(db: &PgPool, id: Id, name: &str) -> anyhow::Result<serde_json::Value> {
+        match name {
+            "records" => Ok(serde_json::to_value(<Self as LinkedTo<Record>>::get_links_to_entry(db, id).await?)?),
+            "fields" => Ok(serde_json::to_value(<Self as LinkedTo<Field>>::get_links_to_entry(db, id).await?)?),
+            _ => Err(anyhow::anyhow!("Cannot include {}", name)),
+        }
+    }
+}
+/// GET /item/id/{id}/records/sharing-field?depth=3 -- see Item::records_sharing_field()
+async fn get_records_sharing_field(db: Data<Db>, id: Path<Id>, query: Query<DepthQuery>) -> actix_web::Result<HttpResponse> {
+    match Item::records_sharing_field(&db.pool, id.into_inner(), query.depth()).await {
+        Ok(records) => Ok(respond::ok(records)),
+        Err(e) => Ok(respond::err(e)),
     }
 }
+
 #[async_trait::async_trait]
 impl Model for ItemField {
     fn table() -> String { String::from("item_fields") }
+    fn columns() -> Vec<&'static str> { vec!["id", "link_id", "item_id", "field_id", "name", "description", "status", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["link_id", "name", "description", "status"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["item_id", "field_id", "link_id", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO item_fields
             (item_id, field_id, link_id, name, description, status)
@@ -78,7 +96,7 @@ impl Model for ItemField {
             .bind(&self.description)
             .bind(&self.status)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 
 }
@@ -294,16 +312,26 @@ impl Item {
         Ok(res)
     }
 
+    /// Records holding another item which shares a field with this one, found within
+    ///     max_depth hops of item_fields and record_items
+    pub async fn records_sharing_field(db: &PgPool, id: Id, max_depth: i32) -> sqlx::Result<Vec<Reached<Record>>> {
+        Graph::new()
+            .with::<ItemField>()
+            .with::<RecordItem>()
+            .through::<Field>()
+            .reachable::<Record, _>(db, Node::of::<Self>(id), max_depth).await
+    }
+
     pub async fn add_new_field(self, db: &PgPool, name: String, kind: FieldKind) -> anyhow::Result<ItemField> {
-        let field = Field::new(name, kind, self.user_id).insert(&db).await?;
-        let item_field = ItemField::new_basic(self.id, field.id, None).insert(&db).await?;
+        let field = Field::new(name, kind, self.user_id).insert(db).await?;
+        let item_field = ItemField::new_basic(self.id, field.id, None).insert(db).await?;
         Ok(item_field)
     }
 
     pub async fn add_existing_item(self, db: &PgPool, field_id: Id) -> anyhow::Result<ItemField> {
-        let field = Field::get(&db, field_id).await?;
+        let field = Field::get(db, field_id).await?;
         if let Some(field) = field {
-            let item_field = ItemField::new_basic(self.id, field.id, None).insert(&db).await?;
+            let item_field = ItemField::new_basic(self.id, field.id, None).insert(db).await?;
             Ok(item_field)
         } else {
             return Err(anyhow::anyhow!("Item does not exist"));
@@ -1,4 +1,5 @@
 use crate::{Id, Model};
+use crate::models::event;
 use serde::{Serialize, Deserialize};
 use sqlx::{
     FromRow, Postgres, postgres::{PgRow, PgPool},
@@ -22,9 +23,12 @@ pub struct Link {
 impl Model for Link {
 
     fn table() -> String { String::from("links") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Link> {
+    fn columns() -> Vec<&'static str> { vec!["id", "name", "value", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "value"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["name", "value"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Link> {
         let res = sqlx::query_as::<Postgres, Link>(
-            "INSERT INTO links id, name, value, created_at, updated_at)
+            "INSERT INTO links (id, name, value, created_at, updated_at)
              VALUES ($1, $2, $3, $4, $5)
              RETURNING *")
             .bind(&self.id)
@@ -33,7 +37,7 @@ impl Model for Link {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
 
     }
 }
@@ -53,7 +57,7 @@ impl Link {
             {
                 Ok(Some(link)) => Ok(link),
                 Ok(None) => {
-                    let link = Link::new(name, Some(value)).insert(&db).await?;
+                    let link = Link::new(name, Some(value)).insert(db).await?;
                     Ok(link)
                 },
                 Err(e) => Err(anyhow::anyhow!("Error getting link"))
@@ -65,7 +69,7 @@ impl Link {
             {
                 Ok(Some(link)) => Ok(link),
                 Ok(None) => {
-                    let link = Link::new(name, None).insert(&db).await?;
+                    let link = Link::new(name, None).insert(db).await?;
                     Ok(link)
                 },
                 Err(e) => Err(anyhow::anyhow!("Error getting link"))
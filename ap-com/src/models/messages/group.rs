@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use crate::{GroupRole, now};
+use crate::models::event;
 use crate::{Id, Model};
 use sqlx::{
     prelude::*, PgPool, Postgres,
@@ -31,7 +32,9 @@ pub struct DirectGroupMessage {
 #[async_trait::async_trait]
 impl Model for DirectGroupMessage {
     fn table() -> String { String::from("direct_group_messages") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "sender_id", "group_id", "replies_to_id", "content", "attachments", "restrict_to_role", "sent_at", "updated_at"] }
+    fn sortable_columns() -> Vec<&'static str> { vec!["sent_at", "updated_at"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO direct_group_messages
             (sender_id, group_id, replies_to_id,
@@ -47,7 +50,7 @@ impl Model for DirectGroupMessage {
             .bind(&self.attachments)
             .bind(&self.content)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -1,4 +1,5 @@
 use crate::Id;
+use crate::models::event;
 use super::GenericMessageBuilder;
 use serde::{Serialize, Deserialize};
 use crate::{
@@ -33,8 +34,10 @@ pub struct DirectTopicMessage {
 #[async_trait::async_trait]
 impl Model for DirectTopicMessage {
     fn table() -> String { String::from("direct_topic_messages") }
+    fn columns() -> Vec<&'static str> { vec!["id", "sender_id", "topic_id", "replies_to_id", "content", "attachments", "sent_at", "updated_at"] }
+    fn sortable_columns() -> Vec<&'static str> { vec!["sent_at", "updated_at"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO direct_topic_messages
             (id, sender_id, topic_id, replies_to_id, content,
@@ -49,7 +52,7 @@ impl Model for DirectTopicMessage {
             .bind(self.content.as_str())
             .bind(&self.attachments)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
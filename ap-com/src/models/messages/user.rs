@@ -1,5 +1,6 @@
 use serde::{Serialize, Deserialize};
 use crate::now;
+use crate::models::event;
 use crate::Id;
 use crate::models::Model;
 use sqlx::{FromRow, Postgres, PgPool, prelude::*, types::{
@@ -29,8 +30,12 @@ pub struct DirectUserMessage {
 
 #[async_trait::async_trait]
 impl Model for DirectUserMessage {
-    fn table() -> String { String::from("direct_group_messages") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn table() -> String { String::from("direct_user_messages") }
+    fn columns() -> Vec<&'static str> { vec!["id", "sender_id", "recipient_id", "replies_to_id", "content", "attachments", "sent_at", "read_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["content", "attachments", "read_at"] }
+    fn sortable_columns() -> Vec<&'static str> { vec!["sent_at", "updated_at"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["sender_id", "recipient_id", "replies_to_id"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO direct_user_messages
             (sender_id, recipient_id, replies_to_id,
@@ -45,7 +50,7 @@ impl Model for DirectUserMessage {
             .bind(&self.attachments)
             .bind(&self.content)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
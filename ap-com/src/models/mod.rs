@@ -1,4 +1,8 @@
 pub mod routes;
+pub mod bulk;
+pub mod etag;
+pub mod audit;
+pub mod event;
 pub mod action;
 pub mod automata;
 pub mod ai;
@@ -40,20 +44,27 @@ pub use messages::{
     DirectGroupMessageReadReceipt
 };
 pub use routes::ModelRoutes;
+pub use ap_derive::{Model, ModelRoutes};
 // pub use learn::LearningUnit;
 // pub use book::{UserBook, RecordBook, GroupBook, TopicBook};
 // pub use condition::Condition;
 
-use crate::Id;
+use crate::{Id, Status};
+use crate::query::{Query, Page, PageQuery, Sql};
+use bulk::{BulkOptions, BulkReport, RowResult};
+use etag::Precondition;
+use event::ModelEvent;
+use serde::Serialize;
+use serde_json::{Map, Value};
 use sqlx::{
-    prelude::*, FromRow, Postgres, PgPool,
-    postgres::PgRow,
+    prelude::*, Acquire, FromRow, Postgres, PgPool,
+    postgres::{PgRow, PgExecutor},
 };
 
 #[async_trait::async_trait]
 pub trait Model
 where
-    Self: Sized + for<'r> FromRow<'r, PgRow> + Unpin + Send {
+    Self: Sized + for<'r> FromRow<'r, PgRow> + Clone + PartialEq + Unpin + Send + 'static {
 
     /// Return corresponding table string
     fn table() -> String;
@@ -74,69 +85,356 @@ where
     }
 
 
-    /// Insert the model into the database
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self>;
+    /// Every column of the table. Names written into generated SQL are checked against
+    ///     this list. Defaults to id, the mutable, filterable and sortable columns, and
+    ///     status for soft_delete() models
+    fn columns() -> Vec<&'static str> {
+        let mut cols = vec!["id"];
+        if Self::soft_delete() {
+            cols.push("status");
+        }
+        let listed = Self::mutable_columns().into_iter()
+            .chain(Self::filterable_columns())
+            .chain(Self::sortable_columns());
+        for col in listed {
+            if !cols.contains(&col) {
+                cols.push(col);
+            }
+        }
+        cols
+    }
 
-    async fn get(db: &PgPool, id: Id) -> sqlx::Result<Option<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("SELECT * FROM {} WHERE id = $1", Self::table()))
-            .bind(id)
-            .fetch_optional(db).await?;
-        Ok(res)
+    /// Columns which may be changed through update(). Ids, foreign keys and
+    ///     timestamps should never be listed here. Defaults to none (immutable)
+    fn mutable_columns() -> Vec<&'static str> {
+        Vec::new()
     }
 
-    async fn get_all(db: &PgPool) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("SELECT * FROM {}", Self::table()))
-            .fetch_all(db).await?;
-        Ok(res)
+    /// Whether rows are soft deleted by setting status to 'deleted' instead of being
+    ///     removed. Requires a status column. Soft deleted rows are hidden from reads
+    ///     and updates until they are restored, or purged for good
+    fn soft_delete() -> bool {
+        false
+    }
+
+    /// Insert the model into the database. Implementations defer() a Created event for
+    ///     the new row (see event::created)
+    async fn insert<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self>;
+
+    /// Insert many rows in one transaction, or with opts.upsert, update rows whose id
+    ///     already exists. Each row runs under its own savepoint, so a failing row is
+    ///     reported in place without aborting the rest -- unless opts.atomic is set, in
+    ///     which case any failure rolls back the whole batch. Rows are mapped onto
+    ///     columns by their serialized field names. Rows an upsert overwrote are deferred
+    ///     as Updated, and the rest as Created
+    async fn insert_many<'a, A>(db: A, rows: Vec<Self>, opts: BulkOptions) -> sqlx::Result<BulkReport<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send,
+        Self: Serialize
+    {
+        let query_str = bulk::insert_sql::<Self>(opts.upsert)?;
+        let mut tx = db.begin().await?;
+        let mut results = Vec::with_capacity(rows.len());
+        for (row, model) in rows.into_iter().enumerate() {
+            let value = match serde_json::to_value(&model) {
+                Ok(value) => value,
+                Err(e) => {
+                    results.push(RowResult::Error { row, error: e.to_string() });
+                    continue;
+                }
+            };
+            let mut savepoint = tx.begin().await?;
+            // The row an upsert is about to overwrite, if any, so it can be reported as updated
+            let previous = match value.get("id").map(|id| serde_json::from_value::<Id>(id.clone())) {
+                Some(Ok(id)) if opts.upsert => Self::get_for_update(&mut savepoint, id).await?,
+                _ => None,
+            };
+            let res = sqlx::query_as::<Postgres, Self>(&query_str)
+                .bind(value)
+                .fetch_optional(&mut savepoint).await;
+            match res {
+                Ok(Some(data)) => {
+                    savepoint.commit().await?;
+                    results.push(RowResult::Ok { row, data, previous });
+                },
+                Ok(None) => {
+                    savepoint.rollback().await?;
+                    results.push(RowResult::Error { row, error: "Row with this id already exists".into() });
+                },
+                Err(e) => {
+                    savepoint.rollback().await?;
+                    results.push(RowResult::Error { row, error: e.to_string() });
+                },
+            }
+        }
+        let committed = !opts.atomic || results.iter().all(|r| r.is_ok());
+        if !committed {
+            tx.rollback().await?;
+            return Ok(BulkReport::new(committed, results));
+        }
+        tx.commit().await?;
+        for res in results.iter() {
+            match res {
+                RowResult::Ok { data, previous: None, .. } => event::defer(ModelEvent::Created(data.clone())),
+                RowResult::Ok { data, previous: Some(old), .. } => event::defer_update(old.clone(), data.clone()),
+                RowResult::Error { .. } => {},
+            }
+        }
+        Ok(BulkReport::new(committed, results))
+    }
+
+    /// Apply a JSON merge-patch to the row with the given id, setting only the supplied
+    ///     columns (null clears a column) and bumping updated_at. Keys which are not in
+    ///     mutable_columns() are rejected with ColumnNotFound. Overwrite for models which
+    ///     need to transform values (ex. hashing) before they are written
+    async fn update<'a, A>(db: A, id: Id, patch: Map<String, Value>) -> sqlx::Result<Option<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        check_patch::<Self>(&patch)?;
+        let mut conn = db.acquire().await?;
+        if patch.is_empty() {
+            return Self::get(&mut *conn, id).await;
+        }
+        let old = Self::get_for_update(&mut *conn, id.clone()).await?;
+        let new = Sql::<Self>::update()
+            .patch(patch)
+            .touch()
+            .eq("id", id)
+            .live()
+            .fetch_optional(&mut *conn).await?;
+        Ok(updated(old, new))
+    }
+
+    /// Like update(), but only applies the patch if the row's updated_at still equals the
+    ///     given timestamp, ie. nobody has changed it since the caller read it. Otherwise
+    ///     returns Precondition::Failed with the row as it is now
+    async fn update_if_unchanged<'a, A>(db: A, id: Id, updated_at: NaiveDateTime, patch: Map<String, Value>) -> sqlx::Result<Precondition<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        check_patch::<Self>(&patch)?;
+        let mut conn = db.acquire().await?;
+        let old = Self::get_for_update(&mut *conn, id.clone()).await?;
+        let query = if patch.is_empty() {
+            Sql::<Self>::select()
+        } else {
+            Sql::<Self>::update().patch(patch).touch()
+        };
+        let res = query
+            .eq("id", id.clone())
+            .eq("updated_at", updated_at)
+            .live()
+            .fetch_optional(&mut *conn).await?;
+        match updated(old, res) {
+            Some(model) => Ok(Precondition::Met(model)),
+            None => Ok(Self::get(&mut *conn, id).await?.into()),
+        }
+    }
+
+    /// Fetch a row by id, soft deleted or not, and lock it until the end of the current
+    ///     transaction -- ex. to read its old values before writing to it
+    async fn get_for_update<'e, E: PgExecutor<'e>>(db: E, id: Id) -> sqlx::Result<Option<Self>> {
+        Sql::<Self>::select()
+            .eq("id", id)
+            .for_update()
+            .fetch_optional(db).await
+    }
+
+    async fn get<'e, E: PgExecutor<'e>>(db: E, id: Id) -> sqlx::Result<Option<Self>> {
+        Sql::<Self>::select()
+            .eq("id", id)
+            .live()
+            .fetch_optional(db).await
+    }
+
+    /// Timestamp columns which list queries may be sorted and paginated by.
+    ///     The first column is the default sort
+    fn sortable_columns() -> Vec<&'static str> {
+        vec!["created_at", "updated_at"]
+    }
+
+    /// Columns which list queries may filter on by value (see query::Query).
+    ///     Date filters are allowed on sortable_columns() instead
+    fn filterable_columns() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Fetch one page of rows using keyset pagination on (sort column, id). Unlike
+    ///     get_all, this is safe to run against large tables
+    async fn get_page<'e, E: PgExecutor<'e>>(db: E, page: &PageQuery) -> sqlx::Result<Page<Self>> {
+        Query::<Self>::new().fetch_page(db, page).await
+    }
+
+    /// Fetch every row of the table -- prefer get_page() for anything user facing
+    async fn get_all<'e, E: PgExecutor<'e>>(db: E) -> sqlx::Result<Vec<Self>> {
+        Query::<Self>::new().fetch_all(db).await
     }
 
-    async fn delete(db: &PgPool, id: Id) -> sqlx::Result<Option<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("DELETE FROM {} WHERE id = $1 RETURNING *", Self::table()))
-            .bind(id)
+    /// Delete the row with the given id -- soft deletes for soft_delete() models
+    async fn delete<'e, E: PgExecutor<'e>>(db: E, id: Id) -> sqlx::Result<Option<Self>> {
+        let row = Sql::<Self>::delete()
+            .eq("id", id)
             .fetch_optional(db).await?;
-        Ok(res)
+        deleted(&row);
+        Ok(row)
     }
 
-    async fn delete_all(db: &PgPool) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("DELETE FROM {} RETURNING *", Self::table()))
-            .fetch_all(db).await?;
-        Ok(res)
+    async fn delete_all<'e, E: PgExecutor<'e>>(db: E) -> sqlx::Result<Vec<Self>> {
+        let rows = Sql::<Self>::delete().fetch_all(db).await?;
+        deleted(&rows);
+        Ok(rows)
     }
 
-    async fn get_by_id(self, db: &PgPool, kind: &str, id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("SELECT * FROM {} WHERE {}_id = $1", Self::table(), kind))
-            .bind(id)
-            .fetch_all(db).await?;
-        Ok(res)
+    async fn get_by_id<'e, E: PgExecutor<'e>>(self, db: E, kind: &str, id: Id) -> sqlx::Result<Vec<Self>> {
+        Sql::<Self>::select()
+            .eq(&format!("{}_id", kind), id)
+            .live()
+            .fetch_all(db).await
     }
 
-    async fn delete_by_id(db: &PgPool, id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("DELETE FROM {} WHERE id = $1", Self::table()))
-            .bind(id)
+    async fn delete_by_id<'e, E: PgExecutor<'e>>(db: E, id: Id) -> sqlx::Result<Vec<Self>> {
+        let rows = Sql::<Self>::delete()
+            .eq("id", id)
             .fetch_all(db).await?;
-        Ok(res)
+        deleted(&rows);
+        Ok(rows)
     }
-    async fn delete_by_id_kind<K: Model>(db: &PgPool, id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("DELETE FROM {} WHERE {} = $1", Self::table(), K::table()))
-            .bind(id)
+    async fn delete_by_id_kind<'e, E: PgExecutor<'e>, K: Model>(db: E, id: Id) -> sqlx::Result<Vec<Self>> {
+        let rows = Sql::<Self>::delete()
+            .eq(&K::id_str(), id)
             .fetch_all(db).await?;
-        Ok(res)
+        deleted(&rows);
+        Ok(rows)
+    }
+    async fn get_by_id_kind<'e, E: PgExecutor<'e>, K: Model>(db: E, id: Id) -> sqlx::Result<Vec<Self>> {
+        Sql::<Self>::select()
+            .eq(&K::id_str(), id)
+            .live()
+            .fetch_all(db).await
     }
-    async fn get_by_id_kind<K: Model>(db: &PgPool, id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("SELECT * FROM {} WHERE {} = $1", Self::table(), K::table()))
-            .bind(id)
+
+    /// Delete the row only if its updated_at still equals the given timestamp -- see
+    ///     update_if_unchanged()
+    async fn delete_if_unchanged<'a, A>(db: A, id: Id, updated_at: NaiveDateTime) -> sqlx::Result<Precondition<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut conn = db.acquire().await?;
+        let res = Sql::<Self>::delete()
+            .eq("id", id.clone())
+            .eq("updated_at", updated_at)
+            .fetch_optional(&mut *conn).await?;
+        deleted(&res);
+        match res {
+            Some(model) => Ok(Precondition::Met(model)),
+            None => Ok(Self::get(&mut *conn, id).await?.into()),
+        }
+    }
+
+    /// Set a live row's status to archived. Archived rows are still readable
+    async fn archive<'a, A>(db: A, id: Id) -> sqlx::Result<Option<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut conn = db.acquire().await?;
+        let old = Self::get_for_update(&mut *conn, id.clone()).await?;
+        let new = Sql::<Self>::update()
+            .set("status", Status::Archived)
+            .touch()
+            .eq("id", id)
+            .ne("status", Status::Deleted)
+            .fetch_optional(&mut *conn).await?;
+        Ok(updated(old, new))
+    }
+
+    /// Bring an archived or soft deleted row back to active
+    async fn restore<'a, A>(db: A, id: Id) -> sqlx::Result<Option<Self>>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut conn = db.acquire().await?;
+        let old = Self::get_for_update(&mut *conn, id.clone()).await?;
+        let new = Sql::<Self>::update()
+            .set("status", Status::Active)
+            .touch()
+            .eq("id", id)
+            .is_in("status", vec![Status::Archived, Status::Deleted])
+            .fetch_optional(&mut *conn).await?;
+        Ok(updated(old, new))
+    }
+
+    /// Permanently remove rows which were soft deleted longer ago than older_than.
+    ///     Does nothing for models which are not soft_delete()
+    async fn purge<'e, E: PgExecutor<'e>>(db: E, older_than: chrono::Duration) -> sqlx::Result<Vec<Self>> {
+        if !Self::soft_delete() {
+            return Ok(Vec::new());
+        }
+        let rows = Sql::<Self>::purge()
+            .eq("status", Status::Deleted)
+            .before("updated_at", crate::now() - older_than)
             .fetch_all(db).await?;
-        Ok(res)
+        deleted(&rows);
+        Ok(rows)
     }
     // TODO get from other table where Self::id_str() = ?
 
-    async fn get_after(self, db: &PgPool, datetime: NaiveDateTime) -> sqlx::Result<Vec<Self>> {
-        /* let res = sqlx::query_as::<Postgres, Self>(&format!("DELETE FROM {} WHERE {}_id = $1", Self::table(), kind))
-            .bind(id)
-            .fetch_all(db).await?; */
-        Ok(Vec::new())
+    /// Rows created after the given time. The creation timestamp is taken to be
+    ///     the first of sortable_columns() (created_at for most models)
+    async fn get_after<'e, E: PgExecutor<'e>>(db: E, datetime: NaiveDateTime) -> sqlx::Result<Vec<Self>> {
+        let created = Self::sortable_columns()[0];
+        Query::<Self>::new().after(created, datetime).fetch_all(db).await
+    }
+
+    /// Rows created before the given time
+    async fn get_before<'e, E: PgExecutor<'e>>(db: E, datetime: NaiveDateTime) -> sqlx::Result<Vec<Self>> {
+        let created = Self::sortable_columns()[0];
+        Query::<Self>::new().before(created, datetime).fetch_all(db).await
+    }
+
+    /// Rows created between the given times (inclusive)
+    async fn get_between<'e, E: PgExecutor<'e>>(db: E, from: NaiveDateTime, to: NaiveDateTime) -> sqlx::Result<Vec<Self>> {
+        let created = Self::sortable_columns()[0];
+        Query::<Self>::new().between(created, from, to).fetch_all(db).await
+    }
+
+    /// Rows created or changed after the given time, for incremental sync. Fails with
+    ///     ColumnNotFound for models without an updated_at column
+    async fn updated_since<'e, E: PgExecutor<'e>>(db: E, datetime: NaiveDateTime) -> sqlx::Result<Vec<Self>> {
+        Query::<Self>::new()
+            .with_deleted()
+            .after("updated_at", datetime)
+            .fetch_all(db).await
+    }
+}
+
+/// Reject patches touching columns outside mutable_columns()
+/// defer() an Updated event for a row a write changed from old to new, handing new back
+fn updated<M: Model>(old: Option<M>, new: Option<M>) -> Option<M> {
+    if let (Some(old), Some(new)) = (old, &new) {
+        event::defer_update(old, new.clone());
+    }
+    new
+}
+
+/// defer() a Deleted event for each of the rows just deleted
+fn deleted<'r, M: Model>(rows: impl IntoIterator<Item = &'r M>) {
+    event::defer_all(rows.into_iter().cloned().map(ModelEvent::Deleted));
+}
+
+pub(crate) fn check_patch<M: Model>(patch: &Map<String, Value>) -> sqlx::Result<()> {
+    let mutable = M::mutable_columns();
+    match patch.keys().find(|k| !mutable.contains(&k.as_str())) {
+        Some(col) => Err(sqlx::Error::ColumnNotFound(col.to_string())),
+        None => Ok(()),
     }
 }
+
+/// Condition excluding soft deleted rows, to be appended after a WHERE clause
+pub(crate) fn not_deleted<M: Model>() -> &'static str {
+    if M::soft_delete() { " AND \"status\" <> 'deleted'" } else { "" }
+}
+
 #[async_trait::async_trait]
 pub trait Relation<T>
 where
@@ -7,6 +7,7 @@ use crate::{
         Model
     },
 };
+use crate::models::event;
 use uuid::Uuid;
 use sqlx::{FromRow, PgPool, Postgres, types::chrono::{NaiveDateTime, Utc}};
 use serde::{Serialize, Deserialize};
@@ -34,7 +35,7 @@ pub struct Post {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
 pub struct GroupPost {
     #[serde(default = "Id::gen")]
     pub id: Id,
@@ -51,12 +52,16 @@ pub struct GroupPost {
 #[async_trait::async_trait]
 impl Model for Post {
     fn table() -> String { String::from("posts") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "responds_to_id", "image", "content", "feeling", "private", "status", "created_at", "updated_at"] }
+    fn id(self) -> Id { self.id }
+    fn mutable_columns() -> Vec<&'static str> { vec!["content", "image", "feeling", "private", "status"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "responds_to_id", "feeling", "private", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("INSERT INTO posts
-            (id, user_id, title, content, image, status, private,
+            (id, user_id, content, image, status, private,
             created_at, updated_at, feeling, responds_to_id)
-            VALUES ($1,, $2,, $3,, $4,, $5,, $6,, $7,, $8,, $9,, $10) RETURNING id")
+            VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10) RETURNING *")
             .bind(&self.id)
             .bind(&self.user_id)
             .bind(&self.content)
@@ -68,7 +73,7 @@ impl Model for Post {
             .bind(&self.feeling)
             .bind(&self.responds_to_id)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 
 }
@@ -85,24 +90,6 @@ impl GroupPost {
     }
 }
 
-#[async_trait::async_trait]
-impl Model for GroupPost {
-    fn table() -> String { String::from("group_posts") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>("
-            INSERT INTO group_posts (id, group_id, post_id, created_at, updated_at)
-            VALUES ($1,, $2,, $3,, $4,, $5) RETURNING *")
-            .bind(&self.id)
-            .bind(&self.group_id)
-            .bind(&self.post_id)
-            .bind(&self.created_at)
-            .bind(&self.updated_at)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-
-}
-
 impl Post {
 
     pub fn new(user_id: Id, content: String, responds_to: Option<Id>, image: Option<String>, feeling: Option<Feeling>) -> Self {
@@ -173,9 +160,18 @@ impl Post {
             post_id: self.id,
             link_id,
         };
-        let res = entry.insert(&db).await?;
+        let res = entry.insert(db).await?;
         Ok(res)
     }
+    /// Insert the post and file it under the topic in one transaction
+    pub async fn create_in_topic(self, db: &PgPool, topic_id: Id, link_id: Option<Id>) -> sqlx::Result<(Post, TopicPost)> {
+        let mut tx = db.begin().await?;
+        let post = self.insert(&mut tx).await?;
+        let entry = TopicPost::new(post.id.clone(), topic_id, link_id)
+            .insert(&mut tx).await?;
+        tx.commit().await?;
+        Ok((post, entry))
+    }
     pub async fn add_to_topic(self, db: &PgPool, topic_id: Id, link_id: Option<Id>) -> sqlx::Result<TopicPost> {
         let entry = TopicPost::new(self.id, topic_id, link_id)
             .insert(db).await?;
@@ -275,7 +271,7 @@ impl Default for TopicPost {
         }
     }
 }
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
 pub struct PostFeelingResponse {
     #[serde(default = "Id::gen")]
     pub id: Id,
@@ -306,9 +302,9 @@ impl TopicPost {
     pub fn new(post_id: Id, topic_id: Id, link_id: Option<Id>) -> Self {
         Self {
             id: Id::gen(),
-            post_id: Id::nil(),
-            topic_id: Id::nil(),
-            link_id: None,
+            post_id,
+            topic_id,
+            link_id,
             created_at: now(),
             updated_at: now(),
         }
@@ -317,40 +313,25 @@ impl TopicPost {
 #[async_trait::async_trait]
 impl Model for TopicPost {
     fn table() -> String { String::from("topic_posts") }
+    fn columns() -> Vec<&'static str> { vec!["id", "post_id", "topic_id", "link_id", "created_at", "updated_at"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>(
-           "INSERT INTO post_topics
-            (post_id, topic_id, link_id, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5) RETURNING id")
+           "INSERT INTO topic_posts
+            (id, post_id, topic_id, link_id, created_at, updated_at)
+            VALUES ($1, $2, $3, $4, $5, $6) RETURNING *")
+            .bind(&self.id)
             .bind(&self.post_id)
             .bind(&self.topic_id)
             .bind(&self.link_id)
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
 
     }
 }
 #[async_trait::async_trait]
-impl Model for PostFeelingResponse {
-    fn table() -> String { String::from("post_feeling_responses") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>(
-            "INSERT INTO post_feeling_responses
-            (post_id, user_id, feeling, created_at, updated_at)
-            VALUES ($1, $2, $3, $4, $5) RETURNING id")
-            .bind(&self.post_id)
-            .bind(&self.user_id)
-            .bind(&self.feeling)
-            .bind(&self.created_at)
-            .bind(&self.updated_at)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-}
-#[async_trait::async_trait]
 impl Linked for TopicPost {
     type Left = Topic;
     type Right = Post;
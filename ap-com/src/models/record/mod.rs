@@ -1,11 +1,15 @@
+pub mod schema;
+
 use actix::prelude::*;
+use actix_web::web::{self, ServiceConfig};
 use uuid::Uuid;
 use crate::{Id, Status, now, private};
+use crate::models::event;
 use crate::rel::link::{LinkedTo, Linked};
-use crate::models::{Model, Link};
+use crate::models::{Model, ModelRoutes, Link};
 use serde::{Serialize, Deserialize};
 use sqlx::{
-    FromRow, Postgres, postgres::PgPool,
+    FromRow, Postgres, postgres::{PgConnection, PgPool},
     types::chrono::{NaiveDateTime, Utc, }
 };
 use super::{Field,User, Topic, item::Item};
@@ -118,6 +122,11 @@ impl Linked for RecordItem {
     fn link_id(self) -> Option<Id> { self.link_id }
     fn left_id(self) -> Id { self.record_id }
     fn right_id(self) -> Id { self.item_id }
+
+    /// Give the item the fields of the record's schema, see schema::apply()
+    async fn after_link(&self, conn: &mut PgConnection) -> sqlx::Result<()> {
+        schema::apply(conn, &self.record_id, &self.item_id).await
+    }
 }
 
 impl RecordItem {
@@ -132,8 +141,13 @@ impl RecordItem {
 #[async_trait::async_trait]
 impl Model for Record {
     fn table() -> String { String::from("records") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "private", "status", "description", "image", "cover_image", "created_at", "updated_at"] }
+    fn id(self) -> Id { self.id }
+    fn soft_delete() -> bool { true }
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "private", "status", "description", "image", "cover_image"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "name", "private", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_scalar("
             INSERT INTO records (name, user_id, private, status, description, image, cover_image, created_at, updated_at) VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9) RETURNING id
             ")
@@ -147,14 +161,39 @@ impl Model for Record {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(Record { id: res, ..self })
+        Ok(event::created(Record { id: res, ..self }))
+    }
+}
+#[async_trait::async_trait]
+impl ModelRoutes for Record {
+    #[inline]
+    fn path() -> String { String::from("/record") }
+
+    fn model_routes(cfg: &mut ServiceConfig) {
+        cfg
+            .service(web::resource("/{id}/schema")
+                .route(web::get().to(schema::get_schema))
+                .route(web::put().to(schema::put_schema))
+            );
+    }
+
+    fn includes() -> Vec<&'static str> { vec!["items"] }
+
+    async fn include(db: &PgPool, id: Id, name: &str) -> anyhow::Result<serde_json::Value> {
+        match name {
+            "items" => Ok(serde_json::to_value(<Self as LinkedTo<Item>>::get_links_to_entry(db, id).await?)?),
+            _ => Err(anyhow::anyhow!("Cannot include {}", name)),
+        }
     }
 }
 #[async_trait::async_trait]
 impl super::Model for RecordItem {
     fn table() -> String { String::from("record_items") }
+    fn columns() -> Vec<&'static str> { vec!["id", "link_id", "record_id", "item_id", "name", "description", "status", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> { vec!["link_id", "name", "description", "status"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["record_id", "item_id", "link_id", "status"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO record_items
             (record_id, item_id, link_id, name, description, status)
@@ -168,7 +207,7 @@ impl super::Model for RecordItem {
             .bind(&self.description)
             .bind(&self.status)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -235,15 +274,15 @@ impl Record {
 
 
     pub async fn add_new_item(self, db: &PgPool, item_name: String) -> anyhow::Result<Self> {
-        let item = Item::new(item_name, self.clone().user_id).insert(&db).await?;
-        let item_link = RecordItem::new(self.clone().id, item.id, None, None, None).insert(&db).await?;
+        let item = Item::new(item_name, self.clone().user_id).insert(db).await?;
+        let item_link = RecordItem::new(self.clone().id, item.id, None, None, None).insert(db).await?;
         Ok(Self { ..self })
     }
 
     pub async fn add_existing_item(self, db: &PgPool, item_id: Id) -> anyhow::Result<RecordItem> {
-        let item = Item::get(&db, item_id).await?;
+        let item = Item::get(db, item_id).await?;
         if let Some(item) = item {
-            let item_link = RecordItem::new(self.clone().id, item.id, None, None, None).insert(&db).await?;
+            let item_link = RecordItem::new(self.clone().id, item.id, None, None, None).insert(db).await?;
             Ok(item_link)
         } else {
             return Err(anyhow::anyhow!("Item does not exist"));
@@ -0,0 +1,342 @@
+//! Record schemas. A record may declare the fields every item in it should have, each
+//!     required or optional and with an optional default value, ex.
+//! ```ignore
+//! Record::set_schema(&db.pool, record_id, vec![
+//!     SchemaFieldInput { field_id: mood_id, required: true, default: None },
+//!     SchemaFieldInput { field_id: notes_id, required: false, default: Some(json!("")) },
+//! ]).await?;
+//! ```
+//! An item linked to the record through RecordItem is given an ItemField for each schema
+//!     field it lacks, and the default value of each field it has no value for. The link
+//!     fails if a required field is still without a value. Changing a schema reports the
+//!     items already in the record which no longer conform, and leaves them as they are
+use std::fmt;
+use actix_web::{HttpResponse, web::{Data, Json, Path}};
+use derive_more::Display;
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sqlx::{
+    prelude::*, Acquire, FromRow, Postgres,
+    postgres::{PgConnection, PgExecutor, PgPool},
+    types::chrono::NaiveDateTime,
+};
+use crate::{Db, Id, Model, Status, now, query::Sql, util::respond, auth::jwt::EncodedUser, models::routes::actor};
+use crate::models::event::{self, ModelEvent};
+use crate::models::field::{Field, FieldKind, data::{FieldData, FieldDataError}, options::FieldOptions, value::FieldValue};
+use crate::models::item::ItemField;
+use super::Record;
+
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
+#[model(table = "record_schema_fields")]
+pub struct SchemaField {
+    #[serde(default = "Id::gen")]
+    pub id: Id,
+    #[model(filter)]
+    pub record_id: Id,
+    #[model(filter)]
+    pub field_id: Id,
+    #[serde(default)]
+    #[model(mutable, filter)]
+    pub required: bool,
+    /// Encoded like FieldValue::value
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
+    pub default_value: Option<Vec<u8>>,
+    #[serde(default)]
+    #[model(mutable)]
+    pub position: i32,
+    #[serde(default = "now")]
+    pub created_at: NaiveDateTime,
+    #[serde(default = "now")]
+    pub updated_at: NaiveDateTime,
+}
+
+/// A schema field as the API reads it, with the field's name and kind and a typed default
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TypedSchemaField {
+    pub field_id: Id,
+    pub name: String,
+    pub kind: FieldKind,
+    pub required: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default: Option<FieldData>,
+}
+
+/// One field of the body of PUT /record/{id}/schema. The default is JSON of the field's kind
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaFieldInput {
+    pub field_id: Id,
+    #[serde(default)]
+    pub required: bool,
+    #[serde(default)]
+    pub default: Option<Value>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Problem {
+    /// The item has no ItemField for the field
+    Unlinked,
+    /// The field is required, and the item has no value for it
+    MissingValue,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Violation {
+    pub field_id: Id,
+    pub problem: Problem,
+}
+
+/// An item of a record which does not conform to the record's schema
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Nonconforming {
+    pub item_id: Id,
+    pub violations: Vec<Violation>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SchemaReport {
+    pub record_id: Id,
+    pub fields: Vec<TypedSchemaField>,
+    pub nonconforming: Vec<Nonconforming>,
+}
+
+#[derive(Display, Debug)]
+pub enum SchemaError {
+    #[display(fmt = "No such record")]
+    NoRecord,
+    #[display(fmt = "No such field {}", _0)]
+    NoField(Id),
+    #[display(fmt = "Field {} is listed twice", _0)]
+    Duplicate(Id),
+    #[display(fmt = "Invalid default for field {}: {}", _0, _1)]
+    Default(Id, FieldDataError),
+    #[display(fmt = "{}", _0)]
+    Db(sqlx::Error),
+}
+
+impl std::error::Error for SchemaError {}
+
+impl From<sqlx::Error> for SchemaError {
+    fn from(e: sqlx::Error) -> Self {
+        Self::Db(e)
+    }
+}
+
+impl fmt::Display for Nonconforming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Item {} does not conform to the record's schema:", self.item_id)?;
+        for v in self.violations.iter() {
+            match v.problem {
+                Problem::Unlinked => write!(f, " field {} is not linked;", v.field_id)?,
+                Problem::MissingValue => write!(f, " field {} is required;", v.field_id)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Nonconforming {}
+
+impl From<Nonconforming> for sqlx::Error {
+    fn from(e: Nonconforming) -> Self {
+        sqlx::Error::Decode(Box::new(e))
+    }
+}
+
+impl Record {
+
+    /// The fields of the record's schema, in order
+    pub async fn schema<'e, E: PgExecutor<'e>>(db: E, id: Id) -> sqlx::Result<Vec<TypedSchemaField>> {
+        sqlx::query("
+            SELECT s.*, f.name, f.kind FROM record_schema_fields s
+            INNER JOIN fields f ON f.id = s.field_id AND f.status <> 'deleted'
+            WHERE s.record_id = $1
+            ORDER BY s.position")
+            .bind(id)
+            .try_map(|row| {
+                let field = SchemaField::from_row(&row)?;
+                let kind: FieldKind = row.try_get("kind")?;
+                let default = field.default_value.as_ref()
+                    .map(|bytes| FieldData::decode(&kind, bytes))
+                    .transpose()?;
+                Ok(TypedSchemaField {
+                    field_id: field.field_id,
+                    name: row.try_get("name")?,
+                    kind,
+                    required: field.required,
+                    default,
+                })
+            })
+            .fetch_all(db).await
+    }
+
+    /// The record's schema and the items which do not conform to it. None if there is no
+    ///     such record
+    pub async fn schema_report(db: &PgPool, id: Id) -> sqlx::Result<Option<SchemaReport>> {
+        if Self::get(db, id.clone()).await?.is_none() {
+            return Ok(None);
+        }
+        let mut conn = db.acquire().await?;
+        Ok(Some(SchemaReport {
+            fields: Self::schema(&mut conn, id.clone()).await?,
+            nonconforming: nonconforming(&mut conn, &id, None).await?,
+            record_id: id,
+        }))
+    }
+
+    /// Replace the record's schema with fields, in their order. Items already in the record
+    ///     are not changed, but the ones which no longer conform are reported
+    pub async fn set_schema<'a, A>(db: A, id: Id, fields: Vec<SchemaFieldInput>) -> Result<SchemaReport, SchemaError>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut tx = db.begin().await?;
+        Sql::<Self>::select()
+            .eq("id", id.clone())
+            .live()
+            .for_update()
+            .fetch_optional(&mut tx).await?
+            .ok_or(SchemaError::NoRecord)?;
+        let removed = Sql::<SchemaField>::delete()
+            .eq("record_id", id.clone())
+            .fetch_all(&mut tx).await?;
+        event::defer_all(removed.into_iter().map(ModelEvent::Deleted));
+        let mut seen: Vec<Id> = Vec::with_capacity(fields.len());
+        for (position, input) in fields.into_iter().enumerate() {
+            if seen.contains(&input.field_id) {
+                return Err(SchemaError::Duplicate(input.field_id));
+            }
+            let field = Field::get(&mut tx, input.field_id.clone()).await?
+                .ok_or_else(|| SchemaError::NoField(input.field_id.clone()))?;
+            let default_value = match input.default {
+                Some(value) => Some(encode_default(&mut tx, &field, value).await?),
+                None => None,
+            };
+            seen.push(input.field_id);
+            SchemaField {
+                id: Id::gen(),
+                record_id: id.clone(),
+                field_id: field.id,
+                required: input.required,
+                default_value,
+                position: position as i32,
+                created_at: now(),
+                updated_at: now(),
+            }.insert(&mut tx).await?;
+        }
+        let report = SchemaReport {
+            fields: Self::schema(&mut tx, id.clone()).await?,
+            nonconforming: nonconforming(&mut tx, &id, None).await?,
+            record_id: id,
+        };
+        tx.commit().await?;
+        Ok(report)
+    }
+}
+
+/// Give an item just linked to the record an ItemField for each schema field it lacks, and
+///     the default of each field it has no value for, through FieldValue::create() so it
+///     is checked against the field's current options. Fails with a Decode error holding
+///     the Nonconforming item if a required field is left without a value
+pub(crate) async fn apply(conn: &mut PgConnection, record_id: &Id, item_id: &Id) -> sqlx::Result<()> {
+    for field in Record::schema(&mut *conn, record_id.clone()).await? {
+        let linked = Sql::<ItemField>::select()
+            .eq("item_id", item_id.clone())
+            .eq("field_id", field.field_id.clone())
+            .ne("status", Status::Deleted)
+            .limit(1)
+            .fetch_optional(&mut *conn).await?;
+        if linked.is_none() {
+            ItemField::new(item_id.clone(), field.field_id.clone(), None, None, None)
+                .insert(&mut *conn).await?;
+        }
+        if let Some(default) = field.default {
+            let value = Sql::<FieldValue>::select()
+                .eq("item_id", item_id.clone())
+                .eq("field_id", field.field_id.clone())
+                .limit(1)
+                .fetch_optional(&mut *conn).await?;
+            if value.is_none() {
+                FieldValue::create(&mut *conn, field.field_id, Some(item_id.clone()), None, default).await?;
+            }
+        }
+    }
+    match nonconforming(conn, record_id, Some(item_id)).await?.pop() {
+        Some(item) => Err(item.into()),
+        None => Ok(()),
+    }
+}
+
+/// Check and encode a schema default given as JSON of the field's kind
+async fn encode_default(conn: &mut PgConnection, field: &Field, value: Value) -> Result<Vec<u8>, SchemaError> {
+    let invalid = |e| SchemaError::Default(field.id.clone(), e);
+    let options = FieldOptions::of(&mut *conn, field).await?;
+    let data = options.input(FieldData::from_json(&field.kind, value).map_err(invalid)?);
+    data.validate(&field.kind, options.allowed().as_deref()).map_err(invalid)?;
+    Ok(data.encode())
+}
+
+/// The live items of the record, or only item, which do not conform to its schema
+async fn nonconforming(conn: &mut PgConnection, record_id: &Id, item: Option<&Id>) -> sqlx::Result<Vec<Nonconforming>> {
+    let only = if item.is_some() { " AND ri.item_id = $2" } else { "" };
+    let sql = format!("
+        SELECT ri.item_id, s.field_id, s.required,
+            EXISTS (
+                SELECT 1 FROM item_fields f
+                WHERE f.item_id = ri.item_id AND f.field_id = s.field_id AND f.status <> 'deleted'
+            ) AS linked,
+            EXISTS (
+                SELECT 1 FROM field_values v
+                WHERE v.item_id = ri.item_id AND v.field_id = s.field_id
+            ) AS has_value
+        FROM record_items ri
+        INNER JOIN items i ON i.id = ri.item_id AND i.status <> 'deleted'
+        INNER JOIN record_schema_fields s ON s.record_id = ri.record_id
+        INNER JOIN fields fd ON fd.id = s.field_id AND fd.status <> 'deleted'
+        WHERE ri.record_id = $1 AND ri.status <> 'deleted'{only}
+        ORDER BY ri.item_id, s.position", only = only);
+    let mut query = sqlx::query_as::<Postgres, (Id, Id, bool, bool, bool)>(&sql).bind(record_id);
+    if let Some(item) = item {
+        query = query.bind(item);
+    }
+    let mut out: Vec<Nonconforming> = Vec::new();
+    for (item_id, field_id, required, linked, has_value) in query.fetch_all(conn).await? {
+        let mut violations = Vec::new();
+        if !linked {
+            violations.push(Violation { field_id: field_id.clone(), problem: Problem::Unlinked });
+        }
+        if required && !has_value {
+            violations.push(Violation { field_id, problem: Problem::MissingValue });
+        }
+        if violations.is_empty() {
+            continue;
+        }
+        match out.last_mut() {
+            Some(last) if last.item_id == item_id => last.violations.extend(violations),
+            _ => out.push(Nonconforming { item_id, violations }),
+        }
+    }
+    Ok(out)
+}
+
+/// GET /record/{id}/schema -- the schema, with the items which do not conform to it
+pub(super) async fn get_schema(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+    match Record::schema_report(&db.pool, id.into_inner()).await {
+        Ok(Some(report)) => Ok(respond::ok(report)),
+        Ok(None) => Ok(respond::not_found("COULD NOT FIND RECORD")),
+        Err(e) => Ok(respond::err(e)),
+    }
+}
+
+/// PUT /record/{id}/schema -- replace the schema, reporting the items which no longer conform
+pub(super) async fn put_schema(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<Vec<SchemaFieldInput>>) -> actix_web::Result<HttpResponse> {
+    let (id, fields) = (id.into_inner(), body.into_inner());
+    match db.transaction_as(actor(&user), |tx| Box::pin(Record::set_schema(tx, id, fields))).await {
+        Ok(report) => Ok(respond::ok(report)),
+        Err(SchemaError::NoRecord) => Ok(respond::not_found("COULD NOT FIND RECORD")),
+        Err(SchemaError::NoField(_)) => Ok(respond::not_found("COULD NOT FIND FIELD")),
+        Err(SchemaError::Db(e)) => Ok(respond::err(e)),
+        Err(e) => Ok(respond::bad_request().body(e.to_string())),
+    }
+}
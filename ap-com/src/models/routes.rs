@@ -1,6 +1,19 @@
-use actix_web::{HttpResponse, Responder, Scope, web::{self, Json, Path, ServiceConfig}, web::Data};
-use crate::{util::respond, Db, Model, Id};
+use actix_web::{HttpRequest, HttpResponse, HttpResponseBuilder, Responder, Scope, http::header, web::{self, Json, Path, Query, ServiceConfig}, web::Data};
+use std::convert::TryFrom;
+use crate::{util::respond, Db, Model, Id, query::{self, Page, PageQuery, Projection}, auth::{jwt::EncodedUser, extract::AdminUser}};
+use super::bulk::{self, BulkOptions, BULK_PAYLOAD_LIMIT};
+use super::etag::{self, IfMatch, Precondition};
 use serde::{Serialize, Deserialize};
+use serde_json::{Map, Value};
+use chrono::NaiveDateTime;
+use sqlx::PgPool;
+
+pub const DEFAULT_PURGE_DAYS: i64 = 30;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PurgeQuery {
+    pub days: Option<i64>,
+}
 
 //TODO require responder trait bound to customize responses per model?
 /// A trait which can be applied to structs implementing the Model trait,
@@ -8,7 +21,7 @@ use serde::{Serialize, Deserialize};
 #[async_trait::async_trait]
 pub trait ModelRoutes
 where
-    for<'a> Self: 'static + Model + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>
+    for<'a> Self: 'static + Model + Clone + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>
 {
 
     fn path() -> String {
@@ -26,57 +39,312 @@ where
                 .route(web::get().to(Self::service_get_all))
                 .route(web::post().to(Self::service_add_new))
                 .route(web::delete().to(Self::service_delete_all))
-                .route(web::put().to(Self::service_update))
+            )
+            .service(web::resource("/bulk")
+                .app_data(web::PayloadConfig::new(BULK_PAYLOAD_LIMIT))
+                .route(web::post().to(Self::service_add_bulk))
+            )
+            .service(web::resource("/since/{timestamp}")
+                .route(web::get().to(Self::service_get_since))
             )
             .service(web::resource("/id/{id}")
                 .route(web::get().to(Self::service_get_by_id))
+                .route(web::put().to(Self::service_update))
+                .route(web::patch().to(Self::service_update))
                 .route(web::delete().to(Self::service_delete_by_id))
             );
+        if Self::soft_delete() {
+            cfg
+                .service(web::resource("/id/{id}/archive")
+                    .route(web::post().to(Self::service_archive))
+                )
+                .service(web::resource("/id/{id}/restore")
+                    .route(web::post().to(Self::service_restore))
+                )
+                .service(web::resource("/purge")
+                    .route(web::delete().to(Self::service_purge))
+                );
+        }
     }
 
     fn model_routes(cfg: &mut ServiceConfig);
 
+    /// Related entities which can be embedded in GET responses with ?include=,
+    ///     ex. profile and accounts for users. Each is fetched with include()
+    fn includes() -> Vec<&'static str> {
+        Vec::new()
+    }
+
+    /// Fetch the related entity called name for the row with this id
+    async fn include(_db: &PgPool, _id: Id, name: &str) -> anyhow::Result<Value> {
+        Err(anyhow::anyhow!("Cannot include {}", name))
+    }
+
+    /// Embed the included entities into a (projected) row, keyed by name
+    async fn compose(db: &PgPool, mut row: Value, include: &[String]) -> anyhow::Result<Value> {
+        if include.is_empty() {
+            return Ok(row);
+        }
+        let id = row.get("id")
+            .and_then(|id| id.as_str())
+            .and_then(|id| Id::try_from(id.to_string()).ok())
+            .ok_or_else(|| anyhow::anyhow!("Cannot include relations of a row without an id"))?;
+        if let Value::Object(obj) = &mut row {
+            for name in include.iter() {
+                obj.insert(name.clone(), Self::include(db, id.clone(), name).await?);
+            }
+        }
+        Ok(row)
+    }
+
+    /// Apply a projection to every row of a page. Includes are fetched row by row,
+    ///     so they are bounded by the page limit
+    async fn compose_page(db: &PgPool, page: Page<Self>, proj: &Projection) -> anyhow::Result<Page<Value>> {
+        let Page { data, next_cursor, prev_cursor } = page;
+        let rows = data.iter()
+            .map(|row| proj.project(row))
+            .collect::<serde_json::Result<Vec<Value>>>()?;
+        let mut data = Vec::with_capacity(rows.len());
+        for row in rows {
+            data.push(Self::compose(db, row, &proj.include).await?);
+        }
+        Ok(Page { data, next_cursor, prev_cursor })
+    }
+
     /// The service which encompasses all routes specified in the base trait and
     ///     in the model implementation of the model_routes() method
     fn service() -> Scope {
         web::scope(Self::path().as_str()).configure(Self::routes)
     }
 
-    async fn service_get_all(db: Data<Db>) -> actix_web::Result<HttpResponse> {
-        match Self::get_all(&db.pool).await {
-            Ok(model) => Ok(respond::ok(model)),
+    /// Returns a page envelope -- see query::PageQuery for the paging params, and
+    ///     query::Projection for ?fields= and ?include=. All other params are parsed
+    ///     as filters by query::Query. Soft deleted rows are only listed for admins
+    ///     filtering on status, ex. ?status=deleted
+    async fn service_get_all(
+        db: Data<Db>,
+        user: Option<EncodedUser>,
+        page: Query<PageQuery>,
+        params: Query<Vec<(String, String)>>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let filters = match query::Query::<Self>::parse(&params) {
+            Ok(filters) if filters.filters_on("status") && user.as_ref().map_or(false, |user| user.is_admin()) => filters.with_deleted(),
+            Ok(filters) => filters,
+            Err(e) => return Ok(respond::bad_request().body(e.to_string())),
+        };
+        let proj = Projection::parse(&params);
+        if let Some(name) = proj.unknown_include(&Self::includes()) {
+            return Ok(respond::bad_request().body(format!("Cannot include {}", name)));
+        }
+        match filters.fetch_page(&db.pool, &page).await {
+            Ok(page) if proj.is_empty() => Ok(respond::ok(page)),
+            Ok(page) => match Self::compose_page(&db.pool, page, &proj).await {
+                Ok(page) => Ok(respond::ok(page)),
+                Err(e) => Ok(respond::err(e)),
+            },
+            Err(sqlx::Error::ColumnNotFound(col)) => Ok(respond::bad_request()
+                .body(format!("Cannot sort by {}", col))),
             Err(e) => Ok(respond::err(e)),
         }
     }
-    async fn service_delete_all(db: Data<Db>) -> actix_web::Result<HttpResponse> {
-        match Self::delete_all(&db.pool).await {
-            Ok(model) => Ok(respond::ok(model)),
+    /// Rows created or changed after {timestamp} (a date, datetime or unix seconds),
+    ///     paged in updated_at order so clients can resume from the last row they saw
+    async fn service_get_since(
+        db: Data<Db>,
+        timestamp: Path<String>,
+        page: Query<PageQuery>,
+        params: Query<Vec<(String, String)>>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let since = match timestamp.parse::<i64>() {
+            Ok(secs) => NaiveDateTime::from_timestamp_opt(secs, 0),
+            Err(_) => query::parse_date(&timestamp),
+        };
+        let since = match since {
+            Some(since) => since,
+            None => return Ok(respond::bad_request().body(format!("Invalid timestamp {}", timestamp))),
+        };
+        let filters = match query::Query::<Self>::parse(&params) {
+            Ok(filters) => filters.with_deleted().after("updated_at", since),
+            Err(e) => return Ok(respond::bad_request().body(e.to_string())),
+        };
+        let proj = Projection::parse(&params);
+        if let Some(name) = proj.unknown_include(&Self::includes()) {
+            return Ok(respond::bad_request().body(format!("Cannot include {}", name)));
+        }
+        let page = PageQuery { sort: Some("updated_at".into()), ..page.into_inner() };
+        match filters.fetch_page(&db.pool, &page).await {
+            Ok(page) if proj.is_empty() => Ok(respond::ok(page)),
+            Ok(page) => match Self::compose_page(&db.pool, page, &proj).await {
+                Ok(page) => Ok(respond::ok(page)),
+                Err(e) => Ok(respond::err(e)),
+            },
+            Err(sqlx::Error::ColumnNotFound(col)) => Ok(respond::bad_request()
+                .body(format!("Cannot sync on {}", col))),
             Err(e) => Ok(respond::err(e)),
         }
     }
-    async fn service_delete_by_id(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
-        match Self::delete_by_id(&db.pool, id.into_inner()).await {
+    async fn service_delete_all(db: Data<Db>, user: Option<EncodedUser>) -> actix_web::Result<HttpResponse> {
+        match db.transaction_as(actor(&user), |tx| Self::delete_all(tx)).await {
             Ok(model) => Ok(respond::ok(model)),
             Err(e) => Ok(respond::err(e)),
         }
     }
-    async fn service_add_new(db: Data<Db>, model: Json<Self>) -> actix_web::Result<HttpResponse> {
-        match model.into_inner().insert(&db.pool).await {
-            Ok(model) => Ok(respond::ok(model)),
+    /// Honors If-Match -- responds 412 with the current row if it has changed since the
+    ///     ETag was issued
+    async fn service_delete_by_id(
+        db: Data<Db>,
+        req: HttpRequest,
+        user: Option<EncodedUser>,
+        id: Path<Id>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let id = id.into_inner();
+        let res = match IfMatch::from_request(&req) {
+            None | Some(IfMatch::Any) => match db.transaction_as(actor(&user), |tx| Self::delete_by_id(tx, id)).await {
+                Ok(model) => return Ok(respond::ok(model)),
+                Err(e) => return Ok(respond::err(e)),
+            },
+            Some(if_match) => match if_match.updated_at(&id) {
+                Some(updated_at) => db.transaction_as(actor(&user), |tx| Self::delete_if_unchanged(tx, id, updated_at)).await,
+                None => Self::get(&db.pool, id).await.map(Precondition::from),
+            },
+        };
+        match res {
+            Ok(Precondition::Met(model)) => Ok(respond::ok(vec![model])),
+            Ok(Precondition::Failed(current)) => Ok(tagged(HttpResponse::PreconditionFailed(), &current)),
+            Ok(Precondition::NotFound) => Ok(respond::not_found("NO ROW WITH THAT ID")),
             Err(e) => Ok(respond::err(e)),
         }
     }
-    async fn service_get_by_id(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
-        match Self::get(&db.pool, id.into_inner()).await {
+    async fn service_add_new(db: Data<Db>, user: Option<EncodedUser>, model: Json<Self>) -> actix_web::Result<HttpResponse> {
+        let model = model.into_inner();
+        match db.transaction_as(actor(&user), |tx| model.insert(tx)).await {
             Ok(model) => Ok(respond::ok(model)),
             Err(e) => Ok(respond::err(e)),
         }
     }
-    //TODO implement
-    async fn service_update(db: Data<Db>) -> actix_web::Result<HttpResponse> {
-        match Self::get_all(&db.pool).await {
-            Ok(model) => Ok(respond::ok(model)),
+    /// Accepts a JSON array or NDJSON body of rows -- see models::bulk. Responds with a
+    ///     result per row, or 422 if an atomic import was rolled back
+    async fn service_add_bulk(
+        db: Data<Db>,
+        user: Option<EncodedUser>,
+        opts: Query<BulkOptions>,
+        body: web::Bytes
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let rows = match bulk::parse_rows::<Self>(&body) {
+            Ok(rows) => rows,
+            Err(e) => return Ok(respond::bad_request().body(e)),
+        };
+        let opts = opts.into_inner();
+        match db.transaction_as(actor(&user), |tx| Box::pin(bulk::import(tx, rows, opts))).await {
+            Ok(report) if report.committed => Ok(respond::ok(report)),
+            Ok(report) => Ok(HttpResponse::UnprocessableEntity().json(report)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+    /// Sets an ETag which can be sent back as If-Match on updates and deletes. Accepts
+    ///     ?fields= and ?include= like service_get_all
+    async fn service_get_by_id(
+        db: Data<Db>,
+        id: Path<Id>,
+        params: Query<Vec<(String, String)>>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let proj = Projection::parse(&params);
+        if let Some(name) = proj.unknown_include(&Self::includes()) {
+            return Ok(respond::bad_request().body(format!("Cannot include {}", name)));
+        }
+        let model = match Self::get(&db.pool, id.into_inner()).await {
+            Ok(Some(model)) if proj.is_empty() => return Ok(tagged(HttpResponse::Ok(), &model)),
+            Ok(Some(model)) => model,
+            Ok(None) => return Ok(respond::ok(None::<Self>)),
+            Err(e) => return Ok(respond::err(e)),
+        };
+        let tag = etag::etag_of(&model);
+        let row = match proj.project(&model) {
+            Ok(row) => row,
+            Err(e) => return Ok(respond::err(e)),
+        };
+        match Self::compose(&db.pool, row, &proj.include).await {
+            Ok(row) => Ok(with_etag(HttpResponse::Ok(), tag).json(row)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+    async fn service_archive(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+        let id = id.into_inner();
+        match db.transaction_as(actor(&user), |tx| Self::archive(tx, id)).await {
+            Ok(Some(model)) => Ok(respond::ok(model)),
+            Ok(None) => Ok(respond::not_found("NO LIVE ROW WITH THAT ID")),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+    async fn service_restore(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+        let id = id.into_inner();
+        match db.transaction_as(actor(&user), |tx| Self::restore(tx, id)).await {
+            Ok(Some(model)) => Ok(respond::ok(model)),
+            Ok(None) => Ok(respond::not_found("NO ARCHIVED OR DELETED ROW WITH THAT ID")),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+    /// Admin only -- permanently removes rows soft deleted more than ?days=N ago (default 30)
+    async fn service_purge(
+        db: Data<Db>,
+        admin: AdminUser,
+        params: Query<PurgeQuery>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let days = chrono::Duration::days(params.days.unwrap_or(DEFAULT_PURGE_DAYS).max(0));
+        match db.transaction_as(Some(&admin.0.user_id), |tx| Self::purge(tx, days)).await {
+            Ok(purged) => Ok(respond::ok(purged)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+    /// Accepts a JSON merge-patch object -- only the supplied mutable columns are updated.
+    ///     Honors If-Match like service_delete_by_id
+    async fn service_update(
+        db: Data<Db>,
+        req: HttpRequest,
+        user: Option<EncodedUser>,
+        id: Path<Id>,
+        patch: Json<Map<String, Value>>
+    ) -> actix_web::Result<HttpResponse>
+    {
+        let (id, patch) = (id.into_inner(), patch.into_inner());
+        let res = match IfMatch::from_request(&req) {
+            None | Some(IfMatch::Any) => db.transaction_as(actor(&user), |tx| Self::update(tx, id, patch)).await
+                .map(|res| res.map_or(Precondition::NotFound, Precondition::Met)),
+            Some(if_match) => match if_match.updated_at(&id) {
+                Some(updated_at) => db.transaction_as(actor(&user), |tx| Self::update_if_unchanged(tx, id, updated_at, patch)).await,
+                None => Self::get(&db.pool, id).await.map(Precondition::from),
+            },
+        };
+        match res {
+            Ok(Precondition::Met(model)) => Ok(tagged(HttpResponse::Ok(), &model)),
+            Ok(Precondition::Failed(current)) => Ok(tagged(HttpResponse::PreconditionFailed(), &current)),
+            Ok(Precondition::NotFound) => Ok(respond::not_found("NO ROW WITH THAT ID")),
+            Err(sqlx::Error::ColumnNotFound(col)) => Ok(respond::bad_request()
+                .body(format!("Column {} does not exist or cannot be updated", col))),
             Err(e) => Ok(respond::err(e)),
         }
     }
 }
+
+/// The user to attribute a request's writes to in the audit log -- see models::audit
+pub(crate) fn actor(user: &Option<EncodedUser>) -> Option<&Id> {
+    user.as_ref().map(|user| &user.user_id)
+}
+
+/// Respond with the model as JSON, along with its ETag if it has one
+fn tagged<T: Serialize>(res: HttpResponseBuilder, model: &T) -> HttpResponse {
+    with_etag(res, etag::etag_of(model)).json(model)
+}
+
+fn with_etag(mut res: HttpResponseBuilder, tag: Option<String>) -> HttpResponseBuilder {
+    if let Some(tag) = tag {
+        res.insert_header((header::ETAG, tag));
+    }
+    res
+}
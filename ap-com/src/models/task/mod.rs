@@ -13,6 +13,7 @@ pub use status::{TaskStepExecStatus, TaskBookExecStatus};
 use actix::prelude::*;
 use actix_web::web::ServiceConfig;
 use crate::{Id, Db, Model, ModelRoutes};
+use crate::models::event;
 use derive_more::{AsRef, AsMut, Display, From};
 use serde::{Serialize, Deserialize};
 use chrono::NaiveDateTime;
@@ -42,8 +43,15 @@ pub struct Task {
 impl Model for Task {
     #[inline]
     fn table() -> String { "tasks".to_string() }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "name", "status", "private", "description", "created_at", "updated_at"] }
+    #[inline]
+    fn soft_delete() -> bool { true }
+    #[inline]
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "status", "private", "description"] }
+    #[inline]
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "name", "status", "private"] }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO tasks
             (id, user_id, name, description, status,
@@ -60,7 +68,7 @@ impl Model for Task {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
 
     }
 }
@@ -15,6 +15,7 @@ use crate::{
         post::{Post, TopicPost},
     }
 };
+use crate::models::event;
 
 #[derive(Debug, Clone, Serialize, Deserialize, )]
 pub struct ScoreRequest {
@@ -72,7 +73,13 @@ pub struct Topic {
 impl Model for Topic {
     #[inline]
     fn table() -> String { String::from("topics") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "name", "description", "created_at", "updated_at"] }
+    fn id(self) -> Id { self.id }
+    #[inline]
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "description"] }
+    #[inline]
+    fn filterable_columns() -> Vec<&'static str> { vec!["name"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let top = sqlx::query_as::<Postgres, Topic>("
             INSERT INTO topics (id, name, description)
             VALUES ($1, $2, $3)
@@ -81,7 +88,7 @@ impl Model for Topic {
             .bind(&self.name)
             .bind(&self.description)
             .fetch_one(db).await?;
-        Ok(top)
+        Ok(event::created(top))
     }
 }
 
@@ -95,30 +102,14 @@ impl ModelRoutes for Topic {
     }
 }
 
-#[async_trait::async_trait]
-impl Model for Category {
-    #[inline]
-    fn table() -> String { String::from("categories") }
-    #[inline]
-    fn id_str() -> String { String::from("category_id") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res =  sqlx::query_as::<Postgres, Self>("
-            INSERT INTO categories (id, name, description)
-            VALUES ($1, $2, $) RETURNING *")
-            .bind(&self.id)
-            .bind(&self.name)
-            .bind(&self.description)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-}
 #[async_trait::async_trait]
 impl Model for TopicCategory {
     #[inline]
     fn table() -> String { String::from("topic_categories") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "category_id", "topic_id", "score", "created_at", "updated_at"] }
     #[inline]
     fn id_str() -> String { String::from("topic_category_id") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res =  sqlx::query_as::<Postgres, TopicCategory>("
             INSERT INTO topic_categories (id, user_id, category_id,  topic_id, score)
             VALUES ($1, $2, $3, $4, $5) RETURNING *")
@@ -128,14 +119,19 @@ impl Model for TopicCategory {
             .bind(self.topic_id)
             .bind(self.score)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 #[async_trait::async_trait]
 impl Model for TopicVote {
     #[inline]
     fn table() -> String { String::from("topic_votes") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "topic_id", "is_for", "feeling", "description", "created_at", "updated_at"] }
+    #[inline]
+    fn mutable_columns() -> Vec<&'static str> { vec!["is_for", "feeling", "description"] }
+    #[inline]
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id", "topic_id", "is_for", "feeling"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res =  sqlx::query_as::<Postgres, TopicVote>("
             INSERT INTO topic_votes (id, user_id,  topic_id, is_for, description, feeling)
             VALUES ($1, $2, $3, $4, $5, $6) RETURNING *")
@@ -146,7 +142,7 @@ impl Model for TopicVote {
             .bind(&self.description)
             .bind(&self.feeling)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -167,13 +163,16 @@ pub struct TopicCategory {
     pub updated_at: NaiveDateTime,
 }
 
-#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model)]
 #[serde(deny_unknown_fields)]
+#[model(table = "categories", id_str = "category_id")]
 pub struct Category {
     #[serde(default = "Id::gen")]
     pub id: Id,
+    #[model(mutable)]
     pub name: String,
     #[serde(skip_serializing_if = "Option::is_none")]
+    #[model(mutable)]
     pub description: Option<String>,
     #[serde(default = "now")]
     pub created_at: NaiveDateTime,
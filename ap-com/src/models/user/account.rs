@@ -5,6 +5,7 @@ use crate::{models::{Credentials, User}, query::datetime::DateFilter, types::{
         now,  token::AccessToken
     }};
 use crate::{ModelRoutes, Model, Id};
+use crate::models::event;
 use sqlx::{postgres::PgPool, FromRow, Postgres, types::chrono::NaiveDateTime };
 use serde::{Serialize, Deserialize};
 
@@ -65,12 +66,17 @@ impl Model for Account {
 
     #[inline]
     fn table() -> String { String::from("accounts") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "provider_type", "provider_id", "provider_account_id", "refresh_token", "access_token", "access_token_expires", "created_at", "updated_at"] }
+    #[inline]
+    fn filterable_columns() -> Vec<&'static str> {
+        vec!["user_id", "provider_type", "provider_id", "provider_account_id"]
+    }
     #[inline]
     fn id_str() -> String { String::from("account_id") }
 
     fn id(self) -> Id { self.id }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let acct = sqlx::query_as::<Postgres, Self>("
             INSERT INTO accounts (
                 id,
@@ -98,7 +104,7 @@ impl Model for Account {
             .bind(self.created_at)
             .bind(self.updated_at)
             .fetch_one(db).await?;
-        Ok(acct)
+        Ok(event::created(acct))
     }
 }
 #[async_trait::async_trait]
@@ -1,5 +1,6 @@
 use actix::prelude::*;
 use crate::Model;
+use crate::models::event;
 use crate::{now, Id};
 use serde::{Serialize, Deserialize};
 use sqlx::{
@@ -11,11 +12,14 @@ use sqlx::{
 impl Model for UserBadge {
     #[inline]
     fn table() -> String { "user_badges".to_string() }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_level_id", "name", "description", "condition", "achieved_at"] }
+    #[inline]
+    fn sortable_columns() -> Vec<&'static str> { vec!["achieved_at"] }
     #[inline]
     fn id_str() -> String { "user_badge_id".to_string() }
     #[inline]
     fn id(self) -> Id { self.id }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("INSERT INTO user_badges
             (id, user_level_id, name, description, condition, achieved_at)
             VALUES ($1, $2, $3, $4, $5, $6)")
@@ -26,7 +30,7 @@ impl Model for UserBadge {
             .bind(&self.condition)
             .bind(&self.achieved_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -1,4 +1,3 @@
-use actix_web::web::ServiceConfig;
 use super::User;
 use uuid::Uuid;
 use pwhash::bcrypt::{BcryptSetup, BcryptVariant, self};
@@ -7,13 +6,16 @@ use sqlx::{FromRow, Postgres, postgres::PgPool};
 use crate::{Id, Model, models::ModelRoutes};
 use super::{Profile, Account,};
 
-#[derive(PartialOrd,  Debug, FromRow, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(PartialOrd,  Debug, FromRow, Clone, Serialize, Deserialize, PartialEq, Model, ModelRoutes)]
 #[sqlx(rename_all = "snake_case")]
+#[model(table = "credentials")]
 pub struct Credentials {
     #[serde(default = "Id::gen")]
     pub id: Id,
     #[serde(default = "Id::nil")]
+    #[model(filter)]
     pub user_id: Id,
+    #[model(filter)]
     pub username: String,
     #[serde(skip_serializing)]
     pub password: String,
@@ -26,32 +28,6 @@ pub struct CredentialsIn {
     pub password: String,
 }
 
-#[async_trait::async_trait]
-impl Model for Credentials {
-    #[inline]
-    fn table() -> String { String::from("credentials") }
-
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
-        let res = sqlx::query_as::<Postgres, Self>(
-        "INSERT INTO credentials
-        (id, user_id, username, password) VALUES ($1, $2, $3, $4)
-            RETURNING id")
-            .bind(&self.id)
-            .bind(&self.user_id)
-            .bind(&self.username)
-            .bind(&self.password)
-            .fetch_one(db).await?;
-        Ok(res)
-    }
-
-}
-#[async_trait::async_trait]
-impl ModelRoutes for Credentials {
-    fn model_routes(cfg: &mut ServiceConfig) {
-        cfg;
-    }
-}
-
 impl CredentialsIn {
 
     pub fn hash(self) -> Self {
@@ -215,7 +191,9 @@ impl CredentialsSignup {
     ///     Must create: User, Credentials, (Devisa) Account, Profile
     ///     *In that order* -> VerificationRequest to confirm email
     ///     -> UserLevel for gamification element
+    ///     All rows are inserted in one transaction, so a failure leaves nothing behind
     pub async fn signup_credentials(self, db: &PgPool) -> sqlx::Result<User> {
+        let mut tx = db.begin().await?;
         let (user_id, cred_id) = ( Uuid::new_v4(), Uuid::new_v4() );
         let user: User = User {
             id: Id::new(user_id),
@@ -223,7 +201,7 @@ impl CredentialsSignup {
             email: Some(self.email),
             ..Default::default()
         }
-            .insert(db).await?;
+            .insert(&mut tx).await?;
         let creds = Credentials {
             id: Id::new(cred_id),
             user_id: Id::new(user_id),
@@ -231,18 +209,19 @@ impl CredentialsSignup {
             password: self.password
         }
             .hash()
-            .insert(db).await?;
+            .insert(&mut tx).await?;
         let acct: Account = Account::new_devisa_credentials(
             Id::new(user_id),
             Id::new(cred_id),
         )
-            .insert(db).await?;
+            .insert(&mut tx).await?;
         let profile: Profile = Profile {
             id: Id::gen(),
             user_id: Id::new(user_id),
             ..Default::default()
         }
-            .insert(db).await?;
+            .insert(&mut tx).await?;
+        tx.commit().await?;
         tracing::info!("NEW SIGNUP: The user has signed up: {:?}\n
                                     The credentials have signed up: {:?}\n
                                     The account has signed up: {:?}\n
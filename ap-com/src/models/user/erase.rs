@@ -0,0 +1,240 @@
+//! Account erasure. erase() removes a user from every table in one transaction: rows others
+//!     may still depend on -- posts, messages, groups and public records, items, fields and
+//!     books -- are handed to the tombstone user, and everything else, private records and
+//!     items included, is deleted for good, ex.
+//! ```ignore
+//! let preview = erase(&db.pool, user_id.clone(), Some(&admin_id), true).await?;
+//! let receipt = erase(&db.pool, user_id, Some(&admin_id), false).await?;
+//! assert!(receipt.unwrap().verify()?);
+//! ```
+//! A dry run performs the same statements and rolls them back, so its receipt lists exactly
+//!     what would be removed, and publishes no events. Real runs return a receipt signed
+//!     with JWT_SECRET. The audit history of every erased row, deleted or anonymized, is
+//!     removed in the same transaction, and the user's id is cleared from the writes they
+//!     made to anything else (see audit::forget)
+use chrono::NaiveDateTime;
+use jsonwebtoken::{encode, decode, Header, Validation, EncodingKey, DecodingKey};
+use serde::{Serialize, Deserialize};
+use sqlx::postgres::{PgConnection, PgPool};
+use uuid::Uuid;
+use crate::{Id, Model, now, query::Sql, auth::jwt::jwt_secret};
+use crate::models::{
+    User, Record, Item, Field, Post, Group, Action,
+    audit,
+    event::{self, ModelEvent},
+    book::Book,
+    channel::Channel,
+    group::GroupUser,
+    post::PostFeelingResponse,
+    task::Task,
+    topic::{TopicCategory, TopicVote},
+    messages::{DirectUserMessage, DirectGroupMessage, DirectTopicMessage},
+};
+use super::{Account, Credentials, Profile, Session, UserLevel, UserBadge, VerificationRequest};
+
+/// Issuer of erasure receipts
+pub const RECEIPT_ISSUER: &str = "dvsa-erasure";
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum ErasureAction {
+    Deleted,
+    Anonymized,
+}
+
+/// The rows of one table which were deleted, or handed to the tombstone user
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Erased {
+    pub table: String,
+    pub action: ErasureAction,
+    pub ids: Vec<Id>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ErasureReceipt {
+    pub id: Id,
+    pub user_id: Id,
+    pub dry_run: bool,
+    pub erased_at: NaiveDateTime,
+    pub rows: Vec<Erased>,
+    /// HS256 JWT over the receipt's id, user and row counts. None for dry runs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq)]
+struct ReceiptClaims {
+    iss: String,
+    sub: Id,
+    jti: Id,
+    iat: i64,
+    rows: Vec<(String, ErasureAction, usize)>,
+}
+
+/// The id of the user erased users' posts and messages are reassigned to --
+///     ffffffff-ffff-ffff-ffff-ffffffffffff
+pub fn tombstone_id() -> Id {
+    Id::new(Uuid::from_u128(u128::MAX))
+}
+
+/// Erase the user with this id, attributing the changes to actor in the audit log. With
+///     dry_run, nothing is committed. None if there is no such user
+pub async fn erase(db: &PgPool, user_id: Id, actor: Option<&Id>, dry_run: bool) -> anyhow::Result<Option<ErasureReceipt>> {
+    if user_id == tombstone_id() {
+        return Err(anyhow::anyhow!("The tombstone user cannot be erased"));
+    }
+    let mut tx = audit::begin_as(db, actor).await?;
+    let user = match Sql::<User>::select().eq("id", user_id.clone()).for_update().fetch_optional(&mut tx).await? {
+        Some(user) => user,
+        None => return Ok(None),
+    };
+    let (rows, pending) = event::collect(erase_rows(&mut tx, &user)).await;
+    let rows = rows?;
+    let mut receipt = ErasureReceipt {
+        id: Id::gen(),
+        user_id,
+        dry_run,
+        erased_at: now(),
+        rows,
+        signature: None,
+    };
+    if dry_run {
+        tx.rollback().await?;
+    } else {
+        tx.commit().await?;
+        pending.publish();
+        receipt.signature = Some(receipt.sign()?);
+    }
+    Ok(Some(receipt))
+}
+
+async fn erase_rows(conn: &mut PgConnection, user: &User) -> anyhow::Result<Vec<Erased>> {
+    let (id, tomb) = (user.id.clone(), tombstone_id());
+    sqlx::query("INSERT INTO users (id, name) VALUES ($1, 'Deleted user') ON CONFLICT (id) DO NOTHING")
+        .bind(&tomb)
+        .execute(&mut *conn).await?;
+    let level_ids = Sql::<UserLevel>::select().eq("user_id", id.clone()).fetch_ids(&mut *conn).await?;
+    let mut rows = vec![
+        anonymize::<Post>(conn, "user_id", &id, &tomb, false).await?,
+        anonymize::<DirectUserMessage>(conn, "sender_id", &id, &tomb, false).await?,
+        anonymize::<DirectUserMessage>(conn, "recipient_id", &id, &tomb, false).await?,
+        anonymize::<DirectGroupMessage>(conn, "sender_id", &id, &tomb, false).await?,
+        anonymize::<DirectTopicMessage>(conn, "sender_id", &id, &tomb, false).await?,
+        anonymize::<Group>(conn, "user_id", &id, &tomb, false).await?,
+        anonymize::<Record>(conn, "user_id", &id, &tomb, true).await?,
+        anonymize::<Item>(conn, "user_id", &id, &tomb, true).await?,
+        anonymize::<Field>(conn, "user_id", &id, &tomb, true).await?,
+        anonymize::<Book>(conn, "user_id", &id, &tomb, true).await?,
+        delete(conn, Sql::<Record>::purge().eq("user_id", id.clone())).await?,
+        delete(conn, Sql::<Item>::purge().eq("user_id", id.clone())).await?,
+        delete(conn, Sql::<Field>::purge().eq("user_id", id.clone())).await?,
+        delete(conn, Sql::<Book>::purge().eq("user_id", id.clone())).await?,
+        delete(conn, Sql::<UserBadge>::purge().is_in("user_level_id", level_ids)).await?,
+    ];
+    rows.push(delete(conn, Sql::<UserLevel>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Credentials>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Account>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Session>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Profile>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Action>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Task>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<Channel>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<GroupUser>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<TopicCategory>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<TopicVote>::purge().eq("user_id", id.clone())).await?);
+    rows.push(delete(conn, Sql::<PostFeelingResponse>::purge().eq("user_id", id.clone())).await?);
+    if let Some(email) = user.email.clone() {
+        rows.push(delete(conn, Sql::<VerificationRequest>::purge().eq("identifier", email)).await?);
+    }
+    rows.push(delete(conn, Sql::<User>::purge().eq("id", id.clone())).await?);
+    let rows = rows.into_iter().filter(|erased| !erased.ids.is_empty()).collect::<Vec<Erased>>();
+    audit::forget(conn, &id, rows.iter().map(|erased| (erased.table.as_str(), erased.ids.as_slice()))).await?;
+    Ok(rows)
+}
+
+/// Hand the rows of M whose col is the user, or with public_only only those which are not
+///     private, to the tombstone user
+async fn anonymize<M: Model + Clone + 'static>(conn: &mut PgConnection, col: &str, user_id: &Id, tombstone: &Id, public_only: bool) -> sqlx::Result<Erased> {
+    let mut select = Sql::<M>::select().eq(col, user_id.clone()).for_update();
+    let mut update = Sql::<M>::update().set(col, tombstone.clone()).eq(col, user_id.clone()).touch();
+    if public_only {
+        select = select.eq("private", false);
+        update = update.eq("private", false);
+    }
+    let old = select.fetch_keyed(&mut *conn).await?;
+    let new = update.fetch_keyed(&mut *conn).await?;
+    for (id, new) in new.iter() {
+        if let Some((_, old)) = old.iter().find(|(old_id, _)| old_id == id) {
+            event::defer(ModelEvent::Updated { old: old.clone(), new: new.clone() });
+        }
+    }
+    Ok(Erased { table: M::table(), action: ErasureAction::Anonymized, ids: new.into_iter().map(|(id, _)| id).collect() })
+}
+
+async fn delete<M: Model + Clone + 'static>(conn: &mut PgConnection, sql: Sql<M>) -> sqlx::Result<Erased> {
+    let rows = sql.fetch_keyed(conn).await?;
+    let ids = rows.iter().map(|(id, _)| id.clone()).collect();
+    event::defer_all(rows.into_iter().map(|(_, row)| ModelEvent::Deleted(row)));
+    Ok(Erased { table: M::table(), action: ErasureAction::Deleted, ids })
+}
+
+impl ErasureReceipt {
+
+    fn claims(&self) -> ReceiptClaims {
+        ReceiptClaims {
+            iss: RECEIPT_ISSUER.to_string(),
+            sub: self.user_id.clone(),
+            jti: self.id.clone(),
+            iat: self.erased_at.timestamp(),
+            rows: self.rows.iter()
+                .map(|erased| (erased.table.clone(), erased.action, erased.ids.len()))
+                .collect(),
+        }
+    }
+
+    fn sign(&self) -> anyhow::Result<String> {
+        let key = EncodingKey::from_secret(jwt_secret()?.as_bytes());
+        Ok(encode(&Header::default(), &self.claims(), &key)?)
+    }
+
+    /// Whether the signature was issued for this receipt, and its row counts match
+    pub fn verify(&self) -> anyhow::Result<bool> {
+        let signature = match &self.signature {
+            Some(signature) => signature,
+            None => return Ok(false),
+        };
+        let secret = jwt_secret()?;
+        let validation = Validation {
+            validate_exp: false,
+            iss: Some(RECEIPT_ISSUER.to_string()),
+            ..Validation::default()
+        };
+        match decode::<ReceiptClaims>(signature, &DecodingKey::from_secret(secret.as_bytes()), &validation) {
+            Ok(data) => Ok(data.claims == self.claims()),
+            Err(_) => Ok(false),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn signed_receipts_verify_until_changed() {
+        std::env::set_var("JWT_SECRET", "erasure-test-secret");
+        let mut receipt = ErasureReceipt {
+            id: Id::gen(),
+            user_id: Id::gen(),
+            dry_run: false,
+            erased_at: now(),
+            rows: vec![Erased { table: "posts".into(), action: ErasureAction::Anonymized, ids: vec![Id::gen()] }],
+            signature: None,
+        };
+        assert!(!receipt.verify().unwrap());
+        receipt.signature = Some(receipt.sign().unwrap());
+        assert!(receipt.verify().unwrap());
+        receipt.rows[0].ids.push(Id::gen());
+        assert!(!receipt.verify().unwrap());
+    }
+}
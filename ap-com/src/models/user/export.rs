@@ -0,0 +1,366 @@
+//! Per-user data export. UserExport gathers everything belonging to a user through the
+//!     User::get_* helpers and writes it as a zip archive, with one NDJSON file per kind
+//!     of row plus a CSV copy of the tabular ones. Exports run in the background on the
+//!     UserExports system service, ex.
+//! ```ignore
+//! let job = UserExports::from_registry().send(StartExport { db: db.pool.clone(), user_id }).await?;
+//! // later
+//! let job = UserExports::from_registry().send(GetExport(job.id)).await?;
+//! ```
+//! Archives are written to a private directory under the system temp directory, readable
+//!     by the server's user only, and removed EXPORT_TTL_HOURS after they are ready
+use std::{
+    collections::HashMap,
+    fs::{self, File, OpenOptions},
+    io::{self, Seek, Write},
+    path::{Path, PathBuf},
+};
+#[cfg(unix)]
+use std::os::unix::fs::{DirBuilderExt, OpenOptionsExt, PermissionsExt};
+use actix::prelude::*;
+use actix_web::web;
+use chrono::{Duration, NaiveDateTime};
+use serde::{Serialize, Deserialize};
+use serde_json::Value;
+use sqlx::postgres::PgPool;
+use zip::{ZipWriter, CompressionMethod, write::FileOptions};
+use crate::{Id, Model, now};
+use crate::types::token::{Token, AccessToken, RefreshToken};
+use crate::models::{
+    User, Record, Item, Field, Post,
+    field::value::FieldValue,
+    topic::TopicVote,
+    messages::{DirectUserMessage, DirectGroupMessage, DirectTopicMessage},
+};
+use super::{Account, Profile, UserLevel, UserBadge};
+
+/// How long a finished export, and its archive, is kept
+pub const EXPORT_TTL_HOURS: i64 = 24;
+
+/// Replaces token values in exported accounts
+const REDACTED: &str = "[redacted]";
+
+/// Everything stored for one user. Credentials and sessions are left out, and account
+///     tokens are redacted
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct UserExport {
+    pub user: User,
+    pub profile: Option<Profile>,
+    pub accounts: Vec<Account>,
+    pub records: Vec<Record>,
+    pub items: Vec<Item>,
+    pub fields: Vec<Field>,
+    pub field_values: Vec<FieldValue>,
+    pub posts: Vec<Post>,
+    pub messages_sent: Vec<DirectUserMessage>,
+    pub messages_received: Vec<DirectUserMessage>,
+    pub group_messages: Vec<DirectGroupMessage>,
+    pub topic_messages: Vec<DirectTopicMessage>,
+    pub topic_votes: Vec<TopicVote>,
+    pub level: Option<UserLevel>,
+    pub badges: Vec<UserBadge>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportStatus {
+    Pending,
+    Ready,
+    Failed,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct ExportJob {
+    pub id: Id,
+    pub user_id: Id,
+    pub status: ExportStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub created_at: NaiveDateTime,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub finished_at: Option<NaiveDateTime>,
+    /// The archive, once status is Ready
+    #[serde(skip)]
+    pub path: Option<PathBuf>,
+}
+
+impl UserExport {
+
+    /// Gather the user's rows. None if there is no such user
+    pub async fn collect(db: &PgPool, user_id: Id) -> anyhow::Result<Option<Self>> {
+        let user = match User::get(db, user_id.clone()).await? {
+            Some(user) => user,
+            None => return Ok(None),
+        };
+        let profile = match User::get_profile(db, user_id.clone()).await {
+            Ok(profile) => Some(profile),
+            Err(e) if matches!(e.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::RowNotFound)) => None,
+            Err(e) => return Err(e),
+        };
+        let accounts = User::get_accounts(db, user_id.clone()).await?
+            .into_iter()
+            .map(redact)
+            .collect();
+        Ok(Some(Self {
+            user,
+            profile,
+            accounts,
+            records: User::get_records_created(db, user_id.clone()).await?,
+            items: User::get_items_created(db, user_id.clone()).await?,
+            fields: User::get_fields_created(db, user_id.clone()).await?,
+            field_values: User::get_field_values(db, user_id.clone()).await?,
+            posts: User::get_posts(db, user_id.clone()).await?,
+            messages_sent: User::get_messages_sent(db, user_id.clone()).await?,
+            messages_received: User::get_messages_received(db, user_id.clone()).await?,
+            group_messages: User::get_group_messages_sent(db, user_id.clone()).await?,
+            topic_messages: User::get_topic_messages_sent(db, user_id.clone()).await?,
+            topic_votes: User::get_topic_votes(db, user_id.clone()).await?,
+            level: User::get_level(db, user_id.clone()).await?,
+            badges: User::get_badges(db, user_id).await?,
+        }))
+    }
+
+    /// Files of the archive by name, as (name, rows, tabular). Tabular files also get a
+    ///     CSV copy
+    fn files(&self) -> anyhow::Result<Vec<(&'static str, Vec<Value>, bool)>> {
+        Ok(vec![
+            ("user", rows(Some(&self.user))?, false),
+            ("profile", rows(&self.profile)?, false),
+            ("accounts", rows(&self.accounts)?, true),
+            ("records", rows(&self.records)?, true),
+            ("items", rows(&self.items)?, true),
+            ("fields", rows(&self.fields)?, true),
+            ("field_values", rows(&self.field_values)?, true),
+            ("posts", rows(&self.posts)?, true),
+            ("messages_sent", rows(&self.messages_sent)?, true),
+            ("messages_received", rows(&self.messages_received)?, true),
+            ("group_messages", rows(&self.group_messages)?, true),
+            ("topic_messages", rows(&self.topic_messages)?, true),
+            ("topic_votes", rows(&self.topic_votes)?, true),
+            ("level", rows(&self.level)?, false),
+            ("badges", rows(&self.badges)?, true),
+        ])
+    }
+
+    /// Write the archive -- {name}.ndjson for every kind of row, and {name}.csv for the
+    ///     tabular ones
+    pub fn write_zip<W: Write + Seek>(&self, out: W) -> anyhow::Result<W> {
+        let mut zip = ZipWriter::new(out);
+        let opts = FileOptions::default().compression_method(CompressionMethod::Deflated);
+        for (name, rows, tabular) in self.files()? {
+            zip.start_file(format!("{}.ndjson", name), opts)?;
+            zip.write_all(&to_ndjson(&rows)?)?;
+            if tabular {
+                zip.start_file(format!("{}.csv", name), opts)?;
+                zip.write_all(&to_csv(&rows)?)?;
+            }
+        }
+        Ok(zip.finish()?)
+    }
+}
+
+fn redact(account: Account) -> Account {
+    Account {
+        access_token: account.access_token.map(|_| AccessToken::new(REDACTED.into())),
+        refresh_token: account.refresh_token.map(|_| RefreshToken::new(REDACTED.into())),
+        ..account
+    }
+}
+
+fn rows<'a, T: Serialize + 'a>(models: impl IntoIterator<Item = &'a T>) -> anyhow::Result<Vec<Value>> {
+    models.into_iter()
+        .map(|m| Ok(serde_json::to_value(m)?))
+        .collect()
+}
+
+fn to_ndjson(rows: &[Value]) -> anyhow::Result<Vec<u8>> {
+    let mut out = Vec::new();
+    for row in rows {
+        serde_json::to_writer(&mut out, row)?;
+        out.push(b'\n');
+    }
+    Ok(out)
+}
+
+/// One column per key found in any row, in order of appearance. Nested values are
+///     written as JSON
+fn to_csv(rows: &[Value]) -> anyhow::Result<Vec<u8>> {
+    let mut header: Vec<&str> = Vec::new();
+    for row in rows.iter().filter_map(Value::as_object) {
+        for key in row.keys() {
+            if !header.contains(&key.as_str()) {
+                header.push(key);
+            }
+        }
+    }
+    let mut out = csv::Writer::from_writer(Vec::new());
+    out.write_record(&header)?;
+    for row in rows {
+        let record = header.iter().map(|col| match row.get(*col) {
+            None | Some(Value::Null) => String::new(),
+            Some(Value::String(s)) => s.clone(),
+            Some(val) => val.to_string(),
+        });
+        out.write_record(record)?;
+    }
+    out.into_inner().map_err(|e| anyhow::anyhow!("Could not write CSV: {}", e))
+}
+
+/// Runs exports in the background and keeps track of them until they expire
+#[derive(Default)]
+pub struct UserExports {
+    jobs: HashMap<Id, ExportJob>,
+}
+
+/// Start exporting the user's data, returning the pending job
+pub struct StartExport {
+    pub db: PgPool,
+    pub user_id: Id,
+}
+
+/// The export job with this id, if it has not expired
+pub struct GetExport(pub Id);
+
+struct Finished {
+    id: Id,
+    res: Result<PathBuf, String>,
+}
+
+impl Message for StartExport {
+    type Result = ExportJob;
+}
+
+impl Message for GetExport {
+    type Result = Option<ExportJob>;
+}
+
+impl Message for Finished {
+    type Result = ();
+}
+
+impl UserExports {
+
+    fn expire(&mut self) {
+        let cutoff = now() - Duration::hours(EXPORT_TTL_HOURS);
+        self.jobs.retain(|_, job| {
+            let expired = job.finished_at.map_or(false, |at| at < cutoff);
+            if let (true, Some(path)) = (expired, &job.path) {
+                let _ = fs::remove_file(path);
+            }
+            !expired
+        });
+    }
+}
+
+impl Actor for UserExports {
+    type Context = Context<Self>;
+
+    fn started(&mut self, ctx: &mut Self::Context) {
+        ctx.run_interval(std::time::Duration::from_secs(60 * 60), |act, _| act.expire());
+    }
+}
+
+impl Supervised for UserExports {}
+
+impl SystemService for UserExports {}
+
+impl Handler<StartExport> for UserExports {
+    type Result = MessageResult<StartExport>;
+
+    fn handle(&mut self, msg: StartExport, ctx: &mut Self::Context) -> Self::Result {
+        let job = ExportJob {
+            id: Id::gen(),
+            user_id: msg.user_id.clone(),
+            status: ExportStatus::Pending,
+            error: None,
+            created_at: now(),
+            finished_at: None,
+            path: None,
+        };
+        self.jobs.insert(job.id.clone(), job.clone());
+        let (id, addr) = (job.id.clone(), ctx.address());
+        actix::spawn(async move {
+            let res = export(&msg.db, msg.user_id, &id).await.map_err(|e| e.to_string());
+            addr.do_send(Finished { id, res });
+        });
+        MessageResult(job)
+    }
+}
+
+impl Handler<GetExport> for UserExports {
+    type Result = MessageResult<GetExport>;
+
+    fn handle(&mut self, msg: GetExport, _: &mut Self::Context) -> Self::Result {
+        MessageResult(self.jobs.get(&msg.0).cloned())
+    }
+}
+
+impl Handler<Finished> for UserExports {
+    type Result = ();
+
+    fn handle(&mut self, msg: Finished, _: &mut Self::Context) {
+        if let Some(job) = self.jobs.get_mut(&msg.id) {
+            job.finished_at = Some(now());
+            match msg.res {
+                Ok(path) => {
+                    job.status = ExportStatus::Ready;
+                    job.path = Some(path);
+                },
+                Err(e) => {
+                    job.status = ExportStatus::Failed;
+                    job.error = Some(e);
+                },
+            }
+        }
+    }
+}
+
+async fn export(db: &PgPool, user_id: Id, job_id: &Id) -> anyhow::Result<PathBuf> {
+    let data = UserExport::collect(db, user_id.clone()).await?
+        .ok_or_else(|| anyhow::anyhow!("No user with id {}", user_id))?;
+    let path = export_dir()?.join(format!("user-export-{}.zip", job_id));
+    let res = web::block(move || {
+        data.write_zip(private_file(&path)?)?;
+        Ok::<_, anyhow::Error>(path)
+    }).await;
+    res.map_err(|e| anyhow::anyhow!("Could not write export: {}", e))?
+}
+
+/// The directory archives are written to, readable by this user only
+fn export_dir() -> io::Result<PathBuf> {
+    let dir = std::env::temp_dir().join("user-exports");
+    let mut builder = fs::DirBuilder::new();
+    builder.recursive(true);
+    #[cfg(unix)]
+    builder.mode(0o700);
+    builder.create(&dir)?;
+    // Fails unless the directory is ours, in case another user created it first
+    #[cfg(unix)]
+    fs::set_permissions(&dir, fs::Permissions::from_mode(0o700))?;
+    Ok(dir)
+}
+
+/// Create a new file readable and writable by this user only
+fn private_file(path: &Path) -> io::Result<File> {
+    let mut opts = OpenOptions::new();
+    opts.write(true).create_new(true);
+    #[cfg(unix)]
+    opts.mode(0o600);
+    opts.open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn writes_csv_columns_from_every_row() {
+        let rows = vec![
+            json!({ "id": "a", "name": "Sleep, naps" }),
+            json!({ "id": "b", "tags": ["x"], "name": null }),
+        ];
+        let out = String::from_utf8(to_csv(&rows).unwrap()).unwrap();
+        assert_eq!(out, "id,name,tags\na,\"Sleep, naps\",\nb,,\"[\"\"x\"\"]\"\n");
+    }
+}
@@ -1,6 +1,7 @@
 use uuid::Uuid;
 use actix::prelude::*;
 use crate::{Model, Id, Db};
+use crate::models::event;
 use crate::{
     types::now,
     models::user::UserBadge,
@@ -38,7 +39,8 @@ impl Model for UserLevel {
     fn id(self) -> Id { self.id }
     #[inline]
     fn table() -> String { String::from("user_levels") }
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "level", "exp", "created_at", "updated_at"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("INSERT INTO user_levels
             (id, user_id, level, exp, created_at, updated_at)
             VALUES ($1, $2, $3, $4, $5, $6) RETURNING *")
@@ -49,7 +51,7 @@ impl Model for UserLevel {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
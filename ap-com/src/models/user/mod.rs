@@ -9,6 +9,8 @@ pub mod link;
 pub mod mail;
 pub mod account;
 pub mod profile;
+pub mod export;
+pub mod erase;
 
 pub use self::{
     account::Account,
@@ -24,11 +26,15 @@ use serde::{Serialize, Deserialize};
 use actix::prelude::*;
 use rand::{distributions::{Uniform, Alphanumeric}, Rng, prelude::Distribution};
 use crate::{Id, Model, Db};
+use crate::models::event;
 use crate::{
     types::now,
+    query::Sql,
     models::{
-        Item, Record, Field, Group,
-        messages::user::DirectUserMessage,
+        Item, Record, Field, Group, Post,
+        field::value::FieldValue,
+        topic::TopicVote,
+        messages::{DirectUserMessage, DirectGroupMessage, DirectTopicMessage},
     }
 };
 use sqlx::{
@@ -134,11 +140,16 @@ impl UserIn {
 impl Model for User {
     #[inline]
     fn table() -> String { String::from("users") }
+    fn columns() -> Vec<&'static str> { vec!["id", "name", "email", "email_verified", "image", "created_at", "updated_at"] }
+    #[inline]
+    fn mutable_columns() -> Vec<&'static str> { vec!["name", "email", "email_verified", "image"] }
+    #[inline]
+    fn filterable_columns() -> Vec<&'static str> { vec!["name", "email", "image"] }
 
     #[inline]
     fn id(self) -> Id { self.id }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
         INSERT INTO users
         (id, name, email, email_verified, image, created_at, updated_at)
@@ -151,7 +162,7 @@ impl Model for User {
             .bind(&self.created_at)
             .bind(&self.updated_at)
             .fetch_one(db).await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -305,6 +316,57 @@ impl User {
         }
     }
 
+    pub async fn get_posts(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<Post>> {
+        let posts = Sql::<Post>::select()
+            .eq("user_id", user_id)
+            .fetch_all(db).await?;
+        Ok(posts)
+    }
+
+    /// Values recorded for this user's items, whoever created their fields
+    pub async fn get_field_values(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<FieldValue>> {
+        let values = Sql::<FieldValue>::select()
+            .join::<Item>("id", "item_id")
+            .eq_on::<Item, _>("user_id", user_id)
+            .fetch_all(db).await?;
+        Ok(values)
+    }
+
+    pub async fn get_messages_sent(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<DirectUserMessage>> {
+        let msgs = Sql::<DirectUserMessage>::select()
+            .eq("sender_id", user_id)
+            .fetch_all(db).await?;
+        Ok(msgs)
+    }
+
+    pub async fn get_messages_received(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<DirectUserMessage>> {
+        let msgs = Sql::<DirectUserMessage>::select()
+            .eq("recipient_id", user_id)
+            .fetch_all(db).await?;
+        Ok(msgs)
+    }
+
+    pub async fn get_group_messages_sent(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<DirectGroupMessage>> {
+        let msgs = Sql::<DirectGroupMessage>::select()
+            .eq("sender_id", user_id)
+            .fetch_all(db).await?;
+        Ok(msgs)
+    }
+
+    pub async fn get_topic_messages_sent(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<DirectTopicMessage>> {
+        let msgs = Sql::<DirectTopicMessage>::select()
+            .eq("sender_id", user_id)
+            .fetch_all(db).await?;
+        Ok(msgs)
+    }
+
+    pub async fn get_topic_votes(db: &PgPool, user_id: Id) -> anyhow::Result<Vec<TopicVote>> {
+        let votes = Sql::<TopicVote>::select()
+            .eq("user_id", user_id)
+            .fetch_all(db).await?;
+        Ok(votes)
+    }
+
     // pub async fn get_dms_as_recipient(self, db: &PgPool, user_id: Id) -> anyhow::Result<Vec<DirectUserMessage>> {
     //     let level = UserLevel::get_by_user_id(&db, user_id).await?;
     //     if let Some(level) = level {
@@ -388,6 +450,25 @@ impl ModelRoutes for User {
 
     }
 
+    /// Accounts are left out -- they hold OAuth tokens, and list routes are public
+    fn includes() -> Vec<&'static str> {
+        vec!["profile", "level", "badges"]
+    }
+
+    async fn include(db: &PgPool, id: Id, name: &str) -> anyhow::Result<serde_json::Value> {
+        let res = match name {
+            "profile" => match Self::get_profile(db, id).await {
+                Ok(profile) => serde_json::to_value(profile)?,
+                Err(e) if matches!(e.downcast_ref::<sqlx::Error>(), Some(sqlx::Error::RowNotFound)) => serde_json::Value::Null,
+                Err(e) => return Err(e),
+            },
+            "level" => serde_json::to_value(Self::get_level(db, id).await?)?,
+            "badges" => serde_json::to_value(Self::get_badges(db, id).await?)?,
+            _ => return Err(anyhow::anyhow!("Cannot include {}", name)),
+        };
+        Ok(res)
+    }
+
 }
 
 
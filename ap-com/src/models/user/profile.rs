@@ -1,4 +1,5 @@
 use crate::{Gender, Role, now, GroupRole};
+use crate::models::event;
 use crate::{Db, Id, models::routes::ModelRoutes};
 use chrono::NaiveDate;
 use super::Model;
@@ -62,8 +63,20 @@ pub struct Profile {
 impl Model for Profile {
 
     fn table() -> String { String::from("profiles") }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "bio", "role", "cover_image", "referral", "phone_number", "company", "postal_code", "state", "country", "city", "website", "occupation", "facebook_url", "linkedin_url", "twitter_url", "education", "gender", "birthday", "created_at", "updated_at"] }
+    fn mutable_columns() -> Vec<&'static str> {
+        vec![
+            "bio", "role", "cover_image", "referral", "phone_number", "company",
+            "postal_code", "state", "country", "city", "website", "occupation",
+            "facebook_url", "linkedin_url", "twitter_url", "education", "gender",
+            "birthday"
+        ]
+    }
+    fn filterable_columns() -> Vec<&'static str> {
+        vec!["user_id", "role", "company", "state", "country", "city", "occupation", "gender"]
+    }
 
-    async fn insert(self, db: &PgPool) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
            INSERT INTO profiles (
             id,
@@ -112,7 +125,7 @@ impl Model for Profile {
             .bind(&self.postal_code)
             .bind(&self.phone_number)
             .fetch_one(db).await?;
-        Ok(self)
+        Ok(event::created(self))
     }
 }
 #[async_trait::async_trait]
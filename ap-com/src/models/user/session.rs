@@ -1,4 +1,5 @@
 use crate::{Model, Id, models::ModelRoutes};
+use crate::models::event;
 use actix_web::web::ServiceConfig;
 use chrono::Duration;
 use crate::{token::{AccessToken, SessionToken, Token}, Expiration, Status, now, private};
@@ -26,9 +27,10 @@ pub struct Session {
 #[async_trait::async_trait]
 impl Model for Session {
     fn table() -> String { "sessions".to_string() }
+    fn columns() -> Vec<&'static str> { vec!["id", "user_id", "expires", "session_token", "access_token", "created_at", "updated_at"] }
+    fn filterable_columns() -> Vec<&'static str> { vec!["user_id"] }
 
-    async fn insert(
-        self, db: &PgPool,) -> sqlx::Result<Self> {
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO session (id, user_id, session_token, access_token, expires)
             VALUES ($1, $2, $3, $4, $5) RETURNING *")
@@ -39,7 +41,7 @@ impl Model for Session {
             .bind(self.expires)
             .fetch_one(db)
             .await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 
 }
@@ -1,6 +1,7 @@
 use actix::prelude::*;
 use actix_web::{guard::Post, Responder};
 use crate::{Id, Status, now, private};
+use crate::models::event;
 use sqlx::{FromRow, Postgres, postgres::PgPool, types::chrono::{NaiveDateTime, Utc}};
 use serde::{Serialize, Deserialize};
 use uuid::Uuid;
@@ -32,7 +33,8 @@ impl Default for VerificationRequest {
 #[async_trait::async_trait]
 impl super::Model for VerificationRequest {
     fn table() -> String { String::from("verification_requests") }
-    async fn insert(self, db: &PgPool,) -> sqlx::Result<Self> {
+    fn columns() -> Vec<&'static str> { vec!["id", "identifier", "token", "expires", "created_at", "updated_at"] }
+    async fn insert<'e, E: sqlx::PgExecutor<'e>>(self, db: E) -> sqlx::Result<Self> {
         let res = sqlx::query_as::<Postgres, Self>("
             INSERT INTO verification_requests (id, identifier, token, expires)
             VALUES ($1, $2, $3, $4) RETURNING *")
@@ -42,7 +44,7 @@ impl super::Model for VerificationRequest {
             .bind(self.expires)
             .fetch_one(db)
             .await?;
-        Ok(res)
+        Ok(event::created(res))
     }
 }
 
@@ -0,0 +1,75 @@
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Sparse fieldsets and embedded relations requested on GET routes, ex.
+///     ?fields=id,name,email&include=profile,accounts
+/// Both take comma separated lists and may be repeated
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct Projection {
+    /// Fields to keep in each row. None keeps every field
+    pub fields: Option<Vec<String>>,
+    /// Related entities to embed in each row, keyed by name -- see ModelRoutes::includes()
+    pub include: Vec<String>,
+}
+
+impl Projection {
+
+    pub fn parse(params: &[(String, String)]) -> Self {
+        let mut out = Self::default();
+        for (key, val) in params.iter() {
+            let list = val.split(',')
+                .map(|s| s.trim().to_string())
+                .filter(|s| !s.is_empty());
+            match key.as_str() {
+                "fields" => out.fields.get_or_insert_with(Vec::new).extend(list),
+                "include" => out.include.extend(list),
+                _ => {},
+            }
+        }
+        out
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_none() && self.include.is_empty()
+    }
+
+    /// The first requested include which is not in the whitelist, if any
+    pub fn unknown_include(&self, includes: &[&str]) -> Option<&str> {
+        self.include.iter()
+            .find(|name| !includes.contains(&name.as_str()))
+            .map(|name| name.as_str())
+    }
+
+    /// Serialize a row, keeping only the requested fields. The id is always kept so
+    ///     projected rows can still be addressed
+    pub fn project<T: Serialize>(&self, row: &T) -> serde_json::Result<Value> {
+        let value = serde_json::to_value(row)?;
+        let (fields, obj) = match (&self.fields, value) {
+            (Some(fields), Value::Object(obj)) => (fields, obj),
+            (_, value) => return Ok(value),
+        };
+        Ok(Value::Object(obj.into_iter()
+            .filter(|(key, _)| key == "id" || fields.contains(key))
+            .collect::<Map<String, Value>>()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_requested_fields_and_id() {
+        let proj = Projection::parse(&[
+            ("fields".to_string(), "name, email".to_string()),
+            ("include".to_string(), "profile,accounts".to_string()),
+            ("limit".to_string(), "10".to_string()),
+        ]);
+        assert_eq!(proj.include, vec!["profile", "accounts"]);
+        assert_eq!(proj.unknown_include(&["profile"]), Some("accounts"));
+        let row = json!({ "id": "a", "name": "b", "email": "c", "image": "d" });
+        assert_eq!(proj.project(&row).unwrap(), json!({ "id": "a", "name": "b", "email": "c" }));
+        assert_eq!(Projection::default().project(&row).unwrap(), row);
+    }
+}
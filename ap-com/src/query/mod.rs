@@ -1,18 +1,359 @@
 pub mod datetime;
+pub mod page;
+pub mod fields;
+pub mod sql;
 
-use sqlx::{FromRow, Postgres};
+pub use page::{Cursor, Page, PageQuery};
+pub use datetime::{DateFilter, RelativeDate};
+pub use fields::Projection;
+pub use sql::{Ident, Sql};
 
-use crate::models::Model;
+use std::marker::PhantomData;
+use chrono::{NaiveDate, NaiveDateTime};
+use derive_more::Display;
+use sqlx::{prelude::*, postgres::PgExecutor};
+use crate::{Id, models::Model};
 
+/// Query string keys which are consumed by pagination or projection rather than
+///     treated as filters
+pub const PAGE_PARAMS: [&str; 6] = ["limit", "after", "before", "sort", "fields", "include"];
+
+/// A single condition on a column. Equality and text matching compare the column's
+///     text representation, so they apply equally to text, enum, bool and id columns
+#[derive(Debug, Clone, PartialEq)]
+pub enum Filter {
+    Eq(String, String),
+    Ne(String, String),
+    Contains(String, String),
+    IsNull(String, bool),
+    Date(String, DateFilter),
+}
+
+#[derive(Display, Debug, Clone, PartialEq)]
+pub enum FilterError {
+    #[display(fmt = "Cannot filter by {}", _0)]
+    UnknownColumn(String),
+    #[display(fmt = "Invalid date {} for {}", _1, _0)]
+    InvalidDate(String, String),
+}
+
+/// A typed set of filters against a model's table, which compiles to parameterized SQL.
+///     Columns are checked against the model's filterable_columns() (and sortable_columns()
+///     for date filters) before any SQL is built, ex.
+/// ```ignore
+/// let active = Query::<Record>::new()
+///     .eq("status", "active")
+///     .after("created_at", datetime)
+///     .fetch_all(&db.pool).await?;
+/// ```
+#[derive(Debug, Clone, PartialEq)]
 pub struct Query<T: Model> {
-    table: T
+    filters: Vec<Filter>,
+    with_deleted: bool,
+    model: PhantomData<fn() -> T>,
 }
 
-/* pub struct Query {
+impl<T: Model> Default for Query<T> {
+    fn default() -> Self {
+        Self { filters: Vec::new(), with_deleted: false, model: PhantomData }
+    }
+}
+
+impl Filter {
+
+    /// The column this filter applies to
+    pub fn col(&self) -> &str {
+        match self {
+            Filter::Eq(col, _) | Filter::Ne(col, _) | Filter::Contains(col, _)
+                | Filter::IsNull(col, _) | Filter::Date(col, _) => col,
+        }
+    }
+}
+
+impl<T: Model> Query<T> {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn filter(mut self, filter: Filter) -> Self {
+        self.filters.push(filter);
+        self
+    }
+
+    pub fn eq<V: ToString>(self, col: &str, val: V) -> Self {
+        self.filter(Filter::Eq(col.to_string(), val.to_string()))
+    }
+
+    pub fn ne<V: ToString>(self, col: &str, val: V) -> Self {
+        self.filter(Filter::Ne(col.to_string(), val.to_string()))
+    }
+
+    /// Case insensitive substring match
+    pub fn contains(self, col: &str, val: &str) -> Self {
+        self.filter(Filter::Contains(col.to_string(), val.to_string()))
+    }
+
+    pub fn is_null(self, col: &str, null: bool) -> Self {
+        self.filter(Filter::IsNull(col.to_string(), null))
+    }
+
+    pub fn date(self, col: &str, date: DateFilter) -> Self {
+        self.filter(Filter::Date(col.to_string(), date))
+    }
+
+    pub fn after(self, col: &str, datetime: NaiveDateTime) -> Self {
+        self.date(col, DateFilter { not: false, rel_date: RelativeDate::After(datetime) })
+    }
+
+    pub fn before(self, col: &str, datetime: NaiveDateTime) -> Self {
+        self.date(col, DateFilter { not: false, rel_date: RelativeDate::Before(datetime) })
+    }
+
+    pub fn between(self, col: &str, from: NaiveDateTime, to: NaiveDateTime) -> Self {
+        self.date(col, DateFilter { not: false, rel_date: RelativeDate::Between(from, to) })
+    }
+
+    /// Include soft deleted rows, which are otherwise skipped whatever the filters. Routes
+    ///     only set this for admins
+    pub fn with_deleted(mut self) -> Self {
+        self.with_deleted = true;
+        self
+    }
+
+    pub fn filters(&self) -> &[Filter] {
+        &self.filters
+    }
+
+    /// Whether any filter applies to col
+    pub fn filters_on(&self, col: &str) -> bool {
+        self.filters.iter().any(|f| f.col() == col)
+    }
+
+    /// Parse filters from query string pairs, skipping pagination params. Supported forms:
+    ///     col=val, col!=val, col~=val (contains), col=null, col=not:val, col=not:null
+    ///     col=after:{date}, col=before:{date}, col=on:{date}, col=between:{date},{date}
+    ///     where dates are either 2026-01-01 or 2026-01-01T12:00:00
+    pub fn parse(params: &[(String, String)]) -> Result<Self, FilterError> {
+        let mut query = Self::new();
+        for (key, val) in params.iter().filter(|(key, _)| !PAGE_PARAMS.contains(&key.as_str())) {
+            let filter = if let Some(col) = key.strip_suffix('~') {
+                Filter::Contains(col.to_string(), val.to_string())
+            } else if let Some(col) = key.strip_suffix('!') {
+                Filter::Ne(col.to_string(), val.to_string())
+            } else {
+                Self::parse_value(key, val)?
+            };
+            query = query.filter(filter);
+        }
+        query.validate()?;
+        Ok(query)
+    }
+
+    fn parse_value(col: &str, val: &str) -> Result<Filter, FilterError> {
+        let (not, val) = match val.strip_prefix("not:") {
+            Some(val) => (true, val),
+            None => (false, val),
+        };
+        let date = |s: &str| parse_date(s)
+            .ok_or_else(|| FilterError::InvalidDate(col.to_string(), s.to_string()));
+        let rel_date = if let Some(d) = val.strip_prefix("after:") {
+            RelativeDate::After(date(d)?)
+        } else if let Some(d) = val.strip_prefix("before:") {
+            RelativeDate::Before(date(d)?)
+        } else if let Some(d) = val.strip_prefix("on:") {
+            RelativeDate::DayOf(date(d)?)
+        } else if let Some(d) = val.strip_prefix("between:") {
+            let mut range = d.splitn(2, ',');
+            match (range.next(), range.next()) {
+                (Some(from), Some(to)) => RelativeDate::Between(date(from)?, date(to)?),
+                _ => return Err(FilterError::InvalidDate(col.to_string(), d.to_string())),
+            }
+        } else if val == "null" {
+            return Ok(Filter::IsNull(col.to_string(), !not));
+        } else if not {
+            return Ok(Filter::Ne(col.to_string(), val.to_string()));
+        } else {
+            return Ok(Filter::Eq(col.to_string(), val.to_string()));
+        };
+        Ok(Filter::Date(col.to_string(), DateFilter { not, rel_date }))
+    }
+
+    /// Ensure every filtered column is whitelisted by the model
+    pub fn validate(&self) -> Result<(), FilterError> {
+        let (filterable, sortable) = (T::filterable_columns(), T::sortable_columns());
+        for filter in self.filters.iter() {
+            let (col, allowed) = match filter {
+                Filter::Date(col, _) => (col, &sortable),
+                Filter::Eq(col, _) | Filter::Ne(col, _)
+                    | Filter::Contains(col, _) | Filter::IsNull(col, _) => (col, &filterable),
+            };
+            if !allowed.contains(&col.as_str()) {
+                return Err(FilterError::UnknownColumn(col.to_string()));
+            }
+        }
+        Ok(())
+    }
+
+    /// A select of the matching rows, skipping soft deleted ones unless with_deleted()
+    pub fn sql(&self) -> Sql<T> {
+        let sql = self.filters.iter().fold(Sql::select(), |sql, filter| match filter {
+            Filter::Eq(col, val) => sql.eq_text(col, val),
+            Filter::Ne(col, val) => sql.ne_text(col, val),
+            Filter::Contains(col, val) => sql.contains(col, val),
+            Filter::IsNull(col, null) => sql.is_null(col, *null),
+            Filter::Date(col, date) => sql.date(col, date),
+        });
+        if self.with_deleted { sql } else { sql.live() }
+    }
+
+    /// Fetch every row matching the filters
+    pub async fn fetch_all<'e, E: PgExecutor<'e>>(&self, db: E) -> sqlx::Result<Vec<T>> {
+        self.validate().map_err(into_sqlx)?;
+        self.sql().fetch_all(db).await
+    }
+
+    /// Fetch one page of rows matching the filters, using keyset pagination on
+    ///     (sort column, id)
+    pub async fn fetch_page<'e, E: PgExecutor<'e>>(&self, db: E, page: &PageQuery) -> sqlx::Result<Page<T>> {
+        self.validate().map_err(into_sqlx)?;
+        let sortable = T::sortable_columns();
+        let (col, order) = page.sort_by(&sortable)
+            .ok_or_else(|| sqlx::Error::ColumnNotFound(page.sort.clone().unwrap_or_default()))?;
+        let limit = page.limit();
+        // Paging backwards runs the query in reverse, then flips the rows back
+        let (cursor, scan) = match (&page.after, &page.before) {
+            (Some(after), _) => (Some(after), order),
+            (None, Some(before)) => (Some(before), order.reverse()),
+            (None, None) => (None, order),
+        };
+        let mut sql = self.sql();
+        if let Some(cursor) = cursor {
+            sql = sql.past(col, scan, cursor);
+        }
+        let rows = sql.order_by(col, scan)
+            .order_by("id", scan)
+            .limit(limit + 1)
+            .fetch_rows(db).await?;
+        let has_more = rows.len() as i64 > limit;
+        let mut keyed = Vec::with_capacity(rows.len());
+        for row in rows.iter().take(limit as usize) {
+            let key = Cursor::new(row.try_get::<NaiveDateTime, _>(col)?, row.try_get::<Id, _>("id")?);
+            keyed.push((key, T::from_row(row)?));
+        }
+        if page.after.is_none() && page.before.is_some() {
+            keyed.reverse();
+        }
+        let first = keyed.first().map(|(key, _)| key.clone());
+        let last = keyed.last().map(|(key, _)| key.clone());
+        let (next_cursor, prev_cursor) = match (&page.after, &page.before) {
+            (None, Some(_)) => (last, if has_more { first } else { None }),
+            (Some(_), _) => (if has_more { last } else { None }, first),
+            (None, None) => (if has_more { last } else { None }, None),
+        };
+        Ok(Page {
+            data: keyed.into_iter().map(|(_, model)| model).collect(),
+            next_cursor,
+            prev_cursor,
+        })
+    }
+}
+
+/// Parse a date or datetime as accepted in filters, ex. 2026-01-01 or 2026-01-01T12:00:00
+pub fn parse_date(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+        .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+        .ok()
+        .or_else(|| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok().map(|d| d.and_hms(0, 0, 0)))
+}
+
+/// Filter errors surfaced from sqlx-returning methods. Unknown columns map to
+///     ColumnNotFound, matching the sort whitelist in fetch_page()
+pub(crate) fn into_sqlx(err: FilterError) -> sqlx::Error {
+    match err {
+        FilterError::UnknownColumn(col) => sqlx::Error::ColumnNotFound(col),
+        err => sqlx::Error::Decode(Box::new(err)),
+    }
+}
+
+impl std::error::Error for FilterError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sql::Arg;
+    use crate::models::Record;
+
+    fn params(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs.iter().map(|(k, v)| (k.to_string(), v.to_string())).collect()
+    }
+
+    #[test]
+    fn parses_filters_and_skips_page_params() {
+        let query = Query::<Record>::parse(&params(&[
+            ("status", "active"), ("name~", "foo"), ("limit", "10"), ("fields", "name"),
+            ("created_at", "after:2026-01-01"), ("description", "not:null"),
+        ])).unwrap();
+        assert_eq!(query.filters(), &[
+            Filter::Eq("status".into(), "active".into()),
+            Filter::Contains("name".into(), "foo".into()),
+            Filter::Date("created_at".into(), DateFilter {
+                not: false,
+                rel_date: RelativeDate::After(NaiveDate::from_ymd(2026, 1, 1).and_hms(0, 0, 0)),
+            }),
+            Filter::IsNull("description".into(), false),
+        ]);
+    }
+
+    #[test]
+    fn rejects_columns_outside_whitelist() {
+        let err = Query::<Record>::parse(&params(&[("password", "x")])).unwrap_err();
+        assert_eq!(err, FilterError::UnknownColumn("password".into()));
+        let err = Query::<Record>::parse(&params(&[("name", "after:2026-01-01")])).unwrap_err();
+        assert_eq!(err, FilterError::UnknownColumn("name".into()));
+    }
+
+    #[test]
+    fn compiles_numbered_placeholders() {
+        let (sql, args) = Query::<Record>::new()
+            .eq("status", "active")
+            .contains("name", "50%")
+            .with_deleted()
+            .sql().build().unwrap();
+        assert_eq!(sql, "SELECT \"records\".* FROM \"records\" \
+            WHERE \"records\".\"status\"::text = $1 AND \"records\".\"name\"::text ILIKE $2");
+        assert_eq!(args[1], Arg::Text("%50\\%%".into()));
+    }
+
+    #[test]
+    fn skips_soft_deleted_rows() {
+        let conds = |query: Query<Record>| {
+            let (sql, _) = query.sql().build().unwrap();
+            sql.splitn(2, " WHERE ").nth(1).unwrap_or_default().to_string()
+        };
+        assert_eq!(conds(Query::new().contains("name", "a")),
+            "\"records\".\"name\"::text ILIKE $1 AND \"records\".\"status\" <> 'deleted'");
+        assert_eq!(conds(Query::new().with_deleted()), "");
+        assert_eq!(conds(Query::new().ne("status", "archived")),
+            "\"records\".\"status\"::text IS DISTINCT FROM $1 AND \"records\".\"status\" <> 'deleted'");
+        assert_eq!(conds(Query::new().eq("status", "deleted")),
+            "\"records\".\"status\"::text = $1 AND \"records\".\"status\" <> 'deleted'");
+        assert_eq!(conds(Query::new().eq("status", "deleted").with_deleted()),
+            "\"records\".\"status\"::text = $1");
+    }
 
+    #[test]
+    fn pages_past_the_cursor() {
+        let cursor = Cursor::new(NaiveDate::from_ymd(2026, 1, 1).and_hms(0, 0, 0), Id::nil());
+        let (sql, args) = Query::<Record>::new()
+            .sql()
+            .past("created_at", page::SortOrder::Desc, &cursor)
+            .order_by("created_at", page::SortOrder::Desc)
+            .build().unwrap();
+        assert_eq!(sql, "SELECT \"records\".* FROM \"records\" \
+            WHERE \"records\".\"status\" <> 'deleted' \
+            AND (\"records\".\"created_at\", \"records\".\"id\") < ($1, $2) \
+            ORDER BY \"records\".\"created_at\" DESC");
+        assert_eq!(args[1], Arg::Id(Id::nil()));
+    }
 }
- */
-/* pub fn get_all<'r, T: Model + FromRow + 'static>(model: T) -> sqlx::query::QueryScalar<'r, Postgres, T> {
-    let q_str = format!("SELECT * FROM {}", T::table());
-    sqlx::query_as::<Postgres, T>(&q_str)
-} */
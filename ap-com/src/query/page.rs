@@ -0,0 +1,190 @@
+use std::{fmt, str::FromStr, convert::TryFrom};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use crate::Id;
+
+pub const DEFAULT_LIMIT: i64 = 50;
+pub const MAX_LIMIT: i64 = 500;
+
+/// Query string accepted by paginated list endpoints, ex.
+///     ?limit=20&sort=-created_at&after=1767225600000000_{id}
+#[derive(Default, Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PageQuery {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub limit: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub after: Option<Cursor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub before: Option<Cursor>,
+    /// Column to sort by, prefixed with '-' for descending order. Must be one
+    ///     of the model's sortable_columns()
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+}
+
+/// Position of a row in a keyset ordering -- the sort column's timestamp plus the
+///     row id as a tiebreaker. Serialized as "{unix micros}_{id}"
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "String", into = "String")]
+pub struct Cursor {
+    pub at: NaiveDateTime,
+    pub id: Id,
+}
+
+/// A single page of rows, along with cursors for the adjacent pages if they exist
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Page<T> {
+    pub data: Vec<T>,
+    pub next_cursor: Option<Cursor>,
+    pub prev_cursor: Option<Cursor>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub enum SortOrder {
+    Asc, Desc,
+}
+
+impl PageQuery {
+
+    pub fn new(limit: i64) -> Self {
+        Self { limit: Some(limit), ..Default::default() }
+    }
+
+    pub fn after(self, cursor: Cursor) -> Self {
+        Self { after: Some(cursor), before: None, ..self }
+    }
+
+    pub fn before(self, cursor: Cursor) -> Self {
+        Self { before: Some(cursor), after: None, ..self }
+    }
+
+    pub fn sort(self, sort: &str) -> Self {
+        Self { sort: Some(sort.to_string()), ..self }
+    }
+
+    /// The requested limit, clamped to 1..=MAX_LIMIT
+    pub fn limit(&self) -> i64 {
+        self.limit.unwrap_or(DEFAULT_LIMIT).max(1).min(MAX_LIMIT)
+    }
+
+    /// Resolve the sort column and order against a whitelist of sortable columns,
+    ///     the first of which is the default (ascending). Returns None for columns
+    ///     which are not in the whitelist
+    pub fn sort_by<'a>(&self, sortable: &[&'a str]) -> Option<(&'a str, SortOrder)> {
+        let (col, order) = match self.sort.as_deref() {
+            None => return sortable.first().map(|c| (*c, SortOrder::Asc)),
+            Some(s) if s.starts_with('-') => (&s[1..], SortOrder::Desc),
+            Some(s) => (s.trim_start_matches('+'), SortOrder::Asc),
+        };
+        sortable.iter()
+            .find(|c| **c == col)
+            .map(|c| (*c, order))
+    }
+}
+
+impl SortOrder {
+
+    pub fn reverse(self) -> Self {
+        match self {
+            SortOrder::Asc => SortOrder::Desc,
+            SortOrder::Desc => SortOrder::Asc,
+        }
+    }
+
+    pub fn sql(self) -> &'static str {
+        match self {
+            SortOrder::Asc => "ASC",
+            SortOrder::Desc => "DESC",
+        }
+    }
+
+    /// Comparison operator selecting rows which come after a cursor in this order
+    pub fn after_op(self) -> &'static str {
+        match self {
+            SortOrder::Asc => ">",
+            SortOrder::Desc => "<",
+        }
+    }
+}
+
+impl Cursor {
+    pub fn new(at: NaiveDateTime, id: Id) -> Self {
+        Self { at, id }
+    }
+}
+
+impl<T> Page<T> {
+
+    pub fn empty() -> Self {
+        Self { data: Vec::new(), next_cursor: None, prev_cursor: None }
+    }
+
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> Page<U> {
+        Page {
+            data: self.data.into_iter().map(f).collect(),
+            next_cursor: self.next_cursor,
+            prev_cursor: self.prev_cursor,
+        }
+    }
+}
+
+impl fmt::Display for Cursor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let micros = self.at.timestamp() * 1_000_000 + self.at.timestamp_subsec_micros() as i64;
+        write!(f, "{}_{}", micros, self.id)
+    }
+}
+
+impl FromStr for Cursor {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.splitn(2, '_');
+        let (micros, id) = match (parts.next(), parts.next()) {
+            (Some(micros), Some(id)) => (micros, id),
+            _ => return Err(format!("Invalid cursor {}", s)),
+        };
+        let micros: i64 = micros.parse()
+            .map_err(|_| format!("Invalid cursor timestamp {}", micros))?;
+        let at = NaiveDateTime::from_timestamp_opt(
+            micros.div_euclid(1_000_000),
+            (micros.rem_euclid(1_000_000) * 1_000) as u32,
+        ).ok_or_else(|| format!("Invalid cursor timestamp {}", micros))?;
+        let id = Id::try_from(id.to_string())
+            .map_err(|_| format!("Invalid cursor id {}", id))?;
+        Ok(Self { at, id })
+    }
+}
+
+impl TryFrom<String> for Cursor {
+    type Error = String;
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<Cursor> for String {
+    fn from(cursor: Cursor) -> String {
+        cursor.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cursor_round_trips() {
+        let at = NaiveDateTime::from_timestamp(1_767_225_600, 123_456_000);
+        let cursor = Cursor::new(at, Id::gen());
+        let parsed: Cursor = cursor.to_string().parse().unwrap();
+        assert_eq!(cursor, parsed);
+    }
+
+    #[test]
+    fn sort_is_whitelisted() {
+        let sortable = ["created_at", "updated_at"];
+        assert_eq!(PageQuery::default().sort_by(&sortable), Some(("created_at", SortOrder::Asc)));
+        assert_eq!(PageQuery::default().sort("-updated_at").sort_by(&sortable), Some(("updated_at", SortOrder::Desc)));
+        assert_eq!(PageQuery::default().sort("password").sort_by(&sortable), None);
+    }
+}
@@ -0,0 +1,546 @@
+//! Identifier-safe SQL for the generic Model, Linked, Db and filter queries. Table and column names
+//!     cannot be bound as parameters, so they are checked against the model's columns()
+//!     and quoted before being written into the statement, while values are always bound, ex.
+//! ```ignore
+//! let records = Sql::<Record>::select()
+//!     .eq("user_id", user_id)
+//!     .live()
+//!     .fetch_all(&db.pool).await?;
+//! ```
+//! An unknown column fails the query with ColumnNotFound before it reaches the database
+use std::{fmt, marker::PhantomData};
+use chrono::{Duration, NaiveDateTime};
+use serde_json::{Map, Value};
+use sqlx::{
+    prelude::*, Postgres,
+    postgres::{PgArguments, PgExecutor, PgQueryResult, PgRow},
+    query::Query as SqlQuery,
+};
+use crate::{Id, Status, models::{Model, bulk::db_labels}};
+use super::{Cursor, DateFilter, RelativeDate, page::SortOrder};
+
+/// A validated, double quoted SQL identifier
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ident(String);
+
+/// A value bound to a placeholder
+#[derive(Debug, Clone, PartialEq)]
+pub enum Arg {
+    Text(String),
+    Time(NaiveDateTime),
+    Id(Id),
+    OptId(Option<Id>),
+    Status(Status),
+    Bool(bool),
+    Json(Value),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Stmt {
+    Select,
+    Insert,
+    Update,
+    Delete,
+}
+
+/// A statement against M's table. Conditions are joined with AND and qualified with the
+///     table name, so they stay unambiguous in joins and patches
+#[derive(Debug, Clone, PartialEq)]
+pub struct Sql<M: Model> {
+    stmt: Stmt,
+    joins: Vec<String>,
+    sets: Vec<(Ident, String)>,
+    from: Option<String>,
+    conds: Vec<String>,
+    args: Vec<Arg>,
+    order: Vec<String>,
+    limit: Option<i64>,
+    lock: bool,
+    /// The first identifier which failed validation
+    invalid: Option<String>,
+    model: PhantomData<fn() -> M>,
+}
+
+impl Ident {
+
+    /// Quote a name made of lowercase ascii letters, digits and underscores. Anything
+    ///     else is rejected rather than escaped
+    pub fn new(name: &str) -> Option<Self> {
+        let mut chars = name.chars();
+        let valid = matches!(chars.next(), Some(c) if c.is_ascii_lowercase() || c == '_')
+            && chars.all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_');
+        if valid { Some(Self(format!("\"{}\"", name))) } else { None }
+    }
+
+    pub fn table<M: Model>() -> sqlx::Result<Self> {
+        let table = M::table();
+        Self::new(&table).ok_or_else(|| sqlx::Error::Protocol(format!("Invalid table name {}", table)))
+    }
+
+    /// A column of M -- must be listed in M::columns()
+    pub fn column<M: Model>(name: &str) -> sqlx::Result<Self> {
+        if !M::columns().contains(&name) {
+            return Err(sqlx::Error::ColumnNotFound(name.to_string()));
+        }
+        Self::new(name).ok_or_else(|| sqlx::Error::ColumnNotFound(name.to_string()))
+    }
+}
+
+impl fmt::Display for Ident {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl<M: Model> Sql<M> {
+
+    fn new(stmt: Stmt) -> Self {
+        Self {
+            stmt,
+            joins: Vec::new(),
+            sets: Vec::new(),
+            from: None,
+            conds: Vec::new(),
+            args: Vec::new(),
+            order: Vec::new(),
+            limit: None,
+            lock: false,
+            invalid: None,
+            model: PhantomData,
+        }
+    }
+
+    pub fn select() -> Self {
+        Self::new(Stmt::Select)
+    }
+
+    pub fn insert() -> Self {
+        Self::new(Stmt::Insert)
+    }
+
+    pub fn update() -> Self {
+        Self::new(Stmt::Update)
+    }
+
+    /// Delete matching rows -- for soft_delete() models, mark live rows deleted instead
+    pub fn delete() -> Self {
+        if M::soft_delete() {
+            Self::update().set("status", Status::Deleted).touch().live()
+        } else {
+            Self::new(Stmt::Delete)
+        }
+    }
+
+    /// Delete matching rows for good, soft_delete() or not
+    pub fn purge() -> Self {
+        Self::new(Stmt::Delete)
+    }
+
+    /// Placeholder for the next bound value
+    fn push(&mut self, arg: Arg) -> String {
+        self.args.push(arg);
+        format!("${}", self.args.len())
+    }
+
+    fn column<T: Model>(&mut self, name: &str) -> Option<String> {
+        match (Ident::column::<T>(name), Ident::table::<T>()) {
+            (Ok(col), Ok(table)) => Some(format!("{}.{}", table, col)),
+            _ => {
+                self.invalid.get_or_insert_with(|| name.to_string());
+                None
+            }
+        }
+    }
+
+    /// Set a column, for inserts and updates
+    pub fn set<A: Into<Arg>>(mut self, col: &str, val: A) -> Self {
+        match Ident::column::<M>(col) {
+            Ok(ident) => {
+                let param = self.push(val.into());
+                self.sets.push((ident, param));
+            },
+            Err(_) => { self.invalid.get_or_insert_with(|| col.to_string()); },
+        }
+        self
+    }
+
+    /// Set updated_at to now, for models which have one
+    pub fn touch(self) -> Self {
+        if M::columns().contains(&"updated_at") {
+            self.set("updated_at", crate::now())
+        } else {
+            self
+        }
+    }
+
+    /// Set the columns of a JSON merge-patch, read through jsonb_populate_record so each
+    ///     value is cast to its column's type, enums from their variant names (see bulk::db_labels)
+    pub fn patch(mut self, patch: Map<String, Value>) -> Self {
+        let table = match Ident::table::<M>() {
+            Ok(table) => table,
+            Err(_) => {
+                self.invalid.get_or_insert_with(M::table);
+                return self;
+            }
+        };
+        for col in patch.keys() {
+            match Ident::column::<M>(col) {
+                Ok(ident) => {
+                    let val = format!("patch.{}", ident);
+                    self.sets.push((ident, val));
+                },
+                Err(_) => { self.invalid.get_or_insert_with(|| col.to_string()); },
+            }
+        }
+        let param = self.push(Arg::Json(Value::Object(patch)));
+        self.from = Some(format!("jsonb_populate_record(NULL::{}, {}) AS patch", table, db_labels(&table, &param)));
+        self
+    }
+
+    pub fn eq<A: Into<Arg>>(self, col: &str, val: A) -> Self {
+        self.eq_on::<M, A>(col, val)
+    }
+
+    /// Condition on a column of a joined table
+    pub fn eq_on<T: Model, A: Into<Arg>>(mut self, col: &str, val: A) -> Self {
+        if let Some(col) = self.column::<T>(col) {
+            let param = self.push(val.into());
+            self.conds.push(format!("{} = {}", col, param));
+        }
+        self
+    }
+
+    pub fn ne<A: Into<Arg>>(mut self, col: &str, val: A) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let param = self.push(val.into());
+            self.conds.push(format!("{} <> {}", col, param));
+        }
+        self
+    }
+
+    /// Compare the column's text representation, for values given as strings whatever
+    ///     the column's type
+    pub fn eq_text<V: ToString>(mut self, col: &str, val: V) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let param = self.push(Arg::Text(val.to_string()));
+            self.conds.push(format!("{}::text = {}", col, param));
+        }
+        self
+    }
+
+    pub fn is_in<A: Into<Arg>>(mut self, col: &str, vals: Vec<A>) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let params = vals.into_iter()
+                .map(|val| self.push(val.into()))
+                .collect::<Vec<String>>();
+            self.conds.push(if params.is_empty() {
+                "FALSE".to_string()
+            } else {
+                format!("{} IN ({})", col, params.join(", "))
+            });
+        }
+        self
+    }
+
+    pub fn before<A: Into<Arg>>(mut self, col: &str, val: A) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let param = self.push(val.into());
+            self.conds.push(format!("{} < {}", col, param));
+        }
+        self
+    }
+
+    /// Negation of eq_text(), which also matches nulls
+    pub fn ne_text<V: ToString>(mut self, col: &str, val: V) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let param = self.push(Arg::Text(val.to_string()));
+            self.conds.push(format!("{}::text IS DISTINCT FROM {}", col, param));
+        }
+        self
+    }
+
+    /// Case insensitive substring match on the column's text representation
+    pub fn contains(mut self, col: &str, val: &str) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let param = self.push(Arg::Text(format!("%{}%", escape_like(val))));
+            self.conds.push(format!("{}::text ILIKE {}", col, param));
+        }
+        self
+    }
+
+    pub fn is_null(mut self, col: &str, null: bool) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            self.conds.push(format!("{} IS {}NULL", col, if null { "" } else { "NOT " }));
+        }
+        self
+    }
+
+    pub fn date(mut self, col: &str, date: &DateFilter) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            let cond = match date.rel_date {
+                RelativeDate::Before(dt) => format!("{} < {}", col, self.push(Arg::Time(dt))),
+                RelativeDate::After(dt) => format!("{} > {}", col, self.push(Arg::Time(dt))),
+                RelativeDate::DayOf(dt) => {
+                    let day = dt.date().and_hms(0, 0, 0);
+                    let (from, to) = (self.push(Arg::Time(day)), self.push(Arg::Time(day + Duration::days(1))));
+                    format!("{col} >= {} AND {col} < {}", from, to, col = col)
+                },
+                RelativeDate::Between(from, to) => {
+                    let (from, to) = (self.push(Arg::Time(from)), self.push(Arg::Time(to)));
+                    format!("{} BETWEEN {} AND {}", col, from, to)
+                },
+            };
+            self.conds.push(if date.not { format!("NOT ({})", cond) } else { cond });
+        }
+        self
+    }
+
+    /// Rows past the (col, id) keyset cursor when scanning in order
+    pub fn past(mut self, col: &str, order: SortOrder, cursor: &Cursor) -> Self {
+        if let (Some(col), Some(id)) = (self.column::<M>(col), self.column::<M>("id")) {
+            let (at, cursor_id) = (self.push(Arg::Time(cursor.at)), self.push(Arg::Id(cursor.id.clone())));
+            self.conds.push(format!("({}, {}) {} ({}, {})", col, id, order.after_op(), at, cursor_id));
+        }
+        self
+    }
+
+    /// Skip soft deleted rows
+    pub fn live(self) -> Self {
+        self.live_on::<M>()
+    }
+
+    /// Skip soft deleted rows of a joined table
+    pub fn live_on<T: Model>(mut self) -> Self {
+        if T::soft_delete() {
+            if let Some(col) = self.column::<T>("status") {
+                self.conds.push(format!("{} <> 'deleted'", col));
+            }
+        }
+        self
+    }
+
+    /// INNER JOIN T on T.t_col = M.m_col
+    pub fn join<T: Model>(mut self, t_col: &str, m_col: &str) -> Self {
+        match (Ident::table::<T>(), self.column::<T>(t_col), self.column::<M>(m_col)) {
+            (Ok(table), Some(t_col), Some(m_col)) => {
+                self.joins.push(format!("INNER JOIN {} ON {} = {}", table, t_col, m_col));
+            },
+            (Err(_), _, _) => { self.invalid.get_or_insert_with(T::table); },
+            _ => {},
+        }
+        self
+    }
+
+    pub fn order_by(mut self, col: &str, order: SortOrder) -> Self {
+        if let Some(col) = self.column::<M>(col) {
+            self.order.push(format!("{} {}", col, order.sql()));
+        }
+        self
+    }
+
+    pub fn limit(mut self, limit: i64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    /// Lock selected rows until the end of the transaction
+    pub fn for_update(mut self) -> Self {
+        self.lock = true;
+        self
+    }
+
+    /// The statement and the values to bind to it, in placeholder order
+    pub fn build(self) -> sqlx::Result<(String, Vec<Arg>)> {
+        if let Some(name) = self.invalid {
+            return Err(sqlx::Error::ColumnNotFound(name));
+        }
+        let table = Ident::table::<M>()?;
+        let filter = if self.conds.is_empty() {
+            String::new()
+        } else {
+            format!(" WHERE {}", self.conds.join(" AND "))
+        };
+        let sql = match self.stmt {
+            Stmt::Select => {
+                let mut sql = format!("SELECT {table}.* FROM {table}", table = table);
+                for join in self.joins.iter() {
+                    sql.push(' ');
+                    sql.push_str(join);
+                }
+                sql.push_str(&filter);
+                if !self.order.is_empty() {
+                    sql.push_str(&format!(" ORDER BY {}", self.order.join(", ")));
+                }
+                if let Some(limit) = self.limit {
+                    sql.push_str(&format!(" LIMIT {}", limit));
+                }
+                if self.lock {
+                    sql.push_str(" FOR UPDATE");
+                }
+                sql
+            },
+            Stmt::Insert if self.sets.is_empty() => format!("INSERT INTO {} DEFAULT VALUES RETURNING *", table),
+            Stmt::Insert => {
+                let (cols, vals): (Vec<String>, Vec<String>) = self.sets.iter()
+                    .map(|(col, val)| (col.to_string(), val.clone()))
+                    .unzip();
+                format!("INSERT INTO {} ({}) VALUES ({}) RETURNING *", table, cols.join(", "), vals.join(", "))
+            },
+            Stmt::Update => {
+                if self.sets.is_empty() {
+                    return Err(sqlx::Error::Protocol("UPDATE without columns to set".into()));
+                }
+                let set = self.sets.iter()
+                    .map(|(col, val)| format!("{} = {}", col, val))
+                    .collect::<Vec<String>>()
+                    .join(", ");
+                let from = self.from.map(|from| format!(" FROM {}", from)).unwrap_or_default();
+                format!("UPDATE {table} SET {set}{from}{filter} RETURNING {table}.*",
+                    table = table, set = set, from = from, filter = filter)
+            },
+            Stmt::Delete => format!("DELETE FROM {table}{filter} RETURNING {table}.*",
+                table = table, filter = filter),
+        };
+        Ok((sql, self.args))
+    }
+
+    pub async fn fetch_all<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Vec<M>> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| M::from_row(&row))
+            .fetch_all(db).await
+    }
+
+    pub async fn fetch_optional<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Option<M>> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| M::from_row(&row))
+            .fetch_optional(db).await
+    }
+
+    pub async fn fetch_one<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<M> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| M::from_row(&row))
+            .fetch_one(db).await
+    }
+
+    /// The rows as returned, for reading columns beside the model's fields
+    pub async fn fetch_rows<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Vec<PgRow>> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .fetch_all(db).await
+    }
+
+    /// The ids of the rows the statement returns, ex. the rows an update or delete touched
+    pub async fn fetch_ids<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Vec<Id>> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| row.try_get::<Id, _>("id"))
+            .fetch_all(db).await
+    }
+
+    /// The rows the statement returns along with their ids, for models whose id() does not
+    ///     read the id column
+    pub async fn fetch_keyed<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<Vec<(Id, M)>> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| Ok((row.try_get::<Id, _>("id")?, M::from_row(&row)?)))
+            .fetch_all(db).await
+    }
+
+    pub async fn execute<'e, E: PgExecutor<'e>>(self, db: E) -> sqlx::Result<PgQueryResult> {
+        let (sql, args) = self.build()?;
+        bind_args(sqlx::query(&sql), args)
+            .execute(db).await
+    }
+}
+
+fn escape_like(val: &str) -> String {
+    val.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub(crate) fn bind_args<'q>(
+    mut query: SqlQuery<'q, Postgres, PgArguments>,
+    args: Vec<Arg>
+) -> SqlQuery<'q, Postgres, PgArguments>
+{
+    for arg in args {
+        query = match arg {
+            Arg::Text(text) => query.bind(text),
+            Arg::Time(time) => query.bind(time),
+            Arg::Id(id) => query.bind(id),
+            Arg::OptId(id) => query.bind(id),
+            Arg::Status(status) => query.bind(status),
+            Arg::Bool(b) => query.bind(b),
+            Arg::Json(json) => query.bind(json),
+        };
+    }
+    query
+}
+
+impl From<String> for Arg {
+    fn from(text: String) -> Self { Arg::Text(text) }
+}
+
+impl From<&str> for Arg {
+    fn from(text: &str) -> Self { Arg::Text(text.to_string()) }
+}
+
+impl From<NaiveDateTime> for Arg {
+    fn from(time: NaiveDateTime) -> Self { Arg::Time(time) }
+}
+
+impl From<Id> for Arg {
+    fn from(id: Id) -> Self { Arg::Id(id) }
+}
+
+impl From<Option<Id>> for Arg {
+    fn from(id: Option<Id>) -> Self { Arg::OptId(id) }
+}
+
+impl From<Status> for Arg {
+    fn from(status: Status) -> Self { Arg::Status(status) }
+}
+
+impl From<bool> for Arg {
+    fn from(b: bool) -> Self { Arg::Bool(b) }
+}
+
+impl From<Value> for Arg {
+    fn from(json: Value) -> Self { Arg::Json(json) }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{Record, record::RecordItem};
+
+    #[test]
+    fn quotes_and_validates_identifiers() {
+        assert_eq!(Ident::new("user_id").unwrap().to_string(), "\"user_id\"");
+        assert!(Ident::new("id; DROP TABLE users").is_none());
+        assert!(Ident::new("Name").is_none());
+        let err = Sql::<Record>::select().eq("password", "x").build().unwrap_err();
+        assert!(matches!(err, sqlx::Error::ColumnNotFound(col) if col == "password"));
+    }
+
+    #[test]
+    fn builds_soft_delete_and_joins() {
+        let (sql, args) = Sql::<Record>::delete().eq("id", Id::nil()).build().unwrap();
+        assert_eq!(sql, "UPDATE \"records\" SET \"status\" = $1, \"updated_at\" = $2 \
+            WHERE \"records\".\"status\" <> 'deleted' AND \"records\".\"id\" = $3 RETURNING \"records\".*");
+        assert_eq!(args[0], Arg::Status(Status::Deleted));
+
+        let (sql, _) = Sql::<Record>::select()
+            .join::<RecordItem>("record_id", "id")
+            .eq_on::<RecordItem>("item_id", Id::nil())
+            .live()
+            .build().unwrap();
+        assert_eq!(sql, "SELECT \"records\".* FROM \"records\" \
+            INNER JOIN \"record_items\" ON \"record_items\".\"record_id\" = \"records\".\"id\" \
+            WHERE \"record_items\".\"item_id\" = $1 AND \"records\".\"status\" <> 'deleted'");
+    }
+}
@@ -0,0 +1,301 @@
+//! Multi-hop traversal over link tables. A Graph is built from one or more Linked join
+//!     tables, each read as an undirected edge between its Left and Right models, and walked
+//!     with a recursive CTE, ex. the records sharing a field with an item
+//! ```ignore
+//! let records = Graph::new()
+//!     .with::<ItemField>()
+//!     .with::<RecordItem>()
+//!     .through::<Field>()
+//!     .reachable::<Record, _>(&db.pool, Node::of::<Item>(item_id), 3).await?;
+//! ```
+//! Walks are breadth first and reach each node at most once before and once after passing
+//!     through() (see walk()), so their cost grows with the number of links rather than of
+//!     paths. They are cut off at max_depth, which is
+//!     clamped to MAX_DEPTH
+use serde::{Serialize, Deserialize};
+use sqlx::{prelude::*, postgres::PgExecutor, types::Json};
+use crate::{Id, Model, query::{Ident, sql::{Arg, bind_args}}};
+use super::Linked;
+
+/// Deepest walk a query may request
+pub const MAX_DEPTH: i32 = 6;
+
+/// Hops walked when a request does not give ?depth=
+pub const DEFAULT_DEPTH: i32 = 3;
+
+/// An entry of some model, by table
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+pub struct Node {
+    pub table: String,
+    pub id: Id,
+}
+
+/// An entry found by a walk, with the number of hops to its nearest path
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Reached<T> {
+    pub depth: i32,
+    pub entry: T,
+}
+
+/// ?depth= for traversal routes
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct DepthQuery {
+    pub depth: Option<i32>,
+}
+
+/// One join table, as the left and right tables and id columns it connects
+#[derive(Debug, Clone, PartialEq)]
+struct Edge {
+    link: Ident,
+    left: String,
+    left_col: Ident,
+    right: String,
+    right_col: Ident,
+    soft_delete: bool,
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Graph {
+    edges: Vec<Edge>,
+    /// Table literal of the model walks must pass through
+    through: Option<String>,
+    /// The first table or column which failed validation
+    invalid: Option<String>,
+}
+
+impl Node {
+
+    pub fn of<M: Model>(id: Id) -> Self {
+        Self { table: M::table(), id }
+    }
+}
+
+impl DepthQuery {
+
+    pub fn depth(&self) -> i32 {
+        self.depth.unwrap_or(DEFAULT_DEPTH)
+    }
+}
+
+impl Graph {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Walk the links of L in both directions
+    pub fn with<L: Linked>(mut self) -> Self {
+        match edge::<L>() {
+            Some(edge) => self.edges.push(edge),
+            None => { self.invalid.get_or_insert_with(L::table); },
+        }
+        self
+    }
+
+    /// Only keep walks which pass through an entry of M on the way
+    pub fn through<M: Model>(mut self) -> Self {
+        match literal::<M>() {
+            Some(name) => self.through = Some(name),
+            None => { self.invalid.get_or_insert_with(M::table); },
+        }
+        self
+    }
+
+    /// Entries of T reachable from `from` within max_depth hops, nearest first
+    pub async fn reachable<'e, T: Model, E: PgExecutor<'e>>(&self, db: E, from: Node, max_depth: i32) -> sqlx::Result<Vec<Reached<T>>> {
+        let (walk, args) = self.walk(from, max_depth)?;
+        let table = Ident::table::<T>()?;
+        let name = literal::<T>().ok_or_else(|| sqlx::Error::ColumnNotFound(T::table()))?;
+        let via = if self.through.is_some() { " AND via" } else { "" };
+        let live = if T::soft_delete() { format!(" WHERE {}.\"status\" <> 'deleted'", table) } else { String::new() };
+        let sql = format!("{walk}
+            SELECT {table}.*, reached.depth FROM {table}
+            INNER JOIN (
+                SELECT node_id, MIN(depth) AS depth FROM walk
+                WHERE node_table = {name} AND depth > 0{via}
+                GROUP BY node_id
+            ) reached ON reached.node_id = {table}.\"id\"::text{live}
+            ORDER BY reached.depth, {table}.\"id\"",
+            walk = walk, table = table, name = name, via = via, live = live);
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| Ok(Reached { depth: row.try_get("depth")?, entry: T::from_row(&row)? }))
+            .fetch_all(db).await
+    }
+
+    /// The nodes of a shortest walk from `from` to `to`, both included. None if `to` is
+    ///     not reachable within max_depth hops
+    pub async fn shortest_path<'e, E: PgExecutor<'e>>(&self, db: E, from: Node, to: Node, max_depth: i32) -> sqlx::Result<Option<Vec<Node>>> {
+        let (walk, mut args) = self.walk(from, max_depth)?;
+        let via = if self.through.is_some() { " AND via" } else { "" };
+        let sql = format!("{walk}
+            SELECT path FROM walk
+            WHERE node_table = ${table} AND node_id = ${id}{via}
+            ORDER BY depth
+            LIMIT 1",
+            walk = walk, table = args.len() + 1, id = args.len() + 2, via = via);
+        args.push(Arg::Text(to.table));
+        args.push(Arg::Text(to.id.get()));
+        bind_args(sqlx::query(&sql), args)
+            .try_map(|row| row.try_get::<Json<Vec<Node>>, _>("path").map(|path| path.0))
+            .fetch_optional(db).await
+    }
+
+    /// The edges, frontier and walk CTEs. The walk is breadth first: each step of frontier
+    ///     holds every node first reached at its depth, once, with one of its shortest
+    ///     paths, and seen holds every node reached so far, which later steps may not
+    ///     revisit. A node may be reached again after the walk passes through() its model,
+    ///     but never more than twice, and the starting node never. walk lists the
+    ///     frontiers' nodes one per row
+    fn walk(&self, from: Node, max_depth: i32) -> sqlx::Result<(String, Vec<Arg>)> {
+        if let Some(name) = &self.invalid {
+            return Err(sqlx::Error::ColumnNotFound(name.clone()));
+        }
+        let mut edges = Vec::new();
+        for edge in self.edges.iter() {
+            let live = if edge.soft_delete {
+                format!(" WHERE {}.\"status\" <> 'deleted'", edge.link)
+            } else {
+                String::new()
+            };
+            for (src, src_col, dst, dst_col) in [
+                (&edge.left, &edge.left_col, &edge.right, &edge.right_col),
+                (&edge.right, &edge.right_col, &edge.left, &edge.left_col),
+            ].iter() {
+                edges.push(format!("SELECT {src}, {link}.{src_col}::text, {dst}, {link}.{dst_col}::text FROM {link}{live}",
+                    src = src, src_col = src_col, dst = dst, dst_col = dst_col, link = edge.link, live = live));
+            }
+        }
+        if edges.is_empty() {
+            return Err(sqlx::Error::Protocol("Graph without link tables".into()));
+        }
+        let via = match &self.through {
+            Some(through) => format!("(n->>'via')::boolean OR e.dst_table = {}", through),
+            None => "FALSE".to_string(),
+        };
+        let sql = format!("WITH RECURSIVE edges (src_table, src_id, dst_table, dst_id) AS (
+                {edges}
+            ), frontier (depth, nodes, seen) AS (
+                SELECT 0, jsonb_build_array(jsonb_build_object('table', $1::text, 'id', $2::text, 'via', FALSE,
+                        'path', jsonb_build_array(jsonb_build_object('table', $1::text, 'id', $2::text)))),
+                    ARRAY[$1::text || ':' || $2::text || ':false', $1::text || ':' || $2::text || ':true']
+                UNION ALL
+                SELECT f.depth + 1, step.nodes, f.seen || step.seen
+                FROM frontier f, LATERAL (
+                    SELECT jsonb_agg(next.node) AS nodes, array_agg(next.key) AS seen FROM (
+                        SELECT DISTINCT ON (s.key) s.key,
+                            jsonb_build_object('table', s.dst_table, 'id', s.dst_id, 'via', s.via,
+                                'path', s.path || jsonb_build_object('table', s.dst_table, 'id', s.dst_id)) AS node
+                        FROM (
+                            SELECT e.dst_table, e.dst_id, n->'path' AS path, {via} AS via,
+                                e.dst_table || ':' || e.dst_id || ':' || ({via})::text AS key
+                            FROM jsonb_array_elements(f.nodes) n
+                            INNER JOIN edges e ON e.src_table = n->>'table' AND e.src_id = n->>'id'
+                        ) s
+                        WHERE NOT s.key = ANY(f.seen)
+                        ORDER BY s.key
+                    ) next
+                ) step
+                WHERE f.depth < {depth} AND step.nodes IS NOT NULL
+            ), walk (node_table, node_id, depth, path, via) AS (
+                SELECT n->>'table', n->>'id', f.depth, n->'path', (n->>'via')::boolean
+                FROM frontier f, jsonb_array_elements(f.nodes) n
+            )",
+            edges = edges.join("\n                UNION ALL "),
+            via = via,
+            depth = max_depth.clamp(1, MAX_DEPTH));
+        Ok((sql, vec![Arg::Text(from.table), Arg::Text(from.id.get())]))
+    }
+}
+
+fn edge<L: Linked>() -> Option<Edge> {
+    Some(Edge {
+        link: Ident::table::<L>().ok()?,
+        left: literal::<L::Left>()?,
+        left_col: Ident::column::<L>(&L::Left::id_str()).ok()?,
+        right: literal::<L::Right>()?,
+        right_col: Ident::column::<L>(&L::Right::id_str()).ok()?,
+        soft_delete: L::soft_delete(),
+    })
+}
+
+/// M's table name as a string literal. Validated as an identifier first, so it cannot
+///     contain quotes
+fn literal<M: Model>() -> Option<String> {
+    Ident::table::<M>().ok()?;
+    Some(format!("'{}'", M::table()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Db;
+    use crate::models::{User, Record, Item, Field, field::FieldKind, record::RecordItem, item::ItemField};
+
+    #[test]
+    fn walks_both_directions_of_each_link_table() {
+        let (sql, args) = Graph::new()
+            .with::<RecordItem>()
+            .with::<ItemField>()
+            .through::<Field>()
+            .walk(Node::of::<Record>(Id::nil()), 10)
+            .unwrap();
+        assert!(sql.contains("SELECT 'records', \"record_items\".\"record_id\"::text, 'items', \"record_items\".\"item_id\"::text FROM \"record_items\""));
+        assert!(sql.contains("SELECT 'fields', \"item_fields\".\"field_id\"::text, 'items', \"item_fields\".\"item_id\"::text FROM \"item_fields\""));
+        assert!(sql.contains("(n->>'via')::boolean OR e.dst_table = 'fields'"));
+        assert!(sql.contains(&format!("f.depth < {}", MAX_DEPTH)));
+        assert_eq!(args, vec![Arg::Text("records".into()), Arg::Text(Id::nil().get())]);
+        assert!(Graph::new().walk(Node::of::<Item>(Id::nil()), 1).is_err());
+    }
+
+    async fn db() -> anyhow::Result<Db> {
+        Db::new(&dotenv::var("DATABASE_URL").unwrap()).await
+    }
+
+    #[actix_rt::test]
+    async fn walks_densely_linked_rows_once() -> anyhow::Result<()> {
+        let db = db().await?;
+        let mut tx = db.pool.begin().await?;
+        let user = User { name: Some("graph".into()), ..Default::default() }.insert(&mut tx).await?;
+        // Every record holds every item, so the number of paths grows as 8^depth
+        let (mut records, mut items) = (Vec::new(), Vec::new());
+        for i in 0..8 {
+            records.push(Record::new(format!("record{}", i), user.id.clone()).insert(&mut tx).await?);
+            items.push(Item::new(format!("item{}", i), user.id.clone()).insert(&mut tx).await?);
+        }
+        for record in records.iter() {
+            for item in items.iter() {
+                RecordItem::new(record.id.clone(), item.id.clone(), None, None, None).insert(&mut tx).await?;
+            }
+        }
+        let field = Field::new("graph".into(), FieldKind::Integer, user.id.clone()).insert(&mut tx).await?;
+        let other = Item::new("other".into(), user.id.clone()).insert(&mut tx).await?;
+        let far = Record::new("far".into(), user.id.clone()).insert(&mut tx).await?;
+        ItemField::new(items[7].id.clone(), field.id.clone(), None, None, None).insert(&mut tx).await?;
+        ItemField::new(other.id.clone(), field.id.clone(), None, None, None).insert(&mut tx).await?;
+        RecordItem::new(far.id.clone(), other.id.clone(), None, None, None).insert(&mut tx).await?;
+
+        let graph = Graph::new().with::<RecordItem>().with::<ItemField>();
+        let from = Node::of::<Record>(records[0].id.clone());
+        let reached = graph.reachable::<Record, _>(&mut tx, from.clone(), MAX_DEPTH).await?;
+        assert_eq!(reached.len(), 8);
+        assert!(reached.iter().all(|r| r.entry.id != records[0].id));
+        assert!(reached.iter().all(|r| r.depth == if r.entry.id == far.id { 4 } else { 2 }));
+
+        let through = graph.clone().through::<Field>()
+            .reachable::<Record, _>(&mut tx, from.clone(), MAX_DEPTH).await?;
+        assert!(through.iter().any(|r| r.entry.id == far.id));
+        assert!(through.iter().all(|r| r.depth == 4 && r.entry.id != records[0].id));
+
+        let path = graph.shortest_path(&mut tx, from.clone(), Node::of::<Record>(far.id.clone()), MAX_DEPTH).await?;
+        assert_eq!(path, Some(vec![
+            from.clone(),
+            Node::of::<Item>(items[7].id.clone()),
+            Node::of::<Field>(field.id.clone()),
+            Node::of::<Item>(other.id.clone()),
+            Node::of::<Record>(far.id.clone()),
+        ]));
+        assert_eq!(graph.shortest_path(&mut tx, from, Node::of::<Record>(far.id.clone()), 3).await?, None);
+        tx.rollback().await?;
+        Ok(())
+    }
+}
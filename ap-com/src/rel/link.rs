@@ -1,13 +1,10 @@
-use crate::{models::Model, types::Id};
+use crate::{models::{Model, Link, event::{self, ModelEvent}}, query::Sql, types::Id};
+use super::graph::{Graph, Node, Reached};
 use serde::{Serialize, Deserialize};
-use sqlx::{
-    FromRow, Postgres, postgres::{PgRow, PgPool},
-    types::chrono::{NaiveDateTime, Utc}
-};
-use crate::now;
+use sqlx::{Acquire, FromRow, Postgres, postgres::{PgConnection, PgRow, PgExecutor}};
 
 #[async_trait::async_trait]
-pub trait Linked: Model + Default + Clone {
+pub trait Linked: Model + Default + Clone + 'static {
 
     type Left: Model + LinkedTo<Self::Right>;
     type Right: Model + LinkedTo<Self::Left>;
@@ -24,78 +21,64 @@ pub trait Linked: Model + Default + Clone {
     /// Default insert function for a join table consisting only of L, R ids + optional link_id
     ///     (and created, updated). Re-implement for join tables with extra required fields
     ///     ex. TopicCategory, etc.
-    async fn insert_left_link(&self, db: &PgPool) -> sqlx::Result<Self> {
-        let query_str = format!("
-            INSERT INTO {link} ({left_id_str}, {right_id_str}, link_id )
-            VALUES ($1, $2, $3)
-            RETURNING *
-            ",
-            link = Self::table(),
-            left_id_str = Self::Left::id_str(),
-            right_id_str = Self::Right::id_str(),);
-        let left = self.clone().left_id();
-        let right = self.clone().right_id();
-        let link = self.clone().link_id();
-        let res = sqlx::query_as::<Postgres, Self>(&query_str)
-            .bind(left)
-            .bind(right)
-            .bind(link)
-            .fetch_one(db).await?;
-        Ok(res)
+    async fn insert_left_link<'e, E: PgExecutor<'e>>(&self, db: E) -> sqlx::Result<Self> {
+        Sql::<Self>::insert()
+            .set(&Self::Left::id_str(), self.clone().left_id())
+            .set(&Self::Right::id_str(), self.clone().right_id())
+            .set("link_id", self.clone().link_id())
+            .fetch_one(db).await
+    }
+
+    /// Run by LinkedTo::add_link() and create_linked() after inserting the join row, in the
+    ///     same transaction, so an error undoes the link. ex. RecordItem applies the record's
+    ///     schema to the new item
+    async fn after_link(&self, _conn: &mut PgConnection) -> sqlx::Result<()> {
+        Ok(())
+    }
 
+    async fn linked_to_left<'e, E: PgExecutor<'e>>(db: E, left_id: Id) -> sqlx::Result<Vec<Self::Right>> {
+        Sql::<Self::Right>::select()
+            .join::<Self>(&Self::Right::id_str(), "id")
+            .eq_on::<Self, _>(&Self::Left::id_str(), left_id)
+            .live_on::<Self>()
+            .live()
+            .fetch_all(db).await
     }
 
-    async fn linked_to_left(db: &PgPool, left_id: Id) -> sqlx::Result<Vec<Self::Right>> {
-        let query_str = format!("
-            SELECT * FROM {right}
-            INNER JOIN {link} ON {right}.id = {link}.{right_id_str}
-            INNER JOIN {left} ON {left}.id = {link}.{left_id_str}
-            WHERE {left}.id = $1
-            ",
-            left = Self::Left::table(),
-            link = Self::table(),
-            right = Self::Right::table(),
-            left_id_str = Self::Left::id_str(),
-            right_id_str = Self::Right::id_str(),);
-        let res = sqlx::query_as::<Postgres, Self::Right>(&query_str)
-            .bind(left_id)
-            .fetch_all(db).await?;
-        Ok(res)
+    async fn linked_to_right<'e, E: PgExecutor<'e>>(db: E, right_id: Id) -> sqlx::Result<Vec<Self::Left>> {
+        Sql::<Self::Left>::select()
+            .join::<Self>(&Self::Left::id_str(), "id")
+            .eq_on::<Self, _>(&Self::Right::id_str(), right_id)
+            .live_on::<Self>()
+            .live()
+            .fetch_all(db).await
     }
-    async fn linked_to_right(db: &PgPool, right_id: Id) -> sqlx::Result<Vec<Self::Left>> {
-        let res = sqlx::query_as::<Postgres, Self::Left>(&format!("
-            SELECT * FROM {left}
-            INNER JOIN {link} ON {left}.id = {link}.{left_id_str}
-            INNER JOIN {right} ON {right}.id = {link}.{right_id_str}
-            WHERE {right}.id = $1
-            ",
-            left = Self::Left::table(),
-            link = Self::table(),
-            right = Self::Right::table(),
-            left_id_str = Self::Left::id_str(),
-            right_id_str = Self::Right::id_str(),
-            ))
-            .bind(right_id)
-            .fetch_all(db).await?;
-        Ok(res)
+
+    async fn linked_between<'e, E: PgExecutor<'e>>(db: E, left_id: Id, right_id: Id) -> sqlx::Result<Vec<Self>> {
+        Sql::<Self>::select()
+            .eq(&Self::Left::id_str(), left_id)
+            .eq(&Self::Right::id_str(), right_id)
+            .fetch_all(db).await
     }
 
-    async fn linked_between(db: &PgPool, left_id: Id, right_id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("
-                SELECT * FROM {link}
-                WHERE {left_id} = $1
-                 AND  {right_id} = $2
-                ",
-                link = Self::table(),
-                left_id = Self::Left::id_str(),
-                right_id = Self::Right::id_str()))
-            .bind(left_id)
-            .bind(right_id)
-            .fetch_all(db).await?;
-        Ok(res)
+    /// Right entries reachable from the Left entry within max_depth hops of this join
+    ///     table, ex. items reachable from a record through the items and records between
+    async fn reachable_from_left<'e, E: PgExecutor<'e>>(db: E, left_id: Id, max_depth: i32) -> sqlx::Result<Vec<Reached<Self::Right>>> {
+        Graph::new().with::<Self>()
+            .reachable::<Self::Right, E>(db, Node::of::<Self::Left>(left_id), max_depth).await
     }
 
+    async fn reachable_from_right<'e, E: PgExecutor<'e>>(db: E, right_id: Id, max_depth: i32) -> sqlx::Result<Vec<Reached<Self::Left>>> {
+        Graph::new().with::<Self>()
+            .reachable::<Self::Left, E>(db, Node::of::<Self::Right>(right_id), max_depth).await
+    }
 
+    /// A shortest chain of links from the Left entry to the Right entry, as the entries
+    ///     along it. None if they are not connected within max_depth hops
+    async fn shortest_path<'e, E: PgExecutor<'e>>(db: E, left_id: Id, right_id: Id, max_depth: i32) -> sqlx::Result<Option<Vec<Node>>> {
+        Graph::new().with::<Self>()
+            .shortest_path(db, Node::of::<Self::Left>(left_id), Node::of::<Self::Right>(right_id), max_depth).await
+    }
 }
 
 #[async_trait::async_trait]
@@ -107,65 +90,116 @@ where
 
     type LinkModel: Model + for<'r> FromRow<'r, PgRow> + Linked;
 
-    async fn get_entries_linked_to(db: &PgPool, other_id: Id) -> sqlx::Result<Vec<L>> {
-        let res = sqlx::query_as::<Postgres, L>(&format!("
-            SELECT * FROM {this}
-            INNER JOIN {link} ON {this}.id = {link}.{this_id_str}
-            INNER JOIN {other} ON {other}.id = {link}.{other_id_str}
-            WHERE {other}.id = $1
-            ",
-            this = Self::table(),
-            link = Self::LinkModel::table(),
-            other = L::table(),
-            this_id_str = Self::id_str(),
-            other_id_str = L::id_str(),
-            ))
-            .bind(other_id)
-            .fetch_all(db).await?;
-        Ok(res)
+    /// Rows of this model linked to the entry of L with other_id. Soft deleted rows are
+    ///     skipped
+    async fn get_entries_linked_to<'e, E: PgExecutor<'e>>(db: E, other_id: Id) -> sqlx::Result<Vec<Self>> {
+        Sql::<Self>::select()
+            .join::<Self::LinkModel>(&Self::id_str(), "id")
+            .eq_on::<Self::LinkModel, _>(&L::id_str(), other_id)
+            .live()
+            .fetch_all(db).await
+    }
+    /// Entries of L linked to this row. Soft deleted entries are skipped
+    async fn get_links_to_entry<'e, E: PgExecutor<'e>>(db: E, this_id: Id) -> sqlx::Result<Vec<L>> {
+        Sql::<L>::select()
+            .join::<Self::LinkModel>(&L::id_str(), "id")
+            .eq_on::<Self::LinkModel, _>(&Self::id_str(), this_id)
+            .live()
+            .fetch_all(db).await
+    }
+    /// Link rows between this row and the entry of L with other_id
+    async fn get_links_between<'e, E: PgExecutor<'e>>(db: E, this_id: Id, other_id: Id) -> sqlx::Result<Vec<Self::LinkModel>> {
+        Sql::<Self::LinkModel>::select()
+            .eq(&Self::id_str(), this_id)
+            .eq(&L::id_str(), other_id)
+            .fetch_all(db).await
     }
-    async fn get_links_to_entry(db: &PgPool, this_id: Id) -> sqlx::Result<Vec<L>> {
-        let res = sqlx::query_as::<Postgres, L>(&format!("
-            SELECT * FROM {other}
-            INNER JOIN {link} ON {other}.id = {link}.{other_id_str}
-            INNER JOIN {this} ON {this}.id = {link}.{this_id_str}
-            WHERE {this}.id = $1
-            ",
-            this = Self::table(),
-            link = Self::LinkModel::table(),
-            other = L::table(),
-            this_id_str = Self::id_str(),
-            other_id_str = L::id_str(),
-            ))
-            .bind(this_id)
-            .fetch_all(db).await?;
-        Ok(res)
+    /// Link this row to the existing entry of L with other_id, attaching or creating the
+    ///     Link describing the relationship in the same transaction
+    async fn add_link<'a, A>(db: A, this_id: Id, other_id: Id, link: Option<LinkInput>) -> sqlx::Result<Self::LinkModel>
+    where
+        A: Acquire<'a, Database = Postgres> + Send
+    {
+        let mut tx = db.begin().await?;
+        let join = insert_join::<Self, L>(&mut tx, this_id, other_id, link).await?;
+        tx.commit().await?;
+        Ok(join)
     }
-    async fn get_links_between(db: &PgPool, this_id: Id, other_id: Id) -> sqlx::Result<Vec<Self>> {
-        let res = sqlx::query_as::<Postgres, Self>(&format!("
-                SELECT * FROM {link}
-                WHERE {this_id_str} = $1
-                 AND  {other_id_str} = $2
-                ",
-                link = Self::LinkModel::table(),
-                this_id_str = Self::id_str(),
-                other_id_str = L::id_str()))
-            .bind(this_id)
-            .bind(other_id)
-            .fetch_all(db).await?;
-        Ok(res)
+    /// Insert other and link it to this row in one transaction, ex. a new item inside a
+    ///     record. Nothing is written if any of the inserts fails
+    async fn create_linked<'a, A>(db: A, this_id: Id, other: L, link: Option<LinkInput>) -> sqlx::Result<(Self::LinkModel, L)>
+    where
+        A: Acquire<'a, Database = Postgres> + Send,
+        L: Clone + 'static
+    {
+        let mut tx = db.begin().await?;
+        let other = other.insert(&mut tx).await?;
+        let join = insert_join::<Self, L>(&mut tx, this_id, other.clone().id(), link).await?;
+        tx.commit().await?;
+        Ok((join, other))
     }
-    /* async fn add_link(db: &PgPool, id: Id, other: L, link: Option<Link>) -> sqlx::Result<(Self::LinkModel, L)> {
-        if let Some(link) = link {
+}
 
-        }
-        match other.insert(db).await {
-            Ok(l) => {
+/// The Link to describe a new relationship with -- an existing one by id, or a new
+///     name and value
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(untagged)]
+pub enum LinkInput {
+    Existing { id: Id },
+    New { name: String, #[serde(default)] value: Option<String> },
+}
 
+/// Insert the join row between this_id and other_id, in whichever order T's link model
+///     keeps them. Their events are deferred until the caller's transaction commits
+async fn insert_join<T, L>(conn: &mut PgConnection, this_id: Id, other_id: Id, link: Option<LinkInput>) -> sqlx::Result<T::LinkModel>
+where
+    T: LinkedTo<L>,
+    L: Model + for<'r> FromRow<'r, PgRow>
+{
+    let link_id = match link {
+        Some(LinkInput::Existing { id }) => Some(id),
+        Some(LinkInput::New { name, value }) => {
+            let link = Link::new(name, value).insert(&mut *conn).await?;
+            Some(link.id)
+        },
+        None => None,
+    };
+    let join = if T::table() == <T::LinkModel as Linked>::Left::table() {
+        T::LinkModel::new_basic(this_id, other_id, link_id)
+    } else {
+        T::LinkModel::new_basic(other_id, this_id, link_id)
+    };
+    let join = join.insert_left_link(&mut *conn).await?;
+    join.after_link(conn).await?;
+    event::defer(ModelEvent::Created(join.clone()));
+    Ok(join)
+}
 
-            }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Db;
+    use crate::models::{User, Record, Item};
 
-        }
+    async fn db() -> anyhow::Result<Db> {
+        Db::new(&dotenv::var("DATABASE_URL").unwrap()).await
+    }
 
-    } */
+    #[actix_rt::test]
+    async fn creates_items_inside_records() -> anyhow::Result<()> {
+        let db = db().await?;
+        let mut tx = db.pool.begin().await?;
+        let user = User { name: Some("linked".into()), ..Default::default() }.insert(&mut tx).await?;
+        let record = Record::new("linked".into(), user.id.clone()).insert(&mut tx).await?;
+        let link = LinkInput::New { name: "contains".into(), value: None };
+        let (join, item) = <Record as LinkedTo<Item>>::create_linked(&mut tx, record.id.clone(), Item::new("inside".into(), user.id.clone()), Some(link)).await?;
+        assert_eq!((join.record_id.clone(), join.item_id.clone()), (record.id.clone(), item.id.clone()));
+        assert!(join.link_id.is_some());
+        let stored = Sql::<Item>::select().eq("id", item.id.clone()).fetch_one(&mut tx).await?;
+        assert_eq!(stored, item);
+        let linked = Sql::<Link>::select().eq("id", join.link_id.clone().unwrap()).fetch_one(&mut tx).await?;
+        assert_eq!(linked.name, "contains");
+        tx.rollback().await?;
+        Ok(())
+    }
 }
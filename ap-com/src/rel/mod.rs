@@ -1,2 +1,6 @@
 pub mod link;
+pub mod graph;
+pub mod routes;
 pub use link::{Linked, LinkedTo};
+pub use graph::{Graph, Node, Reached};
+pub use routes::LinkRoutes;
@@ -0,0 +1,267 @@
+//! Link management routes generated from a Linked join table. For RecordItem,
+//! ```ignore
+//! cfg.service(LinkRoutes::<RecordItem>::service())
+//! ```
+//!     mounts GET, POST and DELETE on both /record/{id}/item and /item/{id}/record, along
+//!     with multi-hop traversal under /record/{id}/item/reachable and
+//!     /record/{id}/item/{item_id}/path (see rel::graph), and the mirrored paths. POST
+//!     /record/{id}/item/new creates an item and links it in one transaction. Link
+//!     routes are plain resources rather than a scope, so they must be registered before
+//!     the ModelRoutes scopes of their models, which would otherwise match the prefix first
+use std::marker::PhantomData;
+use actix_web::{
+    HttpResponse,
+    dev::{AppService, HttpServiceFactory},
+    web::{self, Data, Json, Path, Query},
+};
+use serde::{Serialize, Deserialize};
+use crate::{Db, Id, Model, query::Sql, util::respond, auth::jwt::EncodedUser, models::{routes::actor, event::{self, ModelEvent}}};
+use super::{Linked, LinkedTo, link::LinkInput, graph::DepthQuery};
+
+/// POST body -- the entry on the other side, and optionally the Link describing the
+///     relationship
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkBody {
+    pub id: Id,
+    #[serde(default)]
+    pub link: Option<LinkInput>,
+}
+
+/// POST .../new body -- the entry to create on the other side, and optionally the Link
+///     describing the relationship
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NewLinkBody<T> {
+    pub entry: T,
+    #[serde(default)]
+    pub link: Option<LinkInput>,
+}
+
+/// A newly created entry along with the join row linking it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct LinkedEntry<L, T> {
+    pub link: L,
+    pub entry: T,
+}
+
+/// DELETE params -- either the id of the other entry to unlink, or all=true to remove every
+///     link of the entry to the other model
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UnlinkQuery {
+    pub id: Option<Id>,
+    #[serde(default)]
+    pub all: bool,
+}
+
+/// Which end of the join table the path's {id} refers to
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Side {
+    Left,
+    Right,
+}
+
+pub struct LinkRoutes<L: Linked>(PhantomData<fn() -> L>);
+
+impl<L> LinkRoutes<L>
+where
+    for<'a> L: 'static + Linked + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+    for<'a> L::Left: 'static + LinkedTo<L::Right, LinkModel = L> + Clone + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+    for<'a> L::Right: 'static + LinkedTo<L::Left, LinkModel = L> + Clone + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+{
+
+    pub fn service() -> Self {
+        Self(PhantomData)
+    }
+
+    /// /{left}/{id}/{right}, ex. /record/{id}/item for RecordItem
+    pub fn left_path() -> String {
+        format!("/{}/{{id}}/{}", segment::<L::Left>(), segment::<L::Right>())
+    }
+
+    /// /{right}/{id}/{left}, ex. /item/{id}/record for RecordItem
+    pub fn right_path() -> String {
+        format!("/{}/{{id}}/{}", segment::<L::Right>(), segment::<L::Left>())
+    }
+
+    /// Entries of Right reachable from the Left entry with id within ?depth= hops
+    async fn get_reachable_right(db: Data<Db>, id: Path<Id>, query: Query<DepthQuery>) -> actix_web::Result<HttpResponse> {
+        match L::reachable_from_left(&db.pool, id.into_inner(), query.depth()).await {
+            Ok(reached) => Ok(respond::ok(reached)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+
+    async fn get_reachable_left(db: Data<Db>, id: Path<Id>, query: Query<DepthQuery>) -> actix_web::Result<HttpResponse> {
+        match L::reachable_from_right(&db.pool, id.into_inner(), query.depth()).await {
+            Ok(reached) => Ok(respond::ok(reached)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+
+    /// A shortest chain of links between the Left and Right entries, from either end
+    async fn get_path(db: Data<Db>, side: Side, this: Id, other: Id, depth: i32) -> HttpResponse {
+        let path = match side {
+            Side::Left => L::shortest_path(&db.pool, this, other, depth).await,
+            Side::Right => L::shortest_path(&db.pool, other, this, depth).await
+                .map(|path| path.map(|path| path.into_iter().rev().collect())),
+        };
+        match path {
+            Ok(Some(path)) => respond::ok(path),
+            Ok(None) => respond::not_found("NO PATH WITHIN DEPTH"),
+            Err(e) => respond::err(e),
+        }
+    }
+
+    async fn get_path_from_left(db: Data<Db>, path: Path<(Id, Id)>, query: Query<DepthQuery>) -> actix_web::Result<HttpResponse> {
+        let (this, other) = path.into_inner();
+        Ok(Self::get_path(db, Side::Left, this, other, query.depth()).await)
+    }
+
+    async fn get_path_from_right(db: Data<Db>, path: Path<(Id, Id)>, query: Query<DepthQuery>) -> actix_web::Result<HttpResponse> {
+        let (this, other) = path.into_inner();
+        Ok(Self::get_path(db, Side::Right, this, other, query.depth()).await)
+    }
+
+    /// Entries of Right linked to the Left entry with id
+    async fn get_right(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+        match L::linked_to_left(&db.pool, id.into_inner()).await {
+            Ok(linked) => Ok(respond::ok(linked)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+
+    /// Entries of Left linked to the Right entry with id
+    async fn get_left(db: Data<Db>, id: Path<Id>) -> actix_web::Result<HttpResponse> {
+        match L::linked_to_right(&db.pool, id.into_inner()).await {
+            Ok(linked) => Ok(respond::ok(linked)),
+            Err(e) => Ok(respond::err(e)),
+        }
+    }
+
+    async fn add_right(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<LinkBody>) -> actix_web::Result<HttpResponse> {
+        let (id, LinkBody { id: other, link }) = (id.into_inner(), body.into_inner());
+        let res = db.transaction_as(actor(&user), |tx| <L::Left as LinkedTo<L::Right>>::add_link(tx, id, other, link)).await;
+        Ok(link_response(res))
+    }
+
+    async fn add_left(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<LinkBody>) -> actix_web::Result<HttpResponse> {
+        let (id, LinkBody { id: other, link }) = (id.into_inner(), body.into_inner());
+        let res = db.transaction_as(actor(&user), |tx| <L::Right as LinkedTo<L::Left>>::add_link(tx, id, other, link)).await;
+        Ok(link_response(res))
+    }
+
+    /// Create a Right entry and link it to the Left entry with id
+    async fn create_right(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<NewLinkBody<L::Right>>) -> actix_web::Result<HttpResponse> {
+        let (id, NewLinkBody { entry, link }) = (id.into_inner(), body.into_inner());
+        let res = db.transaction_as(actor(&user), |tx| <L::Left as LinkedTo<L::Right>>::create_linked(tx, id, entry, link)).await;
+        Ok(link_response(res.map(|(link, entry)| LinkedEntry { link, entry })))
+    }
+
+    async fn create_left(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, body: Json<NewLinkBody<L::Left>>) -> actix_web::Result<HttpResponse> {
+        let (id, NewLinkBody { entry, link }) = (id.into_inner(), body.into_inner());
+        let res = db.transaction_as(actor(&user), |tx| <L::Right as LinkedTo<L::Left>>::create_linked(tx, id, entry, link)).await;
+        Ok(link_response(res.map(|(link, entry)| LinkedEntry { link, entry })))
+    }
+
+    async fn remove_right(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, query: Query<UnlinkQuery>) -> actix_web::Result<HttpResponse> {
+        Ok(Self::unlink(&db, &user, Side::Left, id.into_inner(), query.into_inner()).await)
+    }
+
+    async fn remove_left(db: Data<Db>, user: Option<EncodedUser>, id: Path<Id>, query: Query<UnlinkQuery>) -> actix_web::Result<HttpResponse> {
+        Ok(Self::unlink(&db, &user, Side::Right, id.into_inner(), query.into_inner()).await)
+    }
+
+    /// Delete the links of the entry with id on side, either those to query.id or with
+    ///     query.all every one of them
+    async fn unlink(db: &Db, user: &Option<EncodedUser>, side: Side, id: Id, query: UnlinkQuery) -> HttpResponse {
+        let (this_col, other_col) = match side {
+            Side::Left => (L::Left::id_str(), L::Right::id_str()),
+            Side::Right => (L::Right::id_str(), L::Left::id_str()),
+        };
+        let sql = match query {
+            UnlinkQuery { id: Some(other), .. } => Sql::<L>::delete().eq(&this_col, id).eq(&other_col, other),
+            UnlinkQuery { id: None, all: true } => Sql::<L>::delete().eq(&this_col, id),
+            UnlinkQuery { id: None, all: false } => return respond::bad_request()
+                .body("Give the id of the entry to unlink, or all=true to unlink every entry"),
+        };
+        let res = db.transaction_as(actor(user), |tx| Box::pin(async move {
+            let links = sql.fetch_all(tx).await?;
+            event::defer_all(links.iter().cloned().map(ModelEvent::Deleted));
+            Ok::<_, sqlx::Error>(links)
+        })).await;
+        match res {
+            Ok(links) => respond::ok(links),
+            Err(e) => respond::err(e),
+        }
+    }
+}
+
+impl<L> HttpServiceFactory for LinkRoutes<L>
+where
+    for<'a> L: 'static + Linked + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+    for<'a> L::Left: 'static + LinkedTo<L::Right, LinkModel = L> + Clone + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+    for<'a> L::Right: 'static + LinkedTo<L::Left, LinkModel = L> + Clone + std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>,
+{
+    fn register(self, config: &mut AppService) {
+        web::resource(Self::left_path())
+            .route(web::get().to(Self::get_right))
+            .route(web::post().to(Self::add_right))
+            .route(web::delete().to(Self::remove_right))
+            .register(config);
+        web::resource(Self::right_path())
+            .route(web::get().to(Self::get_left))
+            .route(web::post().to(Self::add_left))
+            .route(web::delete().to(Self::remove_left))
+            .register(config);
+        web::resource(format!("{}/new", Self::left_path()))
+            .route(web::post().to(Self::create_right))
+            .register(config);
+        web::resource(format!("{}/new", Self::right_path()))
+            .route(web::post().to(Self::create_left))
+            .register(config);
+        web::resource(format!("{}/reachable", Self::left_path()))
+            .route(web::get().to(Self::get_reachable_right))
+            .register(config);
+        web::resource(format!("{}/reachable", Self::right_path()))
+            .route(web::get().to(Self::get_reachable_left))
+            .register(config);
+        web::resource(format!("{}/{{other_id}}/path", Self::left_path()))
+            .route(web::get().to(Self::get_path_from_left))
+            .register(config);
+        web::resource(format!("{}/{{other_id}}/path", Self::right_path()))
+            .route(web::get().to(Self::get_path_from_right))
+            .register(config);
+    }
+}
+
+/// A missing entry or Link fails the join row's foreign keys, and an entry rejected by
+///     Linked::after_link() fails with a Decode error. Either rolls the transaction back
+fn link_response<T>(res: sqlx::Result<T>) -> HttpResponse
+where
+    for<'a> T: std::fmt::Debug + PartialEq + Serialize + Deserialize<'a>
+{
+    match res {
+        Ok(linked) => respond::ok(linked),
+        Err(sqlx::Error::Database(e)) if e.code().as_deref() == Some("23503") => respond::not_found("NO ENTRY OR LINK WITH THAT ID"),
+        Err(sqlx::Error::Decode(e)) => respond::bad_request().body(e.to_string()),
+        Err(e) => respond::err(e),
+    }
+}
+
+/// The path segment for M, its table name in the singular -- see ModelRoutes::path()
+fn segment<M: Model>() -> String {
+    let mut table = M::table();
+    table.pop();
+    table
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::record::RecordItem;
+
+    #[test]
+    fn mounts_both_directions() {
+        assert_eq!(LinkRoutes::<RecordItem>::left_path(), "/record/{id}/item");
+        assert_eq!(LinkRoutes::<RecordItem>::right_path(), "/item/{id}/record");
+    }
+}
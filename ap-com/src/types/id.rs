@@ -2,11 +2,11 @@ use std::{ops, fmt, convert::{TryFrom, TryInto}};
 use serde::{Serialize, Deserialize};
 use sqlx::{Type, PgPool};
 use uuid::Uuid;
-use crate::error::DiLibError;
+use crate::{error::DiLibError, query::Ident};
 
         // PartialEq, Debug, Clone, Display, AsRef, AsMut)]
 
-#[derive(Type,Clone, Debug, Serialize, Deserialize, PartialEq, PartialOrd)]
+#[derive(Type,Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd)]
 #[sqlx(transparent, type_name = "id")]
 pub struct Id(String);
 
@@ -45,7 +45,9 @@ impl Id {
     }
 
     pub async fn verify_existence(self, db: &PgPool, table: &str) -> anyhow::Result<bool> {
-        let res = format!("SELECT * FROM {} WHERE id = $1", table);
+        let table = Ident::new(table)
+            .ok_or_else(|| anyhow::anyhow!("Invalid table name {}", table))?;
+        let res = format!("SELECT 1 FROM {} WHERE \"id\" = $1", table);
         let out = sqlx::query(res.as_str())
             .bind(self.get())
             .fetch_optional(db).await?;
@@ -52,7 +52,7 @@ pub enum Status {
 }
 
 #[derive(sqlx::Type, Debug, Clone, Serialize, Deserialize, PartialEq)]
-#[sqlx(type_name = "status", rename_all = "lowercase")]
+#[sqlx(type_name = "gender", rename_all = "lowercase")]
 pub enum Gender {
     Male, Female, Other, PreferNotToSay,
 }
@@ -101,6 +101,20 @@ impl Token for AccessToken {
     }
 }
 
+#[async_trait::async_trait]
+impl Token for RefreshToken {
+
+    #[inline]
+    fn new(token: String) -> Self {
+        Self(token)
+    }
+
+    #[inline]
+    fn get(self) -> String {
+        self.0
+    }
+}
+
 impl AccessToken {
 
     pub fn user_from_id(user_id: Id, session_id: Id, exp: Expiration) -> anyhow::Result<Self> {
@@ -1,4 +1,8 @@
 #[actix_web::main]
-async fn main() -> std::io::Result<()> {
-    di_api::run().await
+async fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match args.first().map(String::as_str) {
+        Some("migrate") => di_api::migrate(args.get(1).map(String::as_str)).await,
+        _ => Ok(di_api::run().await?),
+    }
 }
@@ -5,7 +5,7 @@
 //! use di_api::context::{Context, ApiConfig};
 //! ```
 
-use ap_com::Db;
+use ap_com::{Db, db::migrate};
 
 #[derive(Debug,Clone )]
 pub struct Context {
@@ -24,6 +24,15 @@ impl Context {
     pub async fn new() -> anyhow::Result<Self> {
         let config = ApiConfig::default()?;
         let db = Db::new(&config.db_url).await?;
+        if config.require_migrations {
+            let pending = migrate::pending(&db.pool).await?;
+            if !pending.is_empty() {
+                let versions = pending.iter()
+                    .map(|m| m.version.to_string())
+                    .collect::<Vec<String>>();
+                anyhow::bail!("Unapplied migrations {} -- run `di-api migrate up`", versions.join(", "));
+            }
+        }
         let redis = redis::Client::open(config.redis_url.as_str())?;
         Ok(Self { db, config, redis})
     }
@@ -36,12 +45,15 @@ pub struct ApiConfig {
     pub redis_url: String,
     pub prod: bool,
     pub host: String,
+    /// Refuse to start while the database has unapplied migrations. Set with
+    ///     REQUIRE_MIGRATIONS=true
+    pub require_migrations: bool,
 }
 
 impl ApiConfig {
 
-    pub fn with_params(port: u16, db_url: String, redis_url: String, prod: bool, host: String) -> Self {
-        Self { port, db_url, redis_url, prod, host }
+    pub fn with_params(port: u16, db_url: String, redis_url: String, prod: bool, host: String, require_migrations: bool) -> Self {
+        Self { port, db_url, redis_url, prod, host, require_migrations }
     }
 
     pub fn default() -> anyhow::Result<Self> {
@@ -57,7 +69,11 @@ impl ApiConfig {
         let redis_url: String = "redis://127.0.0.1".into();
         let host: String = "0.0.0.0".into();
         let prod = false;
-        Ok(Self { port, db_url, redis_url, prod, host })
+        let require_migrations = if let Ok(req) = std::env::var("REQUIRE_MIGRATIONS") { req
+        } else if let Ok(req) = dotenv::var("REQUIRE_MIGRATIONS") { req
+        } else { "false".to_string() };
+        let require_migrations: bool = require_migrations.trim().parse()?;
+        Ok(Self { port, db_url, redis_url, prod, host, require_migrations })
     }
 
 
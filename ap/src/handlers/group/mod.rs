@@ -41,8 +41,8 @@ pub fn individual_group_ops(cfg: &mut ServiceConfig) {
 }
 
 pub async fn new_group(db: Data<Db>, group: Json<Group>) -> impl Responder {
-    match group.into_inner().insert(&db.pool).await {
-        Ok(group) => respond::ok(group),
+    match group.into_inner().create(&db.pool).await {
+        Ok((group, _owner)) => respond::ok(group),
         Err(e) => respond::err(e),
     }
 }
@@ -30,7 +30,11 @@ use ap_com::models::{
         Model, ModelRoutes,
         User, Session, Credentials, VerificationRequest, Profile,
         Account, Topic, task::Task,
+        Record, Item, Field, Action,
+        audit,
+        record::RecordItem, item::ItemField, post::TopicPost,
 };
+use ap_com::rel::LinkRoutes;
 use crate::{db::Db, util::respond};
 
 pub fn routes(cfg: &mut ServiceConfig) {
@@ -58,12 +62,22 @@ pub fn routes(cfg: &mut ServiceConfig) {
 
 pub fn model_routes(cfg: &mut ServiceConfig) {
     cfg
+        .service(LinkRoutes::<RecordItem>::service())
+        .service(LinkRoutes::<ItemField>::service())
+        .service(LinkRoutes::<TopicPost>::service())
+        .configure(user::export::routes)
+        .configure(user::erase::routes)
         .service(User::service())
         .service(Task::service())
         .service(Session::service())
         .service(Account::service())
         .service(Profile::service())
-        .service(Credentials::service());
+        .service(Credentials::service())
+        .service(Record::service())
+        .service(Item::service())
+        .service(Field::service())
+        .service(Action::service())
+        .service(web::scope("/audit").configure(audit::routes));
 
 }
 
@@ -70,13 +70,8 @@ pub async fn get_all_posts_in_topic(db: Data<Db>, topic_id: Path<Id>) -> impl Re
     }
 }
 pub async fn new_post_in_topic(db: Data<Db>, topic_id: Path<Id>, post: Json<Post>) -> impl Responder {
-    match post.into_inner().insert(&db.pool).await {
-        Ok(p) => {
-            match p.add_topic(&db.pool, topic_id.into_inner(), None).await {
-                Ok(post_topic) => respond::ok(post_topic),
-                Err(e) => respond::err(e),
-            }
-        },
+    match post.into_inner().create_in_topic(&db.pool, topic_id.into_inner(), None).await {
+        Ok((_post, post_topic)) => respond::ok(post_topic),
         Err(e) => respond::err(e),
     }
 }
@@ -0,0 +1,33 @@
+//! /user/{user_id}/erase -- erase the user's account and data (see
+//!     ap_com::models::user::erase)
+use ap_com::{Db, Id};
+use ap_com::auth::jwt::EncodedUser;
+use ap_com::models::user::erase::erase;
+use crate::util::respond;
+use actix_web::{Responder, web::{self, Data, Path, Query, ServiceConfig}};
+use serde::Deserialize;
+
+/// Registered as a full path, like export::routes
+pub fn routes(cfg: &mut ServiceConfig) {
+    cfg.service(web::resource("/user/{user_id}/erase").route(web::post().to(erase_user)));
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EraseQuery {
+    pub dry_run: Option<bool>,
+}
+
+/// POST /user/{user_id}/erase?dry_run=true : Erase the user, responding with the signed
+///     receipt. With dry_run, responds with the rows which would be removed, unsigned.
+///     Only the user themselves or an admin may erase an account
+pub async fn erase_user(db: Data<Db>, user: EncodedUser, user_id: Path<Id>, query: Query<EraseQuery>) -> impl Responder {
+    let user_id = user_id.into_inner();
+    if user.user_id != user_id && !user.is_admin() {
+        return respond::forbidden().finish();
+    }
+    match erase(&db.pool, user_id, Some(&user.user_id), query.dry_run.unwrap_or(false)).await {
+        Ok(Some(receipt)) => respond::ok(receipt),
+        Ok(None) => respond::not_found("NO USER FOUND"),
+        Err(e) => respond::err(e),
+    }
+}
@@ -0,0 +1,92 @@
+//! /user/{user_id}/export -- start a background export of the user's data, poll it, and
+//!     download the archive once it is ready (see ap_com::models::user::export). Only the
+//!     user themselves or an admin may export an account
+use actix::{MailboxError, SystemService};
+use ap_com::{Db, Id, Model};
+use ap_com::auth::jwt::EncodedUser;
+use ap_com::models::User;
+use ap_com::models::user::export::{ExportJob, ExportStatus, GetExport, StartExport, UserExports};
+use crate::util::respond;
+use actix_web::{
+    HttpResponse, Responder, http::header,
+    web::{self, Data, Path, ServiceConfig},
+};
+
+/// Registered as full paths, like rel::LinkRoutes, so they can sit ahead of
+///     User::service() without a /user scope shadowing it
+pub fn routes(cfg: &mut ServiceConfig) {
+    cfg
+        .service(web::resource("/user/{user_id}/export")
+            .route(web::post().to(start_export)))
+        .service(web::resource("/user/{user_id}/export/{export_id}")
+            .route(web::get().to(get_export)))
+        .service(web::resource("/user/{user_id}/export/{export_id}/download")
+            .route(web::get().to(download_export)));
+}
+
+/// POST /user/{user_id}/export : Start exporting the user's data. Responds 202 with the
+///     pending job, to be polled at /user/{user_id}/export/{export_id}
+pub async fn start_export(db: Data<Db>, user: EncodedUser, user_id: Path<Id>) -> impl Responder {
+    let user_id = user_id.into_inner();
+    if !may_export(&user, &user_id) {
+        return respond::forbidden().finish();
+    }
+    match User::get(&db.pool, user_id.clone()).await {
+        Ok(Some(_)) => {},
+        Ok(None) => return respond::not_found("NO USER FOUND"),
+        Err(e) => return respond::err(e),
+    }
+    match UserExports::from_registry().send(StartExport { db: db.pool.clone(), user_id }).await {
+        Ok(job) => respond::accepted().json(job),
+        Err(e) => respond::err(e),
+    }
+}
+
+pub async fn get_export(user: EncodedUser, path: Path<(Id, Id)>) -> impl Responder {
+    let (user_id, export_id) = path.into_inner();
+    if !may_export(&user, &user_id) {
+        return respond::forbidden().finish();
+    }
+    match user_job(user_id, export_id).await {
+        Ok(Some(job)) => respond::ok(job),
+        Ok(None) => respond::not_found("NO EXPORT FOUND"),
+        Err(e) => respond::err(e),
+    }
+}
+
+/// GET /user/{user_id}/export/{export_id}/download : The zip archive. Responds 409 with
+///     the job while it is still pending, or if it failed
+pub async fn download_export(user: EncodedUser, path: Path<(Id, Id)>) -> impl Responder {
+    let (user_id, export_id) = path.into_inner();
+    if !may_export(&user, &user_id) {
+        return respond::forbidden().finish();
+    }
+    let job = match user_job(user_id, export_id).await {
+        Ok(Some(job)) => job,
+        Ok(None) => return respond::not_found("NO EXPORT FOUND"),
+        Err(e) => return respond::err(e),
+    };
+    let archive = match (job.status, job.path.clone()) {
+        (ExportStatus::Ready, Some(archive)) => archive,
+        _ => return HttpResponse::Conflict().json(job),
+    };
+    match web::block(move || std::fs::read(archive)).await {
+        Ok(Ok(bytes)) => HttpResponse::Ok()
+            .content_type("application/zip")
+            .insert_header((header::CONTENT_DISPOSITION,
+                format!("attachment; filename=\"user-export-{}.zip\"", job.id)))
+            .body(bytes),
+        Ok(Err(e)) => respond::err(e),
+        Err(e) => respond::err(e),
+    }
+}
+
+fn may_export(user: &EncodedUser, user_id: &Id) -> bool {
+    &user.user_id == user_id || user.is_admin()
+}
+
+/// The export job with this id, if it belongs to the user
+async fn user_job(user_id: Id, export_id: Id) -> Result<Option<ExportJob>, MailboxError> {
+    let job = UserExports::from_registry().send(GetExport(export_id)).await?;
+    Ok(job.filter(|job| job.user_id == user_id))
+}
@@ -6,6 +6,8 @@ pub mod account;
 pub mod session;
 pub mod credentials;
 pub mod link;
+pub mod export;
+pub mod erase;
 
 use ap_com::{Id, Model, Db};
 use crate::util::respond;
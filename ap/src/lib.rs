@@ -12,6 +12,8 @@ pub mod handlers;
 pub mod models;
 
 pub use ap_com::db;
+use ap_com::{Db, db::migrate as migrations};
+use context::ApiConfig;
 pub use api::Api;
 pub use error::{ApiError, ApiResult};
 
@@ -23,3 +25,24 @@ pub async fn run() -> std::io::Result<()> {
     Ok(())
 }
 
+/// Run `di-api migrate <up|down|status>` against DATABASE_URL
+pub async fn migrate(cmd: Option<&str>) -> anyhow::Result<()> {
+    let config = ApiConfig::default()?;
+    let db = Db::new(&config.db_url).await?;
+    match cmd {
+        Some("up") => {
+            migrations::up(&db.pool).await?;
+            println!("Database is up to date");
+        },
+        Some("down") => match migrations::down(&db.pool).await? {
+            Some(version) => println!("Reverted migration {}", version),
+            None => println!("No migrations to revert"),
+        },
+        Some("status") => for m in migrations::status(&db.pool).await? {
+            let state = if m.applied { "applied" } else { "pending" };
+            println!("{:<16}{:<10}{}", m.version, state, m.description);
+        },
+        _ => anyhow::bail!("Usage: di-api migrate <up|down|status>"),
+    }
+    Ok(())
+}